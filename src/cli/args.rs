@@ -1,21 +1,147 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use encoding_rs::Encoding;
 use url::Url;
 use anyhow::Result;
 
+use super::input::{parse_input, Input};
+use super::sitemap::parse_lastmod;
+use crate::converter::options::ConverterArgs;
+use crate::http::cache::CacheArgs;
+use crate::http::charset::parse_encoding;
+use crate::http::{FetchArgs, ServerArgs};
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// URL to convert to markdown
-    #[arg(value_parser = parse_url)]
-    pub url: Option<Url>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// URLs, file paths or '-' for standard input to convert to markdown
+    #[arg(value_name = "INPUT", value_parser = parse_input)]
+    pub inputs: Vec<Input>,
+
+    /// File listing inputs to convert, one per line
+    #[arg(short, long, value_name = "PATH")]
+    pub input_file: Option<PathBuf>,
 
-    /// Output file path
+    /// Directory for converted pages, required when converting several inputs
+    #[arg(short = 'd', long, value_name = "DIR", conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Number of pages converted at the same time in batch mode
+    #[arg(short = 'j', long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: u16,
+
+    /// URL used to resolve relative links, instead of the page URL
+    #[arg(long, value_parser = parse_url)]
+    pub base_url: Option<Url>,
+
+    /// Output file path for a single input
     #[arg(short, long)]
     pub output: Option<String>,
 
-    /// Port number for the server
-    #[arg(short = 'P', long, default_value_t = 3000)]
-    pub port: u16,
+    #[command(flatten)]
+    pub converter: ConverterArgs,
+
+    #[command(flatten)]
+    pub fetch: FetchArgs,
+
+    #[command(flatten)]
+    pub cache: CacheArgs,
+
+    /// Configuration file [default: ~/.config/url2md/config.toml]
+    #[arg(long, env = "URL2MD_CONFIG", value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Convert the page even if the server answers with a non-2xx status
+    #[arg(long, global = true)]
+    pub allow_error_status: bool,
+
+    /// Decode the page with this encoding (e.g. shift_jis, gbk, windows-1251)
+    /// instead of detecting it
+    #[arg(long, value_parser = parse_encoding, global = true)]
+    pub encoding: Option<&'static Encoding>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Mirror a site as Markdown files by following links from a start page
+    Crawl(CrawlArgs),
+    /// Convert the pages listed in a site's sitemap
+    Sitemap(SitemapArgs),
+    /// Run the HTTP API server
+    Serve(ServerArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CrawlArgs {
+    /// Page to start crawling from; only links to the same origin are followed
+    #[arg(value_parser = parse_url)]
+    pub url: Url,
+
+    /// Directory the Markdown mirror is written to
+    #[arg(short = 'd', long, value_name = "DIR")]
+    pub output_dir: PathBuf,
+
+    /// Follow links at most this many steps from the start page
+    #[arg(long, default_value_t = 3)]
+    pub max_depth: usize,
+
+    /// Stop after converting this many pages
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_pages: u64,
+
+    /// Only follow URLs matching this regular expression (repeatable)
+    #[arg(long = "include-url", value_name = "REGEX")]
+    pub include_urls: Vec<String>,
+
+    /// Never follow URLs matching this regular expression (repeatable)
+    #[arg(long = "exclude-url", value_name = "REGEX")]
+    pub exclude_urls: Vec<String>,
+
+    /// Number of pages fetched at the same time
+    #[arg(short = 'j', long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: u16,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SitemapArgs {
+    /// Sitemap or sitemap index, optionally gzipped; for a site root the
+    /// sitemaps listed in robots.txt or /sitemap.xml are used
+    #[arg(value_parser = parse_url)]
+    pub url: Url,
+
+    /// Directory for converted pages
+    #[arg(short = 'd', long, value_name = "DIR")]
+    pub output_dir: PathBuf,
+
+    /// Only convert URLs matching this regular expression (repeatable)
+    #[arg(long = "include-url", value_name = "REGEX")]
+    pub include_urls: Vec<String>,
+
+    /// Skip URLs matching this regular expression (repeatable)
+    #[arg(long = "exclude-url", value_name = "REGEX")]
+    pub exclude_urls: Vec<String>,
+
+    /// Only convert pages modified on or after this date (e.g. 2024-01-31
+    /// or 2024-01-31T12:00:00Z); pages without a lastmod are kept
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub since: Option<DateTime<Utc>>,
+
+    /// Convert at most this many pages
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_pages: Option<u64>,
+
+    /// Number of pages converted at the same time
+    #[arg(short = 'j', long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: u16,
+}
+
+fn parse_date(date: &str) -> Result<DateTime<Utc>, String> {
+    parse_lastmod(date).ok_or_else(|| "expected a date such as 2024-01-31 or 2024-01-31T12:00:00Z".to_string())
 }
 
 pub fn parse_url(url: &str) -> Result<Url, String> {
@@ -1,53 +1,200 @@
-#[cfg(test)]
-mod tests {
-    use crate::cli::args::{Args, parse_url};
-    use clap::Parser;
-
-    #[test]
-    fn test_parse_url_valid() {
-        let url = "https://example.com";
-        let result = parse_url(url);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap().as_str(), "https://example.com/");
-    }
+use crate::cli::args::{Args, Command, parse_url};
+use crate::http::ServerArgs;
+use crate::cli::input::Input;
+use url::Url;
+use crate::converter::markdown_converter::ExtractMode;
+use clap::Parser;
 
-    #[test]
-    fn test_parse_url_invalid() {
-        let url = "not a url";
-        let result = parse_url(url);
-        assert!(result.is_err());
+fn serve_args(args: Args) -> ServerArgs {
+    match args.command {
+        Some(Command::Serve(server)) => server,
+        command => panic!("expected serve, got {:?}", command),
     }
+}
 
-    #[test]
-    fn test_args_default_port() {
-        let args = Args::parse_from(&["program"]);
-        assert_eq!(args.port, 3000);
-        assert!(args.url.is_none());
-        assert!(args.output.is_none());
-    }
+#[test]
+fn test_parse_url_valid() {
+    let url = "https://example.com";
+    let result = parse_url(url);
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap().as_str(), "https://example.com/");
+}
 
-    #[test]
-    fn test_args_with_url() {
-        let url = "https://example.com";
-        let args = Args::parse_from(&["program", url]);
-        assert_eq!(args.port, 3000);
-        assert_eq!(args.url.unwrap().as_str(), "https://example.com/");
-        assert!(args.output.is_none());
-    }
+#[test]
+fn test_parse_url_invalid() {
+    let url = "not a url";
+    let result = parse_url(url);
+    assert!(result.is_err());
+}
 
-    #[test]
-    fn test_args_with_output() {
-        let args = Args::parse_from(&["program", "-o", "output.md"]);
-        assert_eq!(args.port, 3000);
-        assert!(args.url.is_none());
-        assert_eq!(args.output.unwrap(), "output.md");
-    }
+#[test]
+fn test_args_default_port() {
+    let args = Args::parse_from(["program"]);
+    assert!(args.command.is_none());
+    assert!(args.inputs.is_empty());
+    assert!(args.output.is_none());
 
-    #[test]
-    fn test_args_with_custom_port() {
-        let args = Args::parse_from(&["program", "-P", "8080"]);
-        assert_eq!(args.port, 8080);
-        assert!(args.url.is_none());
-        assert!(args.output.is_none());
-    }
+    let server = serve_args(Args::parse_from(["program", "serve"]));
+    assert_eq!(server.port, None);
+    assert_eq!(server.to_serve_config().unwrap().addr, "127.0.0.1:3000".parse().unwrap());
+}
+
+#[test]
+fn test_args_with_url() {
+    let url = "https://example.com";
+    let args = Args::parse_from(["program", url]);
+    assert!(args.command.is_none());
+    assert_eq!(args.inputs, [Input::Url(Url::parse("https://example.com/").unwrap())]);
+    assert!(args.output.is_none());
+}
+
+#[test]
+fn test_args_with_output() {
+    let args = Args::parse_from(["program", "-o", "output.md"]);
+    assert!(args.command.is_none());
+    assert!(args.inputs.is_empty());
+    assert_eq!(args.output.unwrap(), "output.md");
+}
+
+#[test]
+fn test_args_with_custom_port() {
+    let args = Args::parse_from(["program", "serve", "-P", "8080"]);
+    assert!(args.inputs.is_empty());
+    assert!(args.output.is_none());
+    assert_eq!(serve_args(args).port, Some(8080));
+
+    // The port belongs to the server
+    assert!(Args::try_parse_from(["program", "-P", "8080"]).is_err());
+}
+
+#[test]
+fn test_args_extract_mode() {
+    let args = Args::parse_from(["program"]);
+    assert_eq!(args.converter.extract, ExtractMode::Selector);
+
+    let args = Args::parse_from(["program", "--extract", "readability", "https://example.com"]);
+    assert_eq!(args.converter.extract, ExtractMode::Readability);
+
+    assert!(Args::try_parse_from(["program", "--extract", "bogus"]).is_err());
+}
+
+#[test]
+fn test_args_encoding() {
+    let args = Args::parse_from(["program", "https://example.com"]);
+    assert!(args.encoding.is_none());
+
+    let args = Args::parse_from(["program", "--encoding", "shift_jis", "https://example.com"]);
+    assert_eq!(args.encoding, Some(encoding_rs::SHIFT_JIS));
+
+    assert!(Args::try_parse_from(["program", "--encoding", "klingon"]).is_err());
+}
+
+#[test]
+fn test_args_fetch_options() {
+    let args = Args::parse_from([
+        "program", "-H", "Accept-Language: de", "--header", "X-Token: 1",
+        "--timeout", "5", "--max-redirects", "2", "--insecure", "https://example.com",
+    ]);
+    assert_eq!(args.fetch.headers, ["Accept-Language: de", "X-Token: 1"]);
+    assert_eq!(args.fetch.timeout, Some(5.0));
+    assert_eq!(args.fetch.max_redirects, Some(2));
+    assert!(args.fetch.insecure);
+}
+
+#[test]
+fn test_args_cache_options() {
+    let args = Args::parse_from(["program", "--no-cache", "--cache-ttl", "60", "https://example.com"]);
+    assert!(args.cache.no_cache);
+    assert_eq!(args.cache.cache_ttl, Some(60.0));
+
+    let args = Args::parse_from(["program", "crawl", "https://example.com", "-d", "out", "--cache-dir", "/tmp/cache"]);
+    assert_eq!(args.cache.cache_dir.as_deref(), Some(std::path::Path::new("/tmp/cache")));
+}
+
+#[test]
+fn test_args_server_options() {
+    let args = Args::parse_from([
+        "program", "serve", "--api-key", "one,two", "--rate-limit", "60", "--ip-rate-limit", "120",
+        "--cors-origin", "https://app.example.com", "--bind", "0.0.0.0", "--tls-cert", "cert.pem",
+        "--tls-key", "key.pem", "--shutdown-timeout", "5", "--no-cache",
+    ]);
+    assert!(args.cache.no_cache);
+    let server = serve_args(args);
+    assert_eq!(server.api_keys, ["one", "two"]);
+    assert_eq!(server.rate_limit, Some(60));
+    assert_eq!(server.ip_rate_limit, Some(120));
+    assert_eq!(server.cors_origins, ["https://app.example.com"]);
+    assert_eq!(server.bind, Some("0.0.0.0".parse().unwrap()));
+    assert_eq!(server.shutdown_timeout, Some(5.0));
+
+    // A certificate is useless without its key
+    assert!(Args::try_parse_from(["program", "serve", "--tls-cert", "cert.pem"]).is_err());
+}
+
+#[test]
+fn test_args_stdin_with_base_url() {
+    let args = Args::parse_from(["program", "--base-url", "https://example.com/docs/", "-"]);
+    assert_eq!(args.inputs, [Input::Stdin]);
+    assert_eq!(args.base_url.unwrap().as_str(), "https://example.com/docs/");
+
+    assert!(Args::try_parse_from(["program", "--base-url", "docs/", "-"]).is_err());
+}
+
+#[test]
+fn test_args_batch() {
+    let args = Args::parse_from(["program", "https://example.com/a", "https://example.com/b", "-d", "out", "-j", "8"]);
+    assert_eq!(args.inputs.len(), 2);
+    assert_eq!(args.output_dir.unwrap().to_str(), Some("out"));
+    assert_eq!(args.concurrency, 8);
+
+    let args = Args::parse_from(["program", "--input-file", "urls.txt", "--output-dir", "out"]);
+    assert!(args.inputs.is_empty());
+    assert_eq!(args.input_file.unwrap().to_str(), Some("urls.txt"));
+    assert_eq!(args.concurrency, 4);
+
+    assert!(Args::try_parse_from(["program", "-j", "0", "-d", "out"]).is_err());
+    assert!(Args::try_parse_from(["program", "-o", "a.md", "-d", "out"]).is_err());
+}
+
+#[test]
+fn test_args_crawl() {
+    use crate::cli::args::Command;
+
+    let args = Args::parse_from([
+        "program", "--timeout", "5", "crawl", "https://example.com/docs/", "-d", "mirror",
+        "--max-depth", "2", "--exclude-url", "/blog/", "--link-style", "reference",
+    ]);
+    let Some(Command::Crawl(crawl)) = args.command else {
+        panic!("expected the crawl subcommand");
+    };
+    assert_eq!(crawl.url.as_str(), "https://example.com/docs/");
+    assert_eq!(crawl.output_dir.to_str(), Some("mirror"));
+    assert_eq!(crawl.max_depth, 2);
+    assert_eq!(crawl.max_pages, 100);
+    assert_eq!(crawl.exclude_urls, ["/blog/"]);
+    // Converter and fetch options are accepted before or after the subcommand
+    assert_eq!(args.fetch.timeout, Some(5.0));
+    assert!(args.converter.link_style.is_some());
+
+    assert!(Args::try_parse_from(["program", "crawl", "https://example.com/"]).is_err());
+}
+
+#[test]
+fn test_args_sitemap() {
+    use crate::cli::args::Command;
+
+    let args = Args::parse_from([
+        "program", "sitemap", "https://example.com/sitemap.xml.gz", "-d", "pages",
+        "--include-url", "/docs/", "--since", "2024-01-31", "--max-pages", "50",
+    ]);
+    let Some(Command::Sitemap(sitemap)) = args.command else {
+        panic!("expected the sitemap subcommand");
+    };
+    assert_eq!(sitemap.url.as_str(), "https://example.com/sitemap.xml.gz");
+    assert_eq!(sitemap.include_urls, ["/docs/"]);
+    assert_eq!(sitemap.since.unwrap().to_rfc3339(), "2024-01-31T00:00:00+00:00");
+    assert_eq!(sitemap.max_pages, Some(50));
+    assert_eq!(sitemap.concurrency, 4);
+
+    assert!(Args::try_parse_from(["program", "sitemap", "https://example.com/", "-d", "out", "--since", "soon"]).is_err());
 }
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use encoding_rs::Encoding;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+use url::Url;
+
+use super::input::{parse_input, Input};
+use crate::converter::markdown_converter::MarkdownConverter;
+use crate::converter::options::ConverterOptions;
+use crate::http::cache::{fetch_converted, Cache};
+use crate::http::{save_to_file, FetchedHtml, Fetcher};
+
+/// Longest file name stem derived from a URL, in bytes.
+const MAX_FILE_STEM: usize = 200;
+
+/// Everything needed to turn an [`Input`] into Markdown. Cheap to clone, so
+/// each concurrent conversion gets its own copy.
+#[derive(Clone)]
+pub struct Conversion {
+    pub fetcher: Fetcher,
+    pub options: ConverterOptions,
+    /// Overrides the page URL when resolving relative links.
+    pub base_url: Option<Url>,
+    /// Encoding for local input; fetched pages use the fetcher's setting.
+    pub encoding: Option<&'static Encoding>,
+    /// Converted pages, reused while fresh or unchanged.
+    pub cache: Option<Arc<Cache>>,
+}
+
+impl Conversion {
+    pub async fn convert(&self, input: &Input) -> Result<String> {
+        if let Input::Url(url) = input {
+            let variant = format!("markdown {:?} {:?} {:?}", self.options, self.base_url, self.encoding);
+            // Relative links resolve against where the page ended up
+            let convert = |page: &FetchedHtml| self.convert_html(&page.html, Some(&page.url));
+            let (page, _) = fetch_converted(self.cache.as_deref(), &self.fetcher, url, &variant, convert).await?;
+            return Ok(page.content);
+        }
+        let html = input.read_html(&self.fetcher, self.encoding).await?;
+        self.convert_html(&html, input.source_url())
+    }
+
+    fn convert_html(&self, html: &str, source_url: Option<&Url>) -> Result<String> {
+        let mut converter = MarkdownConverter::with_options(self.options.clone());
+        if let Some(base_url) = self.base_url.clone().or_else(|| source_url.cloned()) {
+            converter = converter.with_source_url(base_url);
+        }
+        converter.convert(html)
+    }
+
+    /// Converts every input, at most `concurrency` at a time, writing each
+    /// result into `output_dir` under a name derived from its URL or path.
+    pub async fn run_batch(&self, inputs: Vec<Input>, output_dir: &Path, concurrency: usize) -> Result<BatchSummary> {
+        if inputs.contains(&Input::Stdin) {
+            bail!("standard input cannot be converted in batch mode");
+        }
+        tokio::fs::create_dir_all(output_dir)
+            .await
+            .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+        let mut summary = BatchSummary { total: inputs.len(), ..BatchSummary::default() };
+        let mut jobs = assign_file_names(inputs).into_iter();
+        let mut tasks = JoinSet::new();
+        loop {
+            while tasks.len() < concurrency.max(1) {
+                let Some((input, file_name)) = jobs.next() else {
+                    break;
+                };
+                let conversion = self.clone();
+                let path = output_dir.join(file_name);
+                tasks.spawn(async move {
+                    let result = conversion.convert_to_file(&input, &path).await.map(|_| path);
+                    (input.to_string(), result)
+                });
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            let (input, result) = joined.unwrap_or_else(|e| ("unknown input".to_string(), Err(anyhow!(e))));
+            match result {
+                Ok(path) => {
+                    info!("Converted {} to {}", input, path.display());
+                    summary.succeeded += 1;
+                },
+                Err(e) => {
+                    error!("Failed to convert {}: {:#}", input, e);
+                    summary.failures.push((input, e));
+                },
+            }
+        }
+        Ok(summary)
+    }
+
+    async fn convert_to_file(&self, input: &Input, path: &Path) -> Result<()> {
+        let markdown = self.convert(input).await?;
+        save_to_file(&markdown, &path.to_string_lossy()).await
+    }
+}
+
+/// The outcome of [`Conversion::run_batch`].
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    /// Each failed input with its error, in completion order.
+    pub failures: Vec<(String, anyhow::Error)>,
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Converted {} of {} pages", self.succeeded, self.total)?;
+        if !self.failures.is_empty() {
+            write!(f, ", {} failed:", self.failures.len())?;
+            for (input, error) in &self.failures {
+                write!(f, "\n  {}: {:#}", input, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a list of inputs, one per line. Blank lines and lines starting
+/// with `#` are ignored.
+pub fn parse_input_list(contents: &str) -> Result<Vec<Input>> {
+    contents.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| parse_input(line).map_err(|e| anyhow!("line {}: {}", number, e)))
+        .collect()
+}
+
+pub async fn read_input_list(path: &Path) -> Result<Vec<Input>> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse_input_list(&contents).with_context(|| format!("invalid input file {}", path.display()))
+}
+
+/// Pairs each input with a distinct output file name, numbering repeats
+/// (`page.md`, `page-2.md`, ...) past any name already taken.
+fn assign_file_names(inputs: Vec<Input>) -> Vec<(Input, PathBuf)> {
+    let mut taken = HashSet::new();
+    inputs.into_iter()
+        .map(|input| {
+            let stem = file_stem(&input);
+            let mut file_name = format!("{}.md", stem);
+            let mut count = 1;
+            while !taken.insert(file_name.clone()) {
+                count += 1;
+                file_name = format!("{}-{}.md", stem, count);
+            }
+            (input, PathBuf::from(file_name))
+        })
+        .collect()
+}
+
+/// A file name stem for `input`: host, path and query for URLs
+/// (`example.com_docs_intro`), the file stem for local files.
+pub fn file_stem(input: &Input) -> String {
+    let raw = match input {
+        Input::Url(url) => {
+            let mut raw = url.host_str().unwrap_or("page").to_string();
+            let path = url.path().trim_matches('/');
+            let path = path.strip_suffix(".html")
+                .or_else(|| path.strip_suffix(".htm"))
+                .unwrap_or(path);
+            for part in [Some(path), url.query()].into_iter().flatten().filter(|part| !part.is_empty()) {
+                raw.push('_');
+                raw.push_str(part);
+            }
+            raw
+        },
+        Input::File(path) => path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        Input::Stdin => "stdin".to_string(),
+    };
+    sanitize_file_stem(&raw)
+}
+
+/// Keeps ASCII letters, digits, `.` and `-`, turning runs of anything else
+/// into a single `_`.
+pub(super) fn sanitize_file_stem(raw: &str) -> String {
+    let mut stem = String::new();
+    for c in raw.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+            stem.push(c);
+        } else if !stem.ends_with('_') {
+            stem.push('_');
+        }
+    }
+    stem.truncate(MAX_FILE_STEM);
+    let stem = stem.trim_matches(|c| c == '_' || c == '.');
+    if stem.is_empty() {
+        "page".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+#[cfg(test)]
+#[path = "batch_test.rs"]
+mod tests;
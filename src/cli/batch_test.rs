@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use url::Url;
+
+use crate::cli::batch::{assign_file_names, file_stem, parse_input_list, Conversion};
+use crate::cli::input::Input;
+use crate::converter::options::ConverterOptions;
+use crate::http::{FetchConfig, Fetcher, RetryPolicy};
+
+fn url(url: &str) -> Input {
+    Input::Url(Url::parse(url).unwrap())
+}
+
+#[test]
+fn test_file_stem() {
+    assert_eq!(file_stem(&url("https://example.com/")), "example.com");
+    assert_eq!(file_stem(&url("https://example.com/docs/intro/")), "example.com_docs_intro");
+    assert_eq!(file_stem(&url("https://example.com/blog/post.html")), "example.com_blog_post");
+    assert_eq!(file_stem(&url("https://example.com/search?q=rust&page=2")), "example.com_search_q_rust_page_2");
+    assert_eq!(file_stem(&url("https://example.com/caf%C3%A9")), "example.com_caf_C3_A9");
+    assert_eq!(file_stem(&Input::File(PathBuf::from("/tmp/saved page.html"))), "saved_page");
+    assert_eq!(file_stem(&Input::Stdin), "stdin");
+
+    let long = format!("https://example.com/{}", "a".repeat(500));
+    assert_eq!(file_stem(&url(&long)).len(), 200);
+}
+
+#[test]
+fn test_assign_file_names() {
+    let names: Vec<PathBuf> = assign_file_names(vec![
+        url("https://example.com/a"),
+        url("https://example.com/a/"),
+        url("https://example.com/b"),
+        url("https://example.com/a.html"),
+    ])
+    .into_iter()
+    .map(|(_, name)| name)
+    .collect();
+    assert_eq!(names, [
+        PathBuf::from("example.com_a.md"),
+        PathBuf::from("example.com_a-2.md"),
+        PathBuf::from("example.com_b.md"),
+        PathBuf::from("example.com_a-3.md"),
+    ]);
+}
+
+#[test]
+fn test_assign_file_names_skips_taken_names() {
+    let names: Vec<PathBuf> = assign_file_names(vec![
+        url("https://example.com/a"),
+        url("https://example.com/a.html"),
+        url("https://example.com/a-2"),
+        url("https://example.com/a/"),
+    ])
+    .into_iter()
+    .map(|(_, name)| name)
+    .collect();
+    assert_eq!(names, [
+        PathBuf::from("example.com_a.md"),
+        PathBuf::from("example.com_a-2.md"),
+        PathBuf::from("example.com_a-2-2.md"),
+        PathBuf::from("example.com_a-3.md"),
+    ]);
+}
+
+#[test]
+fn test_parse_input_list() {
+    let inputs = parse_input_list("# pages to convert\nhttps://example.com/a\n\n  https://example.com/b  \n").unwrap();
+    assert_eq!(inputs, [url("https://example.com/a"), url("https://example.com/b")]);
+
+    let error = parse_input_list("https://example.com/a\nnot a url\n").unwrap_err();
+    assert!(error.to_string().starts_with("line 2:"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_run_batch() {
+    let mut server = mockito::Server::new_async().await;
+    for path in ["/one", "/two"] {
+        server.mock("GET", path)
+            .with_header("content-type", "text/html")
+            .with_body(format!("<html><body><main><p>Page {}</p></main></body></html>", path))
+            .create_async()
+            .await;
+    }
+    server.mock("GET", "/missing")
+        .with_status(404)
+        .create_async()
+        .await;
+    let inputs = ["one", "two", "missing"]
+        .map(|path| url(&format!("{}/{}", server.url(), path)))
+        .to_vec();
+
+    let conversion = Conversion {
+        fetcher: Fetcher::new(FetchConfig {
+            retry: RetryPolicy { max_attempts: 1, ..RetryPolicy::default() },
+            ..FetchConfig::default()
+        }).unwrap(),
+        options: ConverterOptions::default(),
+        base_url: None,
+        encoding: None,
+        cache: None,
+    };
+    let output_dir = tempfile::tempdir().unwrap();
+    let summary = conversion.run_batch(inputs, output_dir.path(), 2).await.unwrap();
+
+    assert_eq!(summary.total, 3);
+    assert_eq!(summary.succeeded, 2);
+    assert_eq!(summary.failures.len(), 1);
+    assert!(summary.failures[0].0.ends_with("/missing"));
+    assert!(summary.to_string().starts_with("Converted 2 of 3 pages, 1 failed:"));
+
+    let mut files: Vec<_> = std::fs::read_dir(output_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["127.0.0.1_one.md", "127.0.0.1_two.md"]);
+    let markdown = std::fs::read_to_string(output_dir.path().join("127.0.0.1_one.md")).unwrap();
+    assert!(markdown.contains("Page /one"));
+
+    assert!(conversion.run_batch(vec![Input::Stdin], output_dir.path(), 1).await.is_err());
+}
+
+#[tokio::test]
+async fn test_convert_resolves_links_after_redirect() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/old").with_status(301).with_header("location", "/docs/guide").create_async().await;
+    server.mock("GET", "/docs/guide")
+        .with_header("content-type", "text/html")
+        .with_body(r#"<html><body><main><p><a href="intro">Intro</a></p></main></body></html>"#)
+        .create_async()
+        .await;
+
+    let conversion = Conversion {
+        fetcher: Fetcher::default(),
+        options: ConverterOptions::builder().preamble(false).build().unwrap(),
+        base_url: None,
+        encoding: None,
+        cache: None,
+    };
+    let markdown = conversion.convert(&url(&format!("{}/old", server.url()))).await.unwrap();
+    assert!(markdown.contains(&format!("[Intro]({}/docs/intro)", server.url())), "{}", markdown);
+}
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::http::cache::CacheArgs;
+use crate::http::{FetchArgs, ServerArgs};
+
+/// Settings read from the TOML configuration file. Command line flags and
+/// environment variables take precedence over it.
+///
+/// ```toml
+/// [fetch]
+/// user_agent = "Mozilla/5.0 (compatible; url2md)"
+/// timeout = 20
+/// headers = ["Accept-Language: en"]
+///
+/// [cache]
+/// cache_ttl = 3600
+///
+/// [server]
+/// api_keys = ["change-me"]
+/// rate_limit = 60
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub fetch: FetchArgs,
+    pub cache: CacheArgs,
+    pub server: ServerArgs,
+}
+
+impl ConfigFile {
+    /// Loads `path`, or the default location if `path` is `None`. Only an
+    /// explicitly given file has to exist.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path().filter(|path| path.is_file()) {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/url2md/config.toml`, falling back to
+/// `~/.config/url2md/config.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("url2md").join("config.toml"))
+}
+
+fn xdg_dir(variable: &str, home_subdir: &str) -> Option<PathBuf> {
+    std::env::var_os(variable)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(home_subdir)))
+}
+
+#[cfg(test)]
+#[path = "config_test.rs"]
+mod tests;
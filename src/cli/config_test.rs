@@ -0,0 +1,58 @@
+use std::io::Write;
+
+use crate::cli::config::ConfigFile;
+
+#[test]
+fn test_parse_config_file() {
+    let config = ConfigFile::parse(r#"
+        [fetch]
+        user_agent = "Mozilla/5.0 (compatible; url2md)"
+        timeout = 20
+        connect_timeout = 2.5
+        headers = ["Accept-Language: en"]
+        proxy = "http://proxy.internal:3128"
+        insecure = true
+
+        [cache]
+        cache_ttl = 3600
+        cache_dir = "/var/cache/url2md"
+
+        [server]
+        bind = "0.0.0.0"
+        port = 8080
+        api_keys = ["secret"]
+        rate_limit = 60
+        cors_origins = ["https://app.example.com"]
+    "#).unwrap();
+    assert_eq!(config.fetch.user_agent.as_deref(), Some("Mozilla/5.0 (compatible; url2md)"));
+    assert_eq!(config.fetch.timeout, Some(20.0));
+    assert_eq!(config.fetch.connect_timeout, Some(2.5));
+    assert_eq!(config.fetch.headers, ["Accept-Language: en"]);
+    assert!(config.fetch.insecure);
+    assert_eq!(config.cache.cache_ttl, Some(3600.0));
+    assert_eq!(config.cache.cache_dir.as_deref(), Some(std::path::Path::new("/var/cache/url2md")));
+    assert_eq!(config.server.bind, Some(std::net::IpAddr::from([0, 0, 0, 0])));
+    assert_eq!(config.server.port, Some(8080));
+    assert_eq!(config.server.api_keys, ["secret"]);
+    assert_eq!(config.server.rate_limit, Some(60));
+    assert_eq!(config.server.cors_origins, ["https://app.example.com"]);
+
+    assert!(ConfigFile::parse("").unwrap().fetch.user_agent.is_none());
+}
+
+#[test]
+fn test_parse_config_file_rejects_unknown_keys() {
+    assert!(ConfigFile::parse("[fetch]\nuser_agnet = \"typo\"").is_err());
+    assert!(ConfigFile::parse("[fetsh]\ntimeout = 1").is_err());
+}
+
+#[test]
+fn test_load_config_file() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "[fetch]\nmax_redirects = 3").unwrap();
+    let config = ConfigFile::load(Some(file.path())).unwrap();
+    assert_eq!(config.fetch.max_redirects, Some(3));
+
+    let missing = file.path().with_extension("missing");
+    assert!(ConfigFile::load(Some(&missing)).is_err());
+}
@@ -0,0 +1,350 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+use tracing::{error, info};
+use url::Url;
+
+use super::batch::{sanitize_file_stem, Conversion};
+use crate::converter::markdown_converter::MarkdownConverter;
+use crate::converter::options::ConverterOptions;
+use crate::http::cache::fetch_converted;
+use crate::http::{save_to_file, FetchError, FetchedHtml};
+
+/// Stands for the n-th link of a converted page until the crawl knows which
+/// pages it mirrors and where.
+static LINK_MARKER: Lazy<Regex> = Lazy::new(|| Regex::new(r"url2md-crawl-link-(\d+)").unwrap());
+
+/// Limits and filters for [`Crawler::crawl`].
+#[derive(Clone, Debug)]
+pub struct CrawlScope {
+    /// Links are followed this many steps away from the start page.
+    pub max_depth: usize,
+    /// Pages fetched in total, including the start page.
+    pub max_pages: usize,
+    /// When non-empty, only URLs matching one of these are followed.
+    pub include: Vec<Regex>,
+    /// URLs matching any of these are never followed.
+    pub exclude: Vec<Regex>,
+}
+
+impl CrawlScope {
+    fn allows(&self, url: &Url) -> bool {
+        let url = url.as_str();
+        (self.include.is_empty() || self.include.iter().any(|pattern| pattern.is_match(url)))
+            && !self.exclude.iter().any(|pattern| pattern.is_match(url))
+    }
+}
+
+/// Compiles URL patterns, naming the offending pattern on error.
+pub fn parse_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns.iter()
+        .map(|pattern| Regex::new(pattern).map_err(|e| anyhow!("invalid URL pattern '{}': {}", pattern, e)))
+        .collect()
+}
+
+/// Mirrors a site as a tree of Markdown files. Pages are fetched breadth
+/// first, following the same-origin links found in their converted
+/// content; once every page is known, links to other crawled pages are
+/// pointed at their local files.
+pub struct Crawler {
+    conversion: Conversion,
+    scope: CrawlScope,
+    concurrency: usize,
+}
+
+struct FetchedPage {
+    /// The URL after redirects.
+    url: Url,
+    converted: MarkedPage,
+}
+
+/// A page converted with its links replaced by [`LINK_MARKER`]s, which is
+/// what the cache keeps for the crawl.
+#[derive(Serialize, Deserialize)]
+struct MarkedPage {
+    markdown: String,
+    /// The link targets, resolved as the converter would write them and
+    /// numbered by their markers.
+    hrefs: Vec<String>,
+}
+
+/// The outcome of [`Crawler::crawl`].
+#[derive(Debug, Default)]
+pub struct CrawlSummary {
+    /// Each page written, with its path relative to the output directory.
+    pub pages: Vec<(Url, PathBuf)>,
+    /// Linked URLs that are not HTML, disallowed by robots.txt or blocked
+    /// by the URL policy.
+    pub skipped: usize,
+    pub failures: Vec<(String, anyhow::Error)>,
+}
+
+impl fmt::Display for CrawlSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Crawled {} pages", self.pages.len())?;
+        if self.skipped > 0 {
+            write!(f, ", skipped {} links", self.skipped)?;
+        }
+        if !self.failures.is_empty() {
+            write!(f, ", {} failed:", self.failures.len())?;
+            for (url, error) in &self.failures {
+                write!(f, "\n  {}: {:#}", url, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Crawler {
+    pub fn new(conversion: Conversion, scope: CrawlScope, concurrency: usize) -> Self {
+        Self { conversion, scope, concurrency: concurrency.max(1) }
+    }
+
+    pub async fn crawl(&self, start: Url, output_dir: &Path) -> Result<CrawlSummary> {
+        let mut summary = CrawlSummary::default();
+        let pages = self.fetch_pages(without_fragment(&start), &mut summary).await;
+
+        let paths = Arc::new(assign_local_paths(pages.iter().map(|page| &page.url)));
+        for page in pages {
+            let path = paths[page.url.as_str()].clone();
+            let markdown = restore_links(&page, &path, &paths);
+            match write_page(&markdown, &output_dir.join(&path)).await {
+                Ok(()) => {
+                    info!("Converted {} to {}", page.url, path.display());
+                    summary.pages.push((page.url, path));
+                },
+                Err(e) => {
+                    error!("Failed to write {}: {:#}", page.url, e);
+                    summary.failures.push((page.url.to_string(), e));
+                },
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Fetches pages breadth first within the scope, at most `concurrency`
+    /// at a time. The site is the origin the start page ended up at after
+    /// redirects; pages redirecting elsewhere are skipped.
+    async fn fetch_pages(&self, start: Url, summary: &mut CrawlSummary) -> Vec<FetchedPage> {
+        let mut seen = HashSet::from([start.to_string()]);
+        let mut origin = None;
+        let mut queue = VecDeque::from([(start, 0)]);
+        let mut pages = Vec::new();
+        let mut tasks = JoinSet::new();
+
+        loop {
+            // Pages still being fetched count against the limit, so that no
+            // more are written than asked for, while the ones that fail
+            // make room for the next in the queue.
+            while tasks.len() < self.concurrency && pages.len() + tasks.len() < self.scope.max_pages {
+                let Some((url, depth)) = queue.pop_front() else {
+                    break;
+                };
+                let conversion = self.conversion.clone();
+                tasks.spawn(async move {
+                    let result = fetch_page(&conversion, &url).await;
+                    (url, depth, result)
+                });
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            let (requested, depth, result) = match joined {
+                Ok(joined) => joined,
+                Err(e) => {
+                    summary.failures.push(("unknown page".to_string(), anyhow!(e)));
+                    continue;
+                },
+            };
+            let (url, converted) = match result {
+                Ok(page) => page,
+                Err(e) if matches!(
+                    e.downcast_ref::<FetchError>(),
+                    Some(FetchError::NotHtml { .. } | FetchError::Disallowed { .. } | FetchError::Blocked { .. })
+                ) => {
+                    info!("Skipping {}: {}", requested, e);
+                    summary.skipped += 1;
+                    continue;
+                },
+                Err(e) => {
+                    error!("Failed to crawl {}: {:#}", requested, e);
+                    summary.failures.push((requested.to_string(), e));
+                    continue;
+                },
+            };
+
+            let url = without_fragment(&url);
+            let site = origin.get_or_insert_with(|| url.origin());
+            if url.origin() != *site {
+                info!("Skipping {}: redirected off-site to {}", requested, url);
+                summary.skipped += 1;
+                continue;
+            }
+            if url != requested && !seen.insert(url.to_string()) {
+                info!("Skipping {}: already crawled as {}", requested, url);
+                continue;
+            }
+
+            if depth < self.scope.max_depth {
+                for href in &converted.hrefs {
+                    let Ok(link) = url.join(href) else {
+                        continue;
+                    };
+                    let link = without_fragment(&link);
+                    if link.origin() == *site && self.scope.allows(&link) && seen.insert(link.to_string()) {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+            pages.push(FetchedPage { url, converted });
+        }
+        pages
+    }
+
+}
+
+/// Fetches and converts a page through the cache, returning the URL it
+/// ended up at and the converted page.
+async fn fetch_page(conversion: &Conversion, url: &Url) -> Result<(Url, MarkedPage)> {
+    let variant = format!("crawl {:?}", conversion.options);
+    let convert = |page: &FetchedHtml| {
+        let converted = mark_links(&conversion.options, page)?;
+        serde_json::to_string(&converted).context("failed to serialize the page")
+    };
+    let (page, _) = fetch_converted(conversion.cache.as_deref(), &conversion.fetcher, url, &variant, convert).await?;
+    let url = Url::parse(&page.url).context("invalid cached URL")?;
+    let converted = serde_json::from_str(&page.content).context("invalid cached page")?;
+    Ok((url, converted))
+}
+
+/// Converts a page, writing a [`LINK_MARKER`] for each distinct link.
+fn mark_links(options: &ConverterOptions, page: &FetchedHtml) -> Result<MarkedPage> {
+    let hrefs = Arc::new(Mutex::new(Vec::<String>::new()));
+    let recorded = Arc::clone(&hrefs);
+    let converter = MarkdownConverter::with_options(options.clone())
+        .with_source_url(page.url.clone())
+        .with_link_rewriter(Arc::new(move |href: &str| {
+            let mut hrefs = recorded.lock().unwrap();
+            let index = match hrefs.iter().position(|known| known == href) {
+                Some(index) => index,
+                None => {
+                    hrefs.push(href.to_string());
+                    hrefs.len() - 1
+                },
+            };
+            Some(format!("url2md-crawl-link-{}", index))
+        }));
+    let markdown = converter.convert(&page.html)?;
+    let hrefs = hrefs.lock().unwrap().clone();
+    Ok(MarkedPage { markdown, hrefs })
+}
+
+/// The Markdown of a page written to `path`, its links pointing at the
+/// local files of crawled pages and at their original targets otherwise.
+fn restore_links(page: &FetchedPage, path: &Path, paths: &HashMap<String, PathBuf>) -> String {
+    let page_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let local = |href: &str| {
+        let target = page.url.join(href).ok()?;
+        let path = paths.get(without_fragment(&target).as_str())?;
+        let mut relative = relative_path(&page_dir, path);
+        if let Some(fragment) = target.fragment() {
+            relative.push('#');
+            relative.push_str(fragment);
+        }
+        Some(relative)
+    };
+    LINK_MARKER.replace_all(&page.converted.markdown, |captures: &Captures| {
+        match captures[1].parse::<usize>().ok().and_then(|index| page.converted.hrefs.get(index)) {
+            Some(href) => local(href).unwrap_or_else(|| href.clone()),
+            None => captures[0].to_string(),
+        }
+    }).into_owned()
+}
+
+async fn write_page(markdown: &str, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    save_to_file(markdown, &path.to_string_lossy()).await
+}
+
+fn without_fragment(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url
+}
+
+/// The file for a page, mirroring its URL path: `/` becomes `index.md`,
+/// `/docs/` `docs/index.md` and `/docs/intro.html` `docs/intro.md`. A query
+/// string is folded into the file name.
+pub fn local_path(url: &Url) -> PathBuf {
+    let mut segments: Vec<String> = url.path_segments()
+        .map(|segments| segments.map(str::to_string).collect())
+        .unwrap_or_default();
+    let mut file = match segments.pop() {
+        Some(last) if !last.is_empty() => {
+            last.strip_suffix(".html")
+                .or_else(|| last.strip_suffix(".htm"))
+                .unwrap_or(&last)
+                .to_string()
+        },
+        _ => "index".to_string(),
+    };
+    if let Some(query) = url.query().filter(|query| !query.is_empty()) {
+        file = format!("{}_{}", file, query);
+    }
+
+    let mut path: PathBuf = segments.iter()
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| sanitize_file_stem(segment))
+        .collect();
+    path.push(format!("{}.md", sanitize_file_stem(&file)));
+    path
+}
+
+/// Maps each URL to a distinct [`local_path`], numbering the URLs whose
+/// paths collide (`docs/index.md`, `docs/index-2.md`, ...) such as `/a`
+/// and `/a.html`. URLs are numbered in sorted order, so the files do not
+/// depend on the order pages were fetched in.
+fn assign_local_paths<'a>(urls: impl Iterator<Item = &'a Url>) -> HashMap<String, PathBuf> {
+    let mut urls: Vec<&Url> = urls.collect();
+    urls.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    let mut taken = HashSet::new();
+    urls.into_iter()
+        .map(|url| {
+            let path = local_path(url);
+            let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            let mut candidate = path.clone();
+            let mut count = 1;
+            while !taken.insert(candidate.clone()) {
+                count += 1;
+                candidate = path.with_file_name(format!("{}-{}.md", stem, count));
+            }
+            (url.to_string(), candidate)
+        })
+        .collect()
+}
+
+/// The `/`-separated path from directory `from` to file `to`, both relative
+/// to the output directory.
+fn relative_path(from: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|part| part.as_os_str().to_string_lossy().into_owned()));
+    parts.join("/")
+}
+
+#[cfg(test)]
+#[path = "crawl_test.rs"]
+mod tests;
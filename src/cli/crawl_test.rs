@@ -0,0 +1,271 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use url::Url;
+
+use crate::cli::batch::Conversion;
+use crate::cli::crawl::{assign_local_paths, local_path, parse_patterns, relative_path, CrawlScope, Crawler};
+use crate::converter::options::ConverterOptions;
+use crate::http::cache::{Cache, CacheConfig};
+use crate::http::{FetchConfig, Fetcher, RetryPolicy};
+
+#[test]
+fn test_local_path() {
+    let path = |url: &str| local_path(&Url::parse(url).unwrap());
+    assert_eq!(path("https://example.com"), PathBuf::from("index.md"));
+    assert_eq!(path("https://example.com/docs/"), PathBuf::from("docs/index.md"));
+    assert_eq!(path("https://example.com/docs/intro"), PathBuf::from("docs/intro.md"));
+    assert_eq!(path("https://example.com/docs/api.html"), PathBuf::from("docs/api.md"));
+    assert_eq!(path("https://example.com/search?q=a%20b"), PathBuf::from("search_q_a_20b.md"));
+    assert_eq!(path("https://example.com/a//b%2F..%2Fc"), PathBuf::from("a/b_2F.._2Fc.md"));
+}
+
+#[test]
+fn test_assign_local_paths() {
+    let urls: Vec<Url> = [
+        "https://example.com/docs/index.html",
+        "https://example.com/a.html",
+        "https://example.com/docs/",
+        "https://example.com/a",
+        "https://example.com/a-2",
+        "https://example.com/s?q=a!b",
+        "https://example.com/s?q=a+b",
+    ]
+    .iter()
+    .map(|url| Url::parse(url).unwrap())
+    .collect();
+    let paths = assign_local_paths(urls.iter());
+    let path = |url: &str| paths[url].to_string_lossy().into_owned();
+    assert_eq!(path("https://example.com/a"), "a.md");
+    assert_eq!(path("https://example.com/a-2"), "a-2.md");
+    assert_eq!(path("https://example.com/a.html"), "a-3.md");
+    assert_eq!(path("https://example.com/docs/"), "docs/index.md");
+    assert_eq!(path("https://example.com/docs/index.html"), "docs/index-2.md");
+    assert_eq!(path("https://example.com/s?q=a!b"), "s_q_a_b.md");
+    assert_eq!(path("https://example.com/s?q=a+b"), "s_q_a_b-2.md");
+}
+
+#[test]
+fn test_relative_path() {
+    assert_eq!(relative_path(Path::new(""), Path::new("docs/intro.md")), "docs/intro.md");
+    assert_eq!(relative_path(Path::new("docs"), Path::new("docs/api.md")), "api.md");
+    assert_eq!(relative_path(Path::new("docs/guide"), Path::new("index.md")), "../../index.md");
+    assert_eq!(relative_path(Path::new("docs/guide"), Path::new("docs/api/index.md")), "../api/index.md");
+}
+
+#[test]
+fn test_parse_patterns() {
+    assert_eq!(parse_patterns(&["/docs/".to_string(), r"\.html$".to_string()]).unwrap().len(), 2);
+    assert!(parse_patterns(&["(".to_string()]).is_err());
+}
+
+fn page(links: &str) -> String {
+    format!("<html><body><main><p>{}</p></main></body></html>", links)
+}
+
+async fn mock_site(server: &mut mockito::ServerGuard) {
+    let pages = [
+        ("/", page(r#"<a href="/docs/">Docs</a> <a href="/docs/intro#setup">Intro</a>
+            <a href="https://other.example/">Other</a> <a href="/file.pdf">PDF</a>"#)),
+        ("/docs/", page(r#"<a href="intro">Intro</a> <a href="api.html">API</a> <a href="/">Home</a>"#)),
+        ("/docs/intro", page(r#"<a href="api.html#errors">Errors</a> <a href="../">Home</a>"#)),
+        ("/docs/api.html", page(r#"<a href="/docs/deep">Deep</a>"#)),
+        ("/docs/deep", page("Deep page")),
+    ];
+    for (path, body) in pages {
+        server.mock("GET", path)
+            .with_header("content-type", "text/html")
+            .with_body(body)
+            .create_async()
+            .await;
+    }
+    server.mock("GET", "/file.pdf")
+        .with_header("content-type", "application/pdf")
+        .create_async()
+        .await;
+}
+
+fn conversion() -> Conversion {
+    Conversion {
+        fetcher: Fetcher::new(FetchConfig {
+            retry: RetryPolicy { max_attempts: 1, ..RetryPolicy::default() },
+            ..FetchConfig::default()
+        }).unwrap(),
+        options: ConverterOptions::builder().preamble(false).build().unwrap(),
+        base_url: None,
+        encoding: None,
+        cache: None,
+    }
+}
+
+fn crawler(max_depth: usize, max_pages: usize, exclude: &[&str]) -> Crawler {
+    let scope = CrawlScope {
+        max_depth,
+        max_pages,
+        include: Vec::new(),
+        exclude: parse_patterns(&exclude.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap(),
+    };
+    Crawler::new(conversion(), scope, 2)
+}
+
+fn written_files(dir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in std::fs::read_dir(current).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path.strip_prefix(dir).unwrap().to_string_lossy().into_owned());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[tokio::test]
+async fn test_crawl_writes_mirror_with_local_links() {
+    let mut server = mockito::Server::new_async().await;
+    mock_site(&mut server).await;
+    let output_dir = tempfile::tempdir().unwrap();
+    let start = Url::parse(&server.url()).unwrap();
+
+    let summary = crawler(2, 100, &[]).crawl(start, output_dir.path()).await.unwrap();
+    assert_eq!(summary.pages.len(), 4);
+    assert_eq!(summary.skipped, 1);
+    assert!(summary.failures.is_empty());
+    // /docs/deep is three links away from the start page
+    assert_eq!(written_files(output_dir.path()), ["docs/api.md", "docs/index.md", "docs/intro.md", "index.md"]);
+
+    let read = |path: &str| std::fs::read_to_string(output_dir.path().join(path)).unwrap();
+    let index = read("index.md");
+    assert!(index.contains("[Docs](docs/index.md) [Intro](docs/intro.md#setup)"), "{}", index);
+    assert!(index.contains("[Other](https://other.example/)"), "{}", index);
+    assert!(index.contains(&format!("[PDF]({}/file.pdf)", server.url())), "{}", index);
+    let intro = read("docs/intro.md");
+    assert!(intro.contains("[Errors](api.md#errors) [Home](../index.md)"), "{}", intro);
+    let api = read("docs/api.md");
+    assert!(api.contains(&format!("[Deep]({}/docs/deep)", server.url())), "{}", api);
+}
+
+#[tokio::test]
+async fn test_crawl_limits() {
+    let mut server = mockito::Server::new_async().await;
+    mock_site(&mut server).await;
+    let start = Url::parse(&server.url()).unwrap();
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let summary = crawler(0, 100, &[]).crawl(start.clone(), output_dir.path()).await.unwrap();
+    assert_eq!(written_files(output_dir.path()), ["index.md"]);
+    assert_eq!(summary.pages.len(), 1);
+
+    let output_dir = tempfile::tempdir().unwrap();
+    crawler(5, 2, &[]).crawl(start.clone(), output_dir.path()).await.unwrap();
+    assert_eq!(written_files(output_dir.path()), ["docs/index.md", "index.md"]);
+
+    let output_dir = tempfile::tempdir().unwrap();
+    crawler(5, 100, &["intro", r"\.pdf$"]).crawl(start, output_dir.path()).await.unwrap();
+    assert_eq!(
+        written_files(output_dir.path()),
+        ["docs/api.md", "docs/deep.md", "docs/index.md", "index.md"]
+    );
+}
+
+#[tokio::test]
+async fn test_crawl_max_pages_counts_written_pages() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body(page(r#"<a href="/missing">Missing</a> <a href="/file.pdf">PDF</a> <a href="/docs">Docs</a>"#))
+        .create_async()
+        .await;
+    server.mock("GET", "/missing").with_status(404).create_async().await;
+    server.mock("GET", "/file.pdf").with_header("content-type", "application/pdf").create_async().await;
+    server.mock("GET", "/docs")
+        .with_header("content-type", "text/html")
+        .with_body(page("Docs page"))
+        .create_async()
+        .await;
+    let output_dir = tempfile::tempdir().unwrap();
+    let start = Url::parse(&server.url()).unwrap();
+
+    let summary = crawler(5, 2, &[]).crawl(start, output_dir.path()).await.unwrap();
+    assert_eq!(written_files(output_dir.path()), ["docs.md", "index.md"]);
+    assert_eq!(summary.failures.len(), 1);
+    assert_eq!(summary.skipped, 1);
+}
+
+#[tokio::test]
+async fn test_crawl_follows_redirects_on_site_only() {
+    let mut server = mockito::Server::new_async().await;
+    let mut other = mockito::Server::new_async().await;
+    let pages = [
+        ("/", page(r#"<a href="/old">Old</a> <a href="/away">Away</a>"#)),
+        ("/guide/", page(r#"<a href="step">Step</a>"#)),
+        ("/guide/step", page("Step page")),
+    ];
+    for (path, body) in pages {
+        server.mock("GET", path)
+            .with_header("content-type", "text/html")
+            .with_body(body)
+            .create_async()
+            .await;
+    }
+    server.mock("GET", "/old").with_status(301).with_header("location", "/guide/").create_async().await;
+    server.mock("GET", "/away")
+        .with_status(302)
+        .with_header("location", &format!("{}/secret", other.url()))
+        .create_async()
+        .await;
+    other.mock("GET", "/secret")
+        .with_header("content-type", "text/html")
+        .with_body(page("Off-site"))
+        .create_async()
+        .await;
+    let output_dir = tempfile::tempdir().unwrap();
+    let start = Url::parse(&server.url()).unwrap();
+
+    let summary = crawler(2, 100, &[]).crawl(start, output_dir.path()).await.unwrap();
+    // Relative links resolve against the redirect target, /guide/
+    assert_eq!(written_files(output_dir.path()), ["guide/index.md", "guide/step.md", "index.md"]);
+    assert_eq!(summary.skipped, 1);
+    assert!(summary.failures.is_empty());
+    let guide = std::fs::read_to_string(output_dir.path().join("guide/index.md")).unwrap();
+    assert!(guide.contains("[Step](step.md)"), "{}", guide);
+}
+
+#[tokio::test]
+async fn test_crawl_uses_cache() {
+    let mut server = mockito::Server::new_async().await;
+    let home = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body(page(r#"<a href="/docs/#setup">Docs</a>"#))
+        .expect(1)
+        .create_async()
+        .await;
+    let docs = server.mock("GET", "/docs/")
+        .with_header("content-type", "text/html")
+        .with_body(page(r#"<a href="/">Home</a>"#))
+        .expect(1)
+        .create_async()
+        .await;
+    let cache = Arc::new(Cache::new(CacheConfig { ttl: Duration::from_secs(60), capacity: 16, dir: None }));
+    let start = Url::parse(&server.url()).unwrap();
+
+    for _ in 0..2 {
+        let conversion = Conversion { cache: Some(Arc::clone(&cache)), ..conversion() };
+        let scope = CrawlScope { max_depth: 5, max_pages: 100, include: Vec::new(), exclude: Vec::new() };
+        let output_dir = tempfile::tempdir().unwrap();
+        let summary = Crawler::new(conversion, scope, 2).crawl(start.clone(), output_dir.path()).await.unwrap();
+        assert_eq!(summary.pages.len(), 2);
+        let index = std::fs::read_to_string(output_dir.path().join("index.md")).unwrap();
+        assert!(index.contains("[Docs](docs/index.md#setup)"), "{}", index);
+        let docs = std::fs::read_to_string(output_dir.path().join("docs/index.md")).unwrap();
+        assert!(docs.contains("[Home](../index.md)"), "{}", docs);
+    }
+    home.assert_async().await;
+    docs.assert_async().await;
+}
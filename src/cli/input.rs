@@ -0,0 +1,92 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use encoding_rs::Encoding;
+use tokio::io::AsyncReadExt;
+use url::Url;
+
+use crate::http::charset::decode_html;
+use crate::http::Fetcher;
+
+/// Where the HTML to convert comes from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    /// An `http` or `https` URL, fetched over the network.
+    Url(Url),
+    /// A local file, given as a path or a `file://` URL.
+    File(PathBuf),
+    /// Standard input, given as `-`.
+    Stdin,
+}
+
+/// Parses a positional input: `-`, an `http(s)://` or `file://` URL, or the
+/// path of an existing file.
+pub fn parse_input(value: &str) -> Result<Input, String> {
+    if value == "-" {
+        return Ok(Input::Stdin);
+    }
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => return Ok(Input::Url(url)),
+        Ok(url) if url.scheme() == "file" => {
+            return url.to_file_path()
+                .map(Input::File)
+                .map_err(|_| format!("invalid file URL '{}'", value));
+        },
+        // A one-letter scheme is a Windows drive letter, not a URL
+        Ok(url) if url.scheme().len() > 1 => {
+            return Err(format!("unsupported URL scheme '{}'", url.scheme()));
+        },
+        _ => {},
+    }
+    let path = PathBuf::from(value);
+    if path.is_file() {
+        Ok(Input::File(path))
+    } else {
+        Err(format!("'{}' is neither a URL nor an existing file", value))
+    }
+}
+
+impl Input {
+    /// The URL of a fetched page, used to resolve its relative links.
+    pub fn source_url(&self) -> Option<&Url> {
+        match self {
+            Input::Url(url) => Some(url),
+            Input::File(_) | Input::Stdin => None,
+        }
+    }
+
+    /// Reads the document as UTF-8. Local input has no Content-Type header,
+    /// so its encoding comes from `encoding`, a BOM, a `<meta>` tag or
+    /// detection.
+    pub async fn read_html(&self, fetcher: &Fetcher, encoding: Option<&'static Encoding>) -> Result<String> {
+        let bytes = match self {
+            Input::Url(url) => return Ok(fetcher.fetch_html(url).await?),
+            Input::File(path) => tokio::fs::read(path)
+                .await
+                .with_context(|| format!("failed to read {}", path.display()))?,
+            Input::Stdin => {
+                let mut bytes = Vec::new();
+                tokio::io::stdin().read_to_end(&mut bytes)
+                    .await
+                    .context("failed to read standard input")?;
+                bytes
+            },
+        };
+        Ok(decode_html(&bytes, None, encoding, None))
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Url(url) => write!(f, "{}", url),
+            Input::File(path) => write!(f, "{}", path.display()),
+            Input::Stdin => write!(f, "standard input"),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "input_test.rs"]
+mod tests;
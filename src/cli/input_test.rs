@@ -0,0 +1,42 @@
+use std::io::Write;
+
+use url::Url;
+
+use crate::cli::input::{parse_input, Input};
+use crate::http::Fetcher;
+
+#[test]
+fn test_parse_input() {
+    assert_eq!(parse_input("-"), Ok(Input::Stdin));
+    assert_eq!(
+        parse_input("https://example.com/page"),
+        Ok(Input::Url(Url::parse("https://example.com/page").unwrap()))
+    );
+    assert!(parse_input("ftp://example.com/page.html").is_err());
+    assert!(parse_input("missing/page.html").is_err());
+}
+
+#[test]
+fn test_parse_input_files() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let path = file.path().to_path_buf();
+
+    assert_eq!(parse_input(path.to_str().unwrap()), Ok(Input::File(path.clone())));
+    let file_url = Url::from_file_path(&path).unwrap();
+    assert_eq!(parse_input(file_url.as_str()), Ok(Input::File(path)));
+}
+
+#[tokio::test]
+async fn test_read_html_from_file() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    let (body, _, _) = encoding_rs::WINDOWS_1251.encode("<meta charset=\"windows-1251\"><p>Привет</p>");
+    file.write_all(&body).unwrap();
+    let input = Input::File(file.path().to_path_buf());
+
+    let html = input.read_html(&Fetcher::default(), None).await.unwrap();
+    assert!(html.contains("<p>Привет</p>"));
+    assert!(input.source_url().is_none());
+
+    let missing = Input::File(file.path().with_extension("missing"));
+    assert!(missing.read_html(&Fetcher::default(), None).await.is_err());
+}
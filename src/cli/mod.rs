@@ -1,2 +1,8 @@
 pub mod args;
+pub mod batch;
+pub mod config;
+pub mod crawl;
+pub mod input;
+pub mod sitemap;
 pub use args::Args;
+pub use config::ConfigFile;
@@ -0,0 +1,256 @@
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use flate2::read::GzDecoder;
+use regex::Regex;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::http::{robots, Fetcher};
+
+/// Largest uncompressed sitemap, as set by the sitemap protocol.
+const MAX_SITEMAP_SIZE: usize = 50 * 1024 * 1024;
+
+/// Sitemaps read at most, guarding against loops and runaway indexes.
+const MAX_SITEMAPS: usize = 1000;
+
+/// A page listed in a sitemap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SitemapEntry {
+    pub url: Url,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+/// The contents of one sitemap file.
+#[derive(Debug, PartialEq)]
+pub enum Sitemap {
+    /// A `<urlset>` listing pages, or a plain text list of URLs.
+    Pages(Vec<SitemapEntry>),
+    /// A `<sitemapindex>` listing other sitemaps.
+    Index(Vec<Url>),
+}
+
+/// Which of the listed pages to convert.
+#[derive(Clone, Debug, Default)]
+pub struct SitemapFilter {
+    /// When non-empty, only URLs matching one of these are kept.
+    pub include: Vec<Regex>,
+    /// URLs matching any of these are dropped.
+    pub exclude: Vec<Regex>,
+    /// Only pages modified at or after this time; pages without a
+    /// `lastmod` are kept.
+    pub since: Option<DateTime<Utc>>,
+    pub max_pages: Option<usize>,
+}
+
+impl SitemapFilter {
+    fn allows(&self, entry: &SitemapEntry) -> bool {
+        let url = entry.url.as_str();
+        (self.include.is_empty() || self.include.iter().any(|pattern| pattern.is_match(url)))
+            && !self.exclude.iter().any(|pattern| pattern.is_match(url))
+            && match (self.since, entry.lastmod) {
+                (Some(since), Some(lastmod)) => lastmod >= since,
+                _ => true,
+            }
+    }
+
+    /// The URLs of the entries that pass the filter, in sitemap order.
+    pub fn select(&self, entries: &[SitemapEntry]) -> Vec<Url> {
+        entries.iter()
+            .filter(|entry| self.allows(entry))
+            .take(self.max_pages.unwrap_or(usize::MAX))
+            .map(|entry| entry.url.clone())
+            .collect()
+    }
+}
+
+/// The pages found by [`read_sitemaps`].
+#[derive(Debug, Default)]
+pub struct SitemapPages {
+    /// Each page once, in the order the sitemaps list them.
+    pub entries: Vec<SitemapEntry>,
+    /// Sitemaps that could not be read, with their errors.
+    pub failures: Vec<(String, anyhow::Error)>,
+}
+
+/// Reads the sitemap at `url`, following sitemap indexes. For a site root
+/// such as `https://example.com/`, the sitemaps listed in its `robots.txt`
+/// are read instead, falling back to `/sitemap.xml`.
+///
+/// Fails only when no sitemap could be read at all; otherwise the sitemaps
+/// that failed are reported in [`SitemapPages::failures`].
+pub async fn read_sitemaps(fetcher: &Fetcher, url: &Url) -> Result<SitemapPages> {
+    let roots = if url.path() == "/" && url.query().is_none() {
+        discover(fetcher, url).await
+    } else {
+        vec![url.clone()]
+    };
+
+    let mut pages = SitemapPages::default();
+    let mut read = 0;
+    let mut seen_sitemaps: HashSet<Url> = roots.iter().cloned().collect();
+    let mut seen_pages = HashSet::new();
+    let mut queue = VecDeque::from(roots);
+    while let Some(sitemap_url) = queue.pop_front() {
+        let sitemap = match fetch_sitemap(fetcher, &sitemap_url).await {
+            Ok(sitemap) => sitemap,
+            Err(e) => {
+                warn!("Failed to read sitemap {}: {:#}", sitemap_url, e);
+                pages.failures.push((sitemap_url.to_string(), e));
+                continue;
+            },
+        };
+        read += 1;
+        match sitemap {
+            Sitemap::Pages(entries) => {
+                info!("Read {} pages from {}", entries.len(), sitemap_url);
+                pages.entries.extend(entries.into_iter().filter(|entry| seen_pages.insert(entry.url.clone())));
+            },
+            Sitemap::Index(sitemaps) => {
+                info!("Read {} sitemaps from {}", sitemaps.len(), sitemap_url);
+                for sitemap in sitemaps {
+                    if seen_sitemaps.len() >= MAX_SITEMAPS {
+                        warn!("Ignoring sitemaps beyond the first {}", MAX_SITEMAPS);
+                        break;
+                    }
+                    if seen_sitemaps.insert(sitemap.clone()) {
+                        queue.push_back(sitemap);
+                    }
+                }
+            },
+        }
+    }
+
+    if read == 0 {
+        let (url, error) = pages.failures.remove(0);
+        return Err(error.context(format!("failed to read sitemap {}", url)));
+    }
+    Ok(pages)
+}
+
+/// The sitemaps a site lists in `robots.txt`, or its `/sitemap.xml`.
+async fn discover(fetcher: &Fetcher, site: &Url) -> Vec<Url> {
+    let robots_url = site.join("/robots.txt").expect("an absolute path joins any http URL");
+    let listed: Vec<Url> = match fetcher.fetch_bytes(&robots_url).await {
+        Ok(body) => robots::sitemaps(&String::from_utf8_lossy(&body))
+            .iter()
+            .filter_map(|sitemap| site.join(sitemap).ok())
+            .collect(),
+        Err(e) => {
+            info!("No sitemaps from {}: {}", robots_url, e);
+            Vec::new()
+        },
+    };
+    if listed.is_empty() {
+        vec![site.join("/sitemap.xml").expect("an absolute path joins any http URL")]
+    } else {
+        listed
+    }
+}
+
+async fn fetch_sitemap(fetcher: &Fetcher, url: &Url) -> Result<Sitemap> {
+    let body = fetcher.fetch_bytes(url).await?;
+    parse_sitemap(&body)
+}
+
+/// Parses a sitemap, sitemap index or plain text URL list, gzipped or
+/// not. Entries whose location is not an absolute http(s) URL are skipped.
+pub fn parse_sitemap(bytes: &[u8]) -> Result<Sitemap> {
+    let bytes = decompress(bytes)?;
+    let text = std::str::from_utf8(&bytes).context("sitemap is not valid UTF-8")?;
+    let text = text.trim_start_matches('\u{feff}');
+    if !text.trim_start().starts_with('<') {
+        let entries = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .filter_map(parse_loc)
+            .map(|url| SitemapEntry { url, lastmod: None })
+            .collect();
+        return Ok(Sitemap::Pages(entries));
+    }
+
+    let document = roxmltree::Document::parse(text).context("invalid sitemap XML")?;
+    let root = document.root_element();
+    let children = |name: &'static str| root.children().filter(move |node| node.has_tag_name(name));
+    match root.tag_name().name() {
+        "urlset" => Ok(Sitemap::Pages(children("url")
+            .filter_map(|node| {
+                let url = parse_loc(child_text(node, "loc")?)?;
+                let lastmod = child_text(node, "lastmod").and_then(parse_lastmod);
+                Some(SitemapEntry { url, lastmod })
+            })
+            .collect())),
+        "sitemapindex" => Ok(Sitemap::Index(children("sitemap")
+            .filter_map(|node| parse_loc(child_text(node, "loc")?))
+            .collect())),
+        other => bail!("expected <urlset> or <sitemapindex>, found <{}>", other),
+    }
+}
+
+/// Inflates gzipped sitemaps (`sitemap.xml.gz`), recognized by their magic
+/// bytes since servers label them inconsistently.
+fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if !bytes.starts_with(&[0x1f, 0x8b]) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let mut inflated = Vec::new();
+    GzDecoder::new(bytes)
+        .take(MAX_SITEMAP_SIZE as u64 + 1)
+        .read_to_end(&mut inflated)
+        .context("invalid gzipped sitemap")?;
+    if inflated.len() > MAX_SITEMAP_SIZE {
+        return Err(anyhow!("sitemap exceeds {} bytes", MAX_SITEMAP_SIZE));
+    }
+    Ok(Cow::Owned(inflated))
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))?
+        .text()
+        .map(str::trim)
+}
+
+fn parse_loc(loc: &str) -> Option<Url> {
+    match Url::parse(loc) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Some(url),
+        _ => {
+            warn!("Skipping invalid sitemap URL '{}'", loc);
+            None
+        },
+    }
+}
+
+/// Parses a W3C datetime as used by `<lastmod>`: a date (`2024-01-31`,
+/// `2024-01` or `2024`), or a date and time with an optional time zone
+/// (`2024-01-31T12:00:00+01:00`, `2024-01-31T12:00Z`). Dates are taken as
+/// midnight UTC, and so are times without a zone.
+pub fn parse_lastmod(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let zoned = match value.strip_suffix('Z') {
+        Some(local) => format!("{}+00:00", local),
+        None => value.to_string(),
+    };
+    if let Ok(time) = DateTime::parse_from_str(&zoned, "%Y-%m-%dT%H:%M%:z") {
+        return Some(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(time.and_utc());
+        }
+    }
+    let date = [value.to_string(), format!("{}-01", value), format!("{}-01-01", value)]
+        .iter()
+        .find_map(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+#[cfg(test)]
+#[path = "sitemap_test.rs"]
+mod tests;
@@ -0,0 +1,198 @@
+use std::io::Write;
+
+use chrono::{TimeZone, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use url::Url;
+
+use crate::cli::crawl::parse_patterns;
+use crate::cli::sitemap::{parse_lastmod, parse_sitemap, read_sitemaps, Sitemap, SitemapEntry, SitemapFilter};
+use crate::http::Fetcher;
+
+const URLSET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/docs/intro</loc>
+    <lastmod>2024-03-01</lastmod>
+  </url>
+  <url>
+    <loc>
+      https://example.com/docs/api?lang=en&amp;v=2
+    </loc>
+    <lastmod>2023-12-31T23:00:00-02:00</lastmod>
+    <changefreq>weekly</changefreq>
+  </url>
+  <url><loc>https://example.com/blog/post</loc></url>
+  <url><loc>not a url</loc></url>
+</urlset>
+"#;
+
+fn gzip(contents: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(contents.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn urlset(urls: &[String]) -> String {
+    let entries: String = urls.iter().map(|url| format!("<url><loc>{}</loc></url>", url)).collect();
+    format!(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">{}</urlset>"#, entries)
+}
+
+fn entry(url: &str, lastmod: Option<&str>) -> SitemapEntry {
+    SitemapEntry { url: Url::parse(url).unwrap(), lastmod: lastmod.and_then(parse_lastmod) }
+}
+
+#[test]
+fn test_parse_urlset() {
+    let Sitemap::Pages(entries) = parse_sitemap(URLSET.as_bytes()).unwrap() else {
+        panic!("expected a urlset");
+    };
+    assert_eq!(entries, [
+        entry("https://example.com/docs/intro", Some("2024-03-01")),
+        entry("https://example.com/docs/api?lang=en&v=2", Some("2024-01-01T01:00:00Z")),
+        entry("https://example.com/blog/post", None),
+    ]);
+}
+
+#[test]
+fn test_parse_sitemap_index() {
+    let index = r#"<?xml version="1.0"?>
+        <sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+          <sitemap><loc>https://example.com/docs.xml</loc><lastmod>2024-01-01</lastmod></sitemap>
+          <sitemap><loc>https://example.com/blog.xml.gz</loc></sitemap>
+        </sitemapindex>"#;
+    let sitemap = parse_sitemap(index.as_bytes()).unwrap();
+    assert_eq!(sitemap, Sitemap::Index(vec![
+        Url::parse("https://example.com/docs.xml").unwrap(),
+        Url::parse("https://example.com/blog.xml.gz").unwrap(),
+    ]));
+}
+
+#[test]
+fn test_parse_gzipped_and_text_sitemaps() {
+    assert_eq!(parse_sitemap(&gzip(URLSET)).unwrap(), parse_sitemap(URLSET.as_bytes()).unwrap());
+
+    let text = "https://example.com/a\n\n  https://example.com/b  \n";
+    assert_eq!(parse_sitemap(text.as_bytes()).unwrap(), Sitemap::Pages(vec![
+        entry("https://example.com/a", None),
+        entry("https://example.com/b", None),
+    ]));
+}
+
+#[test]
+fn test_parse_invalid_sitemap() {
+    assert!(parse_sitemap(b"<html><body>Not found</body></html>").is_err());
+    assert!(parse_sitemap(b"<urlset><url>").is_err());
+    assert!(parse_sitemap(&[0x1f, 0x8b, 0x00]).is_err());
+}
+
+#[test]
+fn test_parse_lastmod() {
+    let utc = |y, m, d, h, min| Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap();
+    assert_eq!(parse_lastmod("2024-03-01"), Some(utc(2024, 3, 1, 0, 0)));
+    assert_eq!(parse_lastmod("2024-03"), Some(utc(2024, 3, 1, 0, 0)));
+    assert_eq!(parse_lastmod("2024"), Some(utc(2024, 1, 1, 0, 0)));
+    assert_eq!(parse_lastmod("2024-03-01T12:30:00+02:00"), Some(utc(2024, 3, 1, 10, 30)));
+    assert_eq!(parse_lastmod("2024-03-01T12:30Z"), Some(utc(2024, 3, 1, 12, 30)));
+    assert_eq!(parse_lastmod("2024-03-01T12:30:00"), Some(utc(2024, 3, 1, 12, 30)));
+    assert_eq!(parse_lastmod(" 2024-03-01T12:30:00.5Z "), parse_lastmod("2024-03-01T12:30:00.5+00:00"));
+    assert_eq!(parse_lastmod("yesterday"), None);
+    assert_eq!(parse_lastmod("2024-13-01"), None);
+}
+
+#[test]
+fn test_sitemap_filter() {
+    let entries = [
+        entry("https://example.com/docs/intro", Some("2024-03-01")),
+        entry("https://example.com/docs/old", Some("2022-01-01")),
+        entry("https://example.com/docs/undated", None),
+        entry("https://example.com/docs/changelog", Some("2024-05-01")),
+        entry("https://example.com/blog/post", Some("2024-04-01")),
+    ];
+    let filter = SitemapFilter {
+        include: parse_patterns(&["/docs/".to_string()]).unwrap(),
+        exclude: parse_patterns(&["changelog".to_string()]).unwrap(),
+        since: parse_lastmod("2024-01-01"),
+        max_pages: None,
+    };
+    let urls: Vec<String> = filter.select(&entries).iter().map(Url::to_string).collect();
+    assert_eq!(urls, ["https://example.com/docs/intro", "https://example.com/docs/undated"]);
+
+    let filter = SitemapFilter { max_pages: Some(2), ..SitemapFilter::default() };
+    assert_eq!(filter.select(&entries).len(), 2);
+}
+
+#[tokio::test]
+async fn test_read_sitemaps_follows_index() {
+    let mut server = mockito::Server::new_async().await;
+    let base = server.url();
+    let index = format!(
+        "<sitemapindex><sitemap><loc>{0}/docs.xml</loc></sitemap><sitemap><loc>{0}/blog.xml.gz</loc></sitemap>\
+         <sitemap><loc>{0}/missing.xml</loc></sitemap><sitemap><loc>{0}/sitemap_index.xml</loc></sitemap></sitemapindex>",
+        base
+    );
+    let _index = server.mock("GET", "/sitemap_index.xml")
+        .with_header("content-type", "application/xml")
+        .with_body(index)
+        .expect(1)
+        .create_async()
+        .await;
+    let _docs = server.mock("GET", "/docs.xml")
+        .with_body(urlset(&[format!("{}/docs/a", base), format!("{}/docs/b", base)]))
+        .create_async()
+        .await;
+    let _blog = server.mock("GET", "/blog.xml.gz")
+        .with_header("content-type", "application/x-gzip")
+        .with_body(gzip(&urlset(&[format!("{}/blog/c", base), format!("{}/docs/a", base)])))
+        .create_async()
+        .await;
+    let _missing = server.mock("GET", "/missing.xml")
+        .with_status(404)
+        .create_async()
+        .await;
+    let url = Url::parse(&format!("{}/sitemap_index.xml", base)).unwrap();
+
+    let pages = read_sitemaps(&Fetcher::default(), &url).await.unwrap();
+    let paths: Vec<&str> = pages.entries.iter().map(|entry| entry.url.path()).collect();
+    assert_eq!(paths, ["/docs/a", "/docs/b", "/blog/c"]);
+    assert_eq!(pages.failures.len(), 1);
+    assert!(pages.failures[0].0.ends_with("/missing.xml"));
+}
+
+#[tokio::test]
+async fn test_read_sitemaps_discovers_from_site_root() {
+    let mut server = mockito::Server::new_async().await;
+    let base = server.url();
+    let _robots = server.mock("GET", "/robots.txt")
+        .with_body("User-agent: *\nDisallow:\nSitemap: /pages.xml\n")
+        .create_async()
+        .await;
+    let _pages = server.mock("GET", "/pages.xml")
+        .with_body(urlset(&[format!("{}/a", base)]))
+        .create_async()
+        .await;
+    let url = Url::parse(&base).unwrap();
+    let pages = read_sitemaps(&Fetcher::default(), &url).await.unwrap();
+    assert_eq!(pages.entries.len(), 1);
+
+    // Without robots.txt, /sitemap.xml is tried
+    let mut server = mockito::Server::new_async().await;
+    let base = server.url();
+    let _robots = server.mock("GET", "/robots.txt").with_status(404).create_async().await;
+    let _sitemap = server.mock("GET", "/sitemap.xml")
+        .with_body(urlset(&[format!("{}/a", base), format!("{}/b", base)]))
+        .create_async()
+        .await;
+    let url = Url::parse(&base).unwrap();
+    let pages = read_sitemaps(&Fetcher::default(), &url).await.unwrap();
+    assert_eq!(pages.entries.len(), 2);
+}
+
+#[tokio::test]
+async fn test_read_sitemaps_fails_without_any_sitemap() {
+    let mut server = mockito::Server::new_async().await;
+    let _sitemap = server.mock("GET", "/sitemap.xml").with_status(404).create_async().await;
+    let url = Url::parse(&format!("{}/sitemap.xml", server.url())).unwrap();
+    let error = read_sitemaps(&Fetcher::default(), &url).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("404"), "{:#}", error);
+}
@@ -0,0 +1,259 @@
+use once_cell::sync::Lazy;
+use scraper::{ElementRef, Html, Node, Selector};
+
+use super::markdown_converter::{is_inline, push_text, Extracted, MarkdownConverter};
+use super::urls;
+
+static TITLE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("title").unwrap());
+
+/// Attributes kept on cleaned elements; everything else (classes, styles,
+/// event handlers, data attributes) is dropped.
+const KEPT_ATTRIBUTES: &[&str] = &[
+    "alt", "title", "colspan", "rowspan", "headers", "scope", "start", "reversed", "datetime", "lang", "dir",
+];
+
+/// Attributes holding URLs, kept and resolved against the page URL when
+/// their scheme is one of [`URL_SCHEMES`].
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "cite", "poster"];
+
+/// Schemes allowed in URL attributes, besides relative URLs.
+const URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Content elements written to the cleaned HTML, whatever `keep_tag` says.
+/// Other elements are replaced by their children, except [`DROPPED_ELEMENTS`].
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "a", "abbr", "address", "article", "aside", "audio", "b", "bdi", "bdo", "blockquote", "br", "caption", "cite",
+    "code", "col", "colgroup", "data", "dd", "del", "details", "dfn", "div", "dl", "dt", "em", "figcaption", "figure",
+    "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "i", "img", "ins", "kbd", "li", "main", "mark", "nav",
+    "ol", "p", "pre", "q", "rp", "rt", "ruby", "s", "samp", "section", "small", "source", "span", "strong", "sub",
+    "summary", "sup", "table", "tbody", "td", "tfoot", "th", "thead", "time", "tr", "u", "ul", "var", "video", "wbr",
+];
+
+/// Elements dropped with their content: scripts, styles, embedded
+/// documents, forms and anything else that runs code or changes the page.
+const DROPPED_ELEMENTS: &[&str] = &[
+    "applet", "base", "button", "canvas", "embed", "form", "frame", "frameset", "iframe", "input", "link", "math",
+    "meta", "noscript", "object", "option", "param", "portal", "script", "select", "style", "svg", "template",
+    "textarea",
+];
+
+const VOID_ELEMENTS: &[&str] = &["br", "col", "hr", "img", "source", "wbr"];
+
+impl MarkdownConverter {
+    /// The extracted content as a standalone HTML document, with the same
+    /// elements skipped as in the Markdown output, only presentation-free
+    /// attributes kept and URLs resolved.
+    pub fn convert_to_html(&self, html: &str) -> String {
+        let document = Html::parse_document(html);
+        self.begin_document(&document);
+
+        let mut body = String::with_capacity(html.len() / 2);
+        for extracted in self.extract(&document) {
+            match extracted {
+                Extracted::Element(element) => self.write_clean_element(element, &mut body),
+                Extracted::Children(element) => self.write_clean_children(element, &mut body),
+            }
+        }
+
+        let title = document.select(&TITLE_SELECTOR)
+            .next()
+            .map(|title| title.text().collect::<String>().trim().to_string())
+            .unwrap_or_default();
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+            escape_text(&title),
+            body.trim(),
+        )
+    }
+
+    /// The extracted content as plain text: paragraphs separated by blank
+    /// lines, list items and table rows on lines of their own, table cells
+    /// separated by tabs and preformatted text kept as is.
+    pub fn convert_to_text(&self, html: &str) -> String {
+        let document = Html::parse_document(html);
+        self.begin_document(&document);
+
+        let mut blocks = Vec::new();
+        let mut inline = String::new();
+        for extracted in self.extract(&document) {
+            match extracted {
+                Extracted::Element(element) => self.collect_text_element(element, &mut blocks, &mut inline),
+                Extracted::Children(element) => self.collect_text_children(element, &mut blocks, &mut inline),
+            }
+        }
+        flush_block(&mut inline, &mut blocks);
+
+        let mut text = blocks.join("\n\n");
+        text.push('\n');
+        text
+    }
+
+    fn write_clean_children(&self, element: ElementRef, out: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => out.push_str(&escape_text(text)),
+                Node::Element(_) => self.write_clean_element(ElementRef::wrap(child).unwrap(), out),
+                _ => {},
+            }
+        }
+    }
+
+    fn write_clean_element(&self, element: ElementRef, out: &mut String) {
+        let tag_name = element.value().name();
+        if DROPPED_ELEMENTS.contains(&tag_name) || self.should_skip(element) {
+            return;
+        }
+        if !ALLOWED_ELEMENTS.contains(&tag_name) {
+            self.write_clean_children(element, out);
+            return;
+        }
+        out.push('<');
+        out.push_str(tag_name);
+        for (name, value) in element.value().attrs() {
+            let value = if URL_ATTRIBUTES.contains(&name) {
+                let url = match (tag_name, name) {
+                    // Lazy-loaded images keep their real URL elsewhere
+                    ("img", "src") => self.resolve_url(urls::image_source(element).unwrap_or(value)),
+                    _ => self.resolve_url(value),
+                };
+                if !is_safe_url(&url) {
+                    continue;
+                }
+                url
+            } else if KEPT_ATTRIBUTES.contains(&name) {
+                value.to_string()
+            } else {
+                continue;
+            };
+            out.push_str(&format!(" {}=\"{}\"", name, escape_attribute(&value)));
+        }
+        out.push('>');
+        if VOID_ELEMENTS.contains(&tag_name) {
+            return;
+        }
+        self.write_clean_children(element, out);
+        out.push_str(&format!("</{}>", tag_name));
+    }
+
+    fn collect_text_children(&self, element: ElementRef, blocks: &mut Vec<String>, inline: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => push_text(inline, text),
+                Node::Element(_) => self.collect_text_element(ElementRef::wrap(child).unwrap(), blocks, inline),
+                _ => {},
+            }
+        }
+    }
+
+    fn collect_text_element(&self, element: ElementRef, blocks: &mut Vec<String>, inline: &mut String) {
+        if self.should_skip(element) {
+            return;
+        }
+        match element.value().name() {
+            "br" => {
+                let trimmed_len = inline.trim_end_matches(' ').len();
+                inline.truncate(trimmed_len);
+                inline.push('\n');
+            },
+            "img" => {},
+            "pre" => {
+                flush_block(inline, blocks);
+                let text = element.text().collect::<String>();
+                let text = text.trim_matches('\n').trim_end();
+                if !text.is_empty() {
+                    blocks.push(text.to_string());
+                }
+            },
+            "ul" | "ol" | "dl" | "table" => {
+                flush_block(inline, blocks);
+                let item_tags: &[&str] = match element.value().name() {
+                    "table" => &["tr"],
+                    "dl" => &["dt", "dd"],
+                    _ => &["li"],
+                };
+                let lines: Vec<String> = element.descendants()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|item| item_tags.contains(&item.value().name()))
+                    // Items of nested lists and tables are reached through their own container
+                    .filter(|item| {
+                        let container = item.ancestors()
+                            .filter_map(ElementRef::wrap)
+                            .find(|ancestor| matches!(ancestor.value().name(), "ul" | "ol" | "dl" | "table"));
+                        container.map(|container| container.id()) == Some(element.id())
+                    })
+                    .map(|item| self.item_text(item))
+                    .filter(|line| !line.is_empty())
+                    .collect();
+                if !lines.is_empty() {
+                    blocks.push(lines.join("\n"));
+                }
+            },
+            tag_name if is_inline(tag_name) => self.collect_text_children(element, blocks, inline),
+            _ => {
+                flush_block(inline, blocks);
+                self.collect_text_children(element, blocks, inline);
+                flush_block(inline, blocks);
+            },
+        }
+    }
+
+    /// A list item or table row on one line, nested blocks joined by
+    /// spaces and table cells by tabs.
+    fn item_text(&self, item: ElementRef) -> String {
+        let cells: Vec<ElementRef> = match item.value().name() {
+            "tr" => item.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                .collect(),
+            _ => vec![item],
+        };
+        let separator = if item.value().name() == "tr" { "\t" } else { " " };
+        cells.iter()
+            .map(|cell| {
+                let mut blocks = Vec::new();
+                let mut inline = String::new();
+                self.collect_text_children(*cell, &mut blocks, &mut inline);
+                flush_block(&mut inline, &mut blocks);
+                blocks.join(" ").replace('\n', " ")
+            })
+            .collect::<Vec<_>>()
+            .join(separator)
+            .trim()
+            .to_string()
+    }
+}
+
+fn flush_block(inline: &mut String, blocks: &mut Vec<String>) {
+    let text: Vec<&str> = inline.lines().map(str::trim).collect();
+    let text = text.join("\n");
+    let text = text.trim();
+    if !text.is_empty() {
+        blocks.push(text.to_string());
+    }
+    inline.clear();
+}
+
+/// Whether `url` is relative or uses one of [`URL_SCHEMES`]. Whitespace
+/// and control characters are ignored as browsers do, so `java\tscript:`
+/// is still recognized.
+fn is_safe_url(url: &str) -> bool {
+    let url: String = url.chars().filter(|c| !c.is_ascii_whitespace() && !c.is_control()).collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => {
+            URL_SCHEMES.iter().any(|scheme| url[..end].eq_ignore_ascii_case(scheme))
+        },
+        _ => true,
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_attribute(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+#[path = "clean_test.rs"]
+mod tests;
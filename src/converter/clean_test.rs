@@ -0,0 +1,109 @@
+use url::Url;
+
+use crate::converter::markdown_converter::MarkdownConverter;
+use crate::converter::options::{ConverterOptions, ExtractMode};
+
+const PAGE: &str = r#"<html><head><title>Guide &amp; Notes</title><script>track()</script></head><body>
+    <nav><a href="/">Home</a></nav>
+    <main class="content">
+      <h1 id="top" onclick="x()">Getting   started</h1>
+      <p>Read <a href="setup" class="btn">the setup</a> first.<br>Then continue.</p>
+      <img src="placeholder.gif" data-src="/img/shot.png" alt="Screenshot">
+      <ul><li>One</li><li>Two <ul><li>Nested</li></ul></li></ul>
+      <table><tr><th>Name</th><th>Value</th></tr><tr><td>a</td><td>1 &lt; 2</td></tr></table>
+      <pre><code>let x = 1;
+let y = 2;</code></pre>
+    </main>
+    <footer>Copyright</footer>
+</body></html>"#;
+
+fn converter() -> MarkdownConverter {
+    MarkdownConverter::new().with_source_url(Url::parse("https://example.com/docs/").unwrap())
+}
+
+#[test]
+fn test_convert_to_html() {
+    let html = converter().convert_to_html(PAGE);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<title>Guide &amp; Notes</title>"));
+    assert!(html.contains("<main>\n      <h1>Getting   started</h1>"));
+    assert!(html.contains(r#"<a href="https://example.com/docs/setup">the setup</a> first.<br>Then"#));
+    assert!(html.contains(r#" src="https://example.com/img/shot.png""#));
+    assert!(html.contains(r#" alt="Screenshot""#));
+    assert!(html.contains("<td>1 &lt; 2</td>"));
+    assert!(html.contains("<pre><code>let x = 1;\nlet y = 2;</code></pre>"));
+    for dropped in ["<nav", "<footer", "<script", "class=", "onclick", "data-src", "Home"] {
+        assert!(!html.contains(dropped), "{} in {}", dropped, html);
+    }
+}
+
+#[test]
+fn test_convert_to_text() {
+    let text = converter().convert_to_text(PAGE);
+    assert_eq!(text, "\
+Getting started
+
+Read the setup first.
+Then continue.
+
+One
+Two Nested
+
+Name\tValue
+a\t1 < 2
+
+let x = 1;
+let y = 2;
+");
+}
+
+#[test]
+fn test_clean_output_follows_extraction() {
+    let options = ConverterOptions::builder().extract_mode(ExtractMode::Full).keep_tag("footer").build().unwrap();
+    let text = MarkdownConverter::with_options(options).convert_to_text(PAGE);
+    assert!(text.ends_with("Copyright\n"));
+
+    let options = ConverterOptions::builder().include_selector("table").build().unwrap();
+    let html = MarkdownConverter::with_options(options).convert_to_html(PAGE);
+    assert!(html.contains("<body>\n<table>"));
+    assert!(!html.contains("<h1>"));
+}
+
+#[test]
+fn test_clean_html_drops_active_elements() {
+    let page = r#"<html><body><main><p>Text</p>
+        <script>alert(document.cookie)</script><style>p{}</style>
+        <iframe src="https://evil.example/"></iframe><embed src="https://evil.example/x.swf">
+        <object data="x.swf"><p>Fallback</p></object><base href="https://evil.example/">
+        <form action="/login"><input name="user"><button>Go</button></form>
+        <svg><script>alert(1)</script></svg><font color="red">Kept text</font>
+    </main></body></html>"#;
+    let options = ConverterOptions::builder().keep_tag("script").keep_tag("iframe").build().unwrap();
+    let html = MarkdownConverter::with_options(options).convert_to_html(page);
+    for tag in ["<script", "<style", "<iframe", "<embed", "<object", "<base", "<form", "<input", "<button", "<svg", "<font"] {
+        assert!(!html.contains(tag), "{} in {}", tag, html);
+    }
+    assert!(!html.contains("alert"));
+    assert!(!html.contains("Fallback"));
+    assert!(html.contains("<p>Text</p>"));
+    assert!(html.contains("Kept text"));
+}
+
+#[test]
+fn test_clean_html_drops_unsafe_urls() {
+    let page = r#"<html><body><main>
+        <a href="javascript:alert(1)">one</a>
+        <a href=" JaVa&#9;Script:alert(1)">two</a>
+        <a href="vbscript:msgbox(1)">three</a>
+        <img src="data:image/svg+xml;base64,PHN2Zz4=" alt="four">
+        <a href="mailto:me@example.com">five</a>
+        <a href="/six?x=a:b">six</a>
+    </main></body></html>"#;
+    let html = converter().convert_to_html(page);
+    assert!(!html.to_lowercase().contains("script:"), "{}", html);
+    assert!(!html.contains("data:"));
+    assert!(html.contains("<a>one</a>"));
+    assert!(html.contains(r#"<img alt="four">"#));
+    assert!(html.contains(r#"<a href="mailto:me@example.com">five</a>"#));
+    assert!(html.contains(r#"<a href="https://example.com/six?x=a:b">six</a>"#));
+}
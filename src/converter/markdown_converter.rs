@@ -1,220 +1,281 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
 use anyhow::Result;
-use scraper::{Html, Selector, ElementRef};
+use chrono::Utc;
+use scraper::{Html, Node, Selector, ElementRef};
 use once_cell::sync::Lazy;
+use url::Url;
+
+use super::metadata::PageMetadata;
+use super::options::{ConverterOptions, FrontMatter, HeadingStyle, LinkStyle};
+use super::readability;
+use super::urls;
+
+pub use super::options::ExtractMode;
 
 static TITLE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("title").unwrap());
 static MAIN_CONTENT_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("main, article, .content, #content, .main, #main").unwrap());
+static BASE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("base[href]").unwrap());
 static BODY_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("body").unwrap());
-static LI_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("li").unwrap());
-static TD_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("td").unwrap());
-static TH_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("th").unwrap());
-static TR_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("tr").unwrap());
-static TBODY_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("tbody").unwrap());
-static THEAD_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("thead").unwrap());
 static CODE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("code").unwrap());
-static SKIP_HEADER_SELECTOR: Lazy<Selector> = Lazy::new(|| {
-    Selector::parse("header.header, header#site-header, header.site-header, header.page-header, header.banner, header#masthead").unwrap()
-});
-static SKIP_LANGUAGE_SELECTOR: Lazy<Selector> = Lazy::new(|| {
-    Selector::parse("#p-lang, .interlanguage-link, #p-lang-btn, .language-list, #language-list, .mw-interlanguage-selector").unwrap()
-});
-static SKIP_INFOBOX_SELECTOR: Lazy<Selector> = Lazy::new(|| {
-    Selector::parse(".infobox, .vcard, .infobox.vcard, .infobox.biography.vcard").unwrap()
-});
+
+/// Maps a resolved link URL to the URL written to the Markdown, or `None`
+/// to keep it.
+pub type LinkRewriter = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
 
 pub struct MarkdownConverter {
-    skip_tags: Vec<&'static str>,
+    options: ConverterOptions,
+    source_url: Option<Url>,
+    link_rewriter: Option<LinkRewriter>,
+    state: RefCell<ConversionState>,
+}
+
+/// State for the document currently being converted.
+#[derive(Default)]
+struct ConversionState {
+    /// URL that relative links resolve against.
+    base_url: Option<Url>,
+    /// Distinct links in the order first seen; reference and footnote
+    /// numbers are positions in this list.
+    links: Vec<CollectedLink>,
+    /// Distinct image URLs in the order first seen.
+    images: Vec<String>,
+}
+
+struct CollectedLink {
+    url: String,
+    text: String,
+}
+
+/// A piece of the extracted content: an element converted whole, or a
+/// container converted through its children only.
+pub(super) enum Extracted<'a> {
+    Element(ElementRef<'a>),
+    Children(ElementRef<'a>),
+}
+
+/// A converted document with the metadata, links and images found in it.
+#[derive(Clone, Debug)]
+pub struct ConvertedPage {
+    pub markdown: String,
+    pub metadata: PageMetadata,
+    pub links: Vec<String>,
+    pub images: Vec<String>,
+}
+
+impl Default for MarkdownConverter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MarkdownConverter {
     pub fn new() -> Self {
+        Self::with_options(ConverterOptions::default())
+    }
+
+    pub fn with_options(options: ConverterOptions) -> Self {
         Self {
-            skip_tags: vec![
-                "nav", "footer", "script", "style", "noscript", "iframe", "meta",
-                "link", // For external stylesheets
-                "svg", // Often contains styling
-                "path", // SVG elements
-                "defs", // SVG definitions
-                "symbol", // SVG symbols
-                "use", // SVG use elements
-                "template", // HTML templates that might contain styles
-                "input", // Input elements
-                "button", // Button elements
-                "form", // Form elements
-                "select", // Select elements
-                "option", // Option elements
-                "textarea", // Textarea elements
-            ],
+            options,
+            source_url: None,
+            link_rewriter: None,
+            state: RefCell::new(ConversionState::default()),
         }
     }
 
+    /// Sets the URL the HTML was fetched from. Relative links resolve against
+    /// it, and it is recorded in the front matter.
+    pub fn with_source_url(mut self, source_url: Url) -> Self {
+        self.source_url = Some(source_url);
+        self
+    }
+
+    /// Rewrites link targets, e.g. to point at local copies of other pages.
+    pub fn with_link_rewriter(mut self, link_rewriter: LinkRewriter) -> Self {
+        self.link_rewriter = Some(link_rewriter);
+        self
+    }
+
+    pub fn options(&self) -> &ConverterOptions {
+        &self.options
+    }
+
     pub fn convert(&self, html: &str) -> Result<String> {
+        self.convert_page(html).map(|page| page.markdown)
+    }
+
+    /// Converts `html`, also returning the page metadata and the links and
+    /// images of the converted content.
+    pub fn convert_page(&self, html: &str) -> Result<ConvertedPage> {
         let document = Html::parse_document(html);
-        let mut markdown = String::with_capacity(html.len() / 2); // Pre-allocate buffer
+        let fetched_at = Utc::now();
+        self.begin_document(&document);
+
+        let mut content = String::with_capacity(html.len() / 2); // Pre-allocate buffer
 
-        // Extract title if available
-        if let Some(title) = document.select(&TITLE_SELECTOR)
-            .next()
-            .map(|title| title.text().collect::<Vec<_>>().join(" ").trim().to_string()) {
-            markdown.push_str(&format!("Title: {}\n\n", title));
+        // Process main content
+        for extracted in self.extract(&document) {
+            match extracted {
+                Extracted::Element(element) => self.process_element(element, &mut content),
+                Extracted::Children(element) => self.process_children(element, &mut content),
+            }
         }
 
-        // Add Markdown Content header
-        markdown.push_str("Markdown Content:\n");
+        // Taken before the link definitions are added, so URLs are not
+        // counted as words
+        let metadata = PageMetadata::extract(&document, self.source_url.as_ref(), fetched_at, &content);
+        self.process_collected_links(&self.state.borrow().links, &mut content);
 
-        // Process main content
-        if let Some(main_content) = document.select(&MAIN_CONTENT_SELECTOR).next() {
-            self.process_element(main_content, &mut markdown, 0);
-        } else {
-            if let Some(body) = document.select(&BODY_SELECTOR).next() {
-                self.process_body_content(body, &mut markdown);
-            } else {
-                let root = ElementRef::from(document.root_element());
-                self.process_body_content(root, &mut markdown);
+        let mut markdown = String::with_capacity(content.len() + 256);
+        if self.options.front_matter != FrontMatter::None {
+            markdown.push_str(&metadata.to_front_matter(self.options.front_matter));
+        }
+
+        if self.options.preamble {
+            // Extract title if available
+            if let Some(title) = document.select(&TITLE_SELECTOR)
+                .next()
+                .map(|title| title.text().collect::<Vec<_>>().join(" ").trim().to_string()) {
+                markdown.push_str(&format!("Title: {}\n\n", title));
             }
+
+            // Add Markdown Content header
+            markdown.push_str("Markdown Content:\n");
+        }
+
+        markdown.push_str(&content);
+        let state = self.state.borrow();
+        Ok(ConvertedPage {
+            markdown,
+            metadata,
+            links: state.links.iter().map(|link| link.url.clone()).collect(),
+            images: state.images.clone(),
+        })
+    }
+
+    /// Resets the conversion state for a new document.
+    pub(super) fn begin_document(&self, document: &Html) {
+        let base_href = document.select(&BASE_SELECTOR).next().and_then(|base| base.value().attr("href"));
+        *self.state.borrow_mut() = ConversionState {
+            base_url: urls::document_base(self.source_url.as_ref(), base_href),
+            ..ConversionState::default()
+        };
+    }
+
+    /// Picks the content to convert: the elements matching the include
+    /// selectors, or else what the extraction mode selects.
+    pub(super) fn extract<'a>(&self, document: &'a Html) -> Vec<Extracted<'a>> {
+        if let Some(include) = &self.options.include {
+            return document.select(include)
+                // Nested matches are already converted with their ancestor
+                .filter(|element| {
+                    !element.ancestors()
+                        .filter_map(ElementRef::wrap)
+                        .any(|ancestor| include.matches(&ancestor))
+                })
+                .map(Extracted::Element)
+                .collect();
         }
 
-        Ok(markdown)
+        match self.options.extract_mode {
+            ExtractMode::Readability => {
+                let content = readability::extract(document);
+                if content.is_empty() {
+                    // Nothing scored (e.g. an empty or link-only page)
+                    return selected_content(document);
+                }
+                // Containers are processed through their children so the
+                // skip rules for their own attributes cannot drop them.
+                content.into_iter()
+                    .map(|element| match element.value().name() {
+                        "p" => Extracted::Element(element),
+                        _ => Extracted::Children(element),
+                    })
+                    .collect()
+            },
+            ExtractMode::Selector => selected_content(document),
+            ExtractMode::Full => vec![full_content(document)],
+        }
     }
 
-    fn process_body_content(&self, element: ElementRef, markdown: &mut String) {
+    /// Processes the children of a block element, gathering consecutive text
+    /// and inline elements into paragraphs and recursing into block elements.
+    pub(super) fn process_children(&self, element: ElementRef, markdown: &mut String) {
+        let mut inline = String::new();
         for child in element.children() {
-            if let Some(child) = ElementRef::wrap(child) {
-                if !self.skip_tags.contains(&child.value().name.local.as_ref()) {
-                    self.process_element(child, markdown, 0);
+            match child.value() {
+                Node::Text(text) => push_text(&mut inline, text),
+                Node::Element(_) => {
+                    let child_ref = ElementRef::wrap(child).unwrap();
+                    if is_inline(child_ref.value().name()) {
+                        self.render_inline_element(child_ref, &mut inline);
+                    } else {
+                        flush_paragraph(&mut inline, markdown);
+                        self.process_element(child_ref, markdown);
+                    }
                 }
+                _ => {}
             }
         }
+        flush_paragraph(&mut inline, markdown);
     }
 
-    fn process_element(&self, element: ElementRef, markdown: &mut String, depth: usize) {
-        let tag_name = element.value().name();
+    fn is_skipped_tag(&self, tag_name: &str) -> bool {
+        self.options.skip_tags.iter().any(|tag| tag == tag_name)
+    }
 
-        // Skip style elements completely
-        if tag_name == "style" {
-            return;
-        }
-        
-        // Skip elements with style-related attributes
-        if element.value().attr("style").is_some() || 
-           element.value().attr("class").map(|c| c.contains("style-scope")).unwrap_or(false) {
-            return;
-        }
-        
+    pub(super) fn should_skip(&self, element: ElementRef) -> bool {
         // Skip elements in skip_tags list
-        if self.skip_tags.contains(&tag_name) {
-            return;
-        }
-        
-        // Skip site headers
-        if SKIP_HEADER_SELECTOR.matches(&element) {
-            return;
+        if self.is_skipped_tag(element.value().name()) {
+            return true;
         }
 
-        // Skip language list
-        if SKIP_LANGUAGE_SELECTOR.matches(&element) {
-            return;
+        // Skip site headers, infoboxes, language lists and anything else
+        // matched by the exclude selectors
+        if self.options.exclude.as_ref().is_some_and(|exclude| exclude.matches(&element)) {
+            return true;
         }
 
-        // Skip infobox/vcard elements
-        if SKIP_INFOBOX_SELECTOR.matches(&element) {
-            return;
-        }
+        // Skip banners, sidebars and the like when extracting the article
+        self.options.extract_mode == ExtractMode::Readability && readability::is_unlikely_candidate(element)
+    }
 
-        // Skip elements with infobox/vcard-related classes
-        if element.value().attr("class").map(|c| {
-            c.contains("infobox") || 
-            c.contains("vcard") || 
-            c.contains("metadata")
-        }).unwrap_or(false) {
+    fn process_element(&self, element: ElementRef, markdown: &mut String) {
+        if self.should_skip(element) {
             return;
         }
 
-        // Skip elements with language-related classes
-        if element.value().attr("class").map(|c| {
-            c.contains("interwiki") || 
-            c.contains("language-list") || 
-            c.contains("lang-list") ||
-            c.contains("mw-interlanguage")
-        }).unwrap_or(false) {
-            return;
-        }
-        
+        let tag_name = element.value().name();
         match tag_name {
             "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
                 let level = tag_name.chars().nth(1).unwrap().to_digit(10).unwrap() as usize;
-                let text = element.text().collect::<Vec<_>>().join(" ").trim().to_string();
+                let text = self.render_inline(element);
                 if !text.is_empty() {
-                    // Add separator line before headers
-                    if level <= 2 {
-                        markdown.push_str(&format!("\n{}\n", "-".repeat(74)));
-                    }
-                    markdown.push_str(&format!("\n{} {}\n", "#".repeat(level), text));
+                    markdown.push_str(&self.format_heading(level, &text));
                 }
             },
             "p" => {
-                let text = element.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                if !text.is_empty() {
-                    markdown.push_str(&text);
-                    markdown.push_str("\n\n");
-                }
+                let mut text = self.render_inline(element);
+                flush_paragraph(&mut text, markdown);
             },
             "blockquote" => {
-                for child in element.children() {
-                    if let Some(child_ref) = ElementRef::wrap(child) {
-                        let text = child_ref.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                        if !text.is_empty() {
-                            markdown.push_str(&format!("> {}\n", text));
+                let mut inner = String::new();
+                self.process_children(element, &mut inner);
+                let inner = inner.trim();
+                if !inner.is_empty() {
+                    for line in inner.lines() {
+                        if line.is_empty() {
+                            markdown.push_str(">\n");
+                        } else {
+                            markdown.push_str(&format!("> {}\n", line));
                         }
                     }
-                }
-                markdown.push('\n');
-            },
-            "a" => {
-                if let Some(href) = element.value().attr("href") {
-                    let text = element.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                    if !text.is_empty() {
-                        markdown.push_str(&format!("[{}]({})", text, href));
-                    }
-                }
-            },
-            "img" => {
-                if let Some(src) = element.value().attr("src") {
-                    let alt = element.value().attr("alt").unwrap_or("");
-                    markdown.push_str(&format!("![{}]({})\n\n", alt, src));
-                }
-            },
-            "ul" => {
-                let mut items = Vec::new();
-                for li in element.select(&LI_SELECTOR) {
-                    let text = li.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                    if !text.is_empty() {
-                        items.push(text);
-                    }
-                }
-                if !items.is_empty() {
-                    markdown.push('\n');
-                    for item in items {
-                        markdown.push_str(&format!("* {}\n", item));
-                    }
                     markdown.push('\n');
                 }
             },
-            "ol" => {
-                let mut items = Vec::new();
-                for li in element.select(&LI_SELECTOR) {
-                    let text = li.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                    if !text.is_empty() {
-                        items.push(text);
-                    }
-                }
-                if !items.is_empty() {
-                    markdown.push('\n');
-                    for (i, item) in items.iter().enumerate() {
-                        markdown.push_str(&format!("{}. {}\n", i + 1, item));
-                    }
-                    markdown.push('\n');
-                }
+            "ul" | "ol" => {
+                self.process_list(element, markdown);
             },
             "table" => {
                 self.process_table(element, markdown);
@@ -230,61 +291,403 @@ impl MarkdownConverter {
                     
                     let code_text = code.text().collect::<Vec<_>>().join("").trim().to_string();
                     if !code_text.is_empty() {
-                        markdown.push_str(&format!("```{}\n{}\n```\n\n", language, code_text));
+                        let fence = self.options.fence_for(&code_text);
+                        markdown.push_str(&format!("{}{}\n{}\n{}\n\n", fence, language, code_text, fence));
                     }
                 }
             },
+            "video" => {
+                let poster = element.value().attr("poster").map(|poster| self.resolve_url(poster));
+                let src = element.value().attr("src")
+                    .or_else(|| {
+                        element.children()
+                            .filter_map(ElementRef::wrap)
+                            .find(|child| child.value().name() == "source")
+                            .and_then(|source| source.value().attr("src"))
+                    })
+                    .map(|src| self.resolve_url(src));
+                let title = element.value().attr("title").unwrap_or("Video");
+                let mut text = match (poster, src) {
+                    (Some(poster), Some(src)) => format!("[![{}]({})]({})", title, poster, src),
+                    (Some(poster), None) => format!("![{}]({})", title, poster),
+                    (None, Some(src)) => format!("[{}]({})", title, src),
+                    (None, None) => String::new(),
+                };
+                flush_paragraph(&mut text, markdown);
+            },
             "hr" => {
-                markdown.push_str(&format!("{}\n", "-".repeat(74)));
+                markdown.push_str(&format!("{}\n", self.options.horizontal_rule));
             },
             "br" => {
                 markdown.push_str("  \n");
             },
+            _ if is_inline(tag_name) => {
+                let mut text = String::new();
+                self.render_inline_element(element, &mut text);
+                flush_paragraph(&mut text, markdown);
+            },
             _ => {
                 // Process children for other elements
-                for child in element.children() {
-                    if let Some(child_ref) = ElementRef::wrap(child) {
-                        self.process_element(child_ref, markdown, depth + 1);
+                self.process_children(element, markdown);
+            }
+        }
+    }
+
+    fn format_heading(&self, level: usize, text: &str) -> String {
+        let mut heading = String::new();
+        // Add separator line before headers
+        if level <= 2 && self.options.heading_separator {
+            heading.push_str(&format!("\n{}\n", self.options.horizontal_rule));
+        }
+        if level <= 2 && self.options.heading_style == HeadingStyle::Setext {
+            let underline = if level == 1 { "=" } else { "-" };
+            let width = text.lines().map(|line| line.chars().count()).max().unwrap_or(0).max(3);
+            heading.push_str(&format!("\n{}\n{}\n", text, underline.repeat(width)));
+        } else {
+            heading.push_str(&format!("\n{} {}\n", "#".repeat(level), text));
+        }
+        heading
+    }
+
+    /// Renders the children of `element` as a single line of inline Markdown.
+    pub(super) fn render_inline(&self, element: ElementRef) -> String {
+        let mut buffer = String::new();
+        self.render_inline_children(element, &mut buffer);
+        buffer.trim().to_string()
+    }
+
+    fn render_inline_children(&self, element: ElementRef, buffer: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => push_text(buffer, text),
+                Node::Element(_) => {
+                    self.render_inline_element(ElementRef::wrap(child).unwrap(), buffer);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn render_inline_element(&self, element: ElementRef, buffer: &mut String) {
+        let tag_name = element.value().name();
+        if self.is_skipped_tag(tag_name) {
+            return;
+        }
+
+        match tag_name {
+            "strong" | "b" => self.render_delimited(element, &self.options.strong, buffer),
+            "em" | "i" | "cite" | "dfn" => self.render_delimited(element, &self.options.emphasis, buffer),
+            "del" | "s" | "strike" => self.render_delimited(element, "~~", buffer),
+            "code" | "kbd" | "samp" | "tt" => {
+                let text = collapse_whitespace(&element.text().collect::<String>());
+                let (leading, code, trailing) = split_whitespace_edges(&text);
+                if code.is_empty() {
+                    push_text(buffer, &text);
+                } else {
+                    push_text(buffer, leading);
+                    buffer.push_str(&code_span(code));
+                    push_text(buffer, trailing);
+                }
+            },
+            "a" => {
+                let mut inner = String::new();
+                self.render_inline_children(element, &mut inner);
+                let (leading, text, trailing) = split_whitespace_edges(&inner);
+                match element.value().attr("href").map(str::trim) {
+                    Some(href) if !text.is_empty() && !href.is_empty() && !href.starts_with("javascript:") => {
+                        let href = self.resolve_url(href);
+                        let href = self.link_rewriter.as_ref()
+                            .and_then(|rewrite| rewrite(&href))
+                            .unwrap_or(href);
+                        let href = href.as_str();
+                        push_text(buffer, leading);
+                        let number = self.collect_link(href, text);
+                        match self.options.link_style {
+                            LinkStyle::Inline => buffer.push_str(&format!("[{}]({})", text, href)),
+                            LinkStyle::Reference => buffer.push_str(&format!("[{}][{}]", text, number)),
+                            LinkStyle::Footnote => buffer.push_str(&format!("{}[^{}]", text, number)),
+                            LinkStyle::Strip => buffer.push_str(text),
+                        }
+                        push_text(buffer, trailing);
+                    },
+                    _ => push_text(buffer, &inner),
+                }
+            },
+            "img" => {
+                if let Some(src) = urls::image_source(element) {
+                    let alt = element.value().attr("alt").unwrap_or("");
+                    let src = self.resolve_url(src);
+                    buffer.push_str(&format!("![{}]({})", alt, src));
+                    let images = &mut self.state.borrow_mut().images;
+                    if !images.contains(&src) {
+                        images.push(src);
                     }
                 }
+            },
+            "q" => {
+                buffer.push('"');
+                self.render_inline_children(element, buffer);
+                buffer.push('"');
+            },
+            "br" => {
+                let trimmed_len = buffer.trim_end_matches(' ').len();
+                buffer.truncate(trimmed_len);
+                buffer.push_str("  \n");
+            },
+            _ if is_inline(tag_name) => self.render_inline_children(element, buffer),
+            _ => {
+                // Block elements nested in inline context are flattened with a
+                // separating space so their words do not run together.
+                push_text(buffer, " ");
+                self.render_inline_children(element, buffer);
+                push_text(buffer, " ");
+            }
+        }
+    }
+
+    pub(super) fn resolve_url(&self, url: &str) -> String {
+        if !self.options.resolve_urls {
+            return url.to_string();
+        }
+        urls::resolve(self.state.borrow().base_url.as_ref(), url)
+    }
+
+    /// Records a link and returns its number, reusing the number of an
+    /// earlier link to the same URL.
+    fn collect_link(&self, url: &str, text: &str) -> usize {
+        let links = &mut self.state.borrow_mut().links;
+        match links.iter().position(|link| link.url == url) {
+            Some(index) => index + 1,
+            None => {
+                links.push(CollectedLink {
+                    url: url.to_string(),
+                    text: text.to_string(),
+                });
+                links.len()
             }
         }
     }
 
-    fn process_table(&self, table: ElementRef, markdown: &mut String) {
-        let mut headers = Vec::new();
-        let mut rows = Vec::new();
+    /// Writes reference definitions or footnotes for the collected links,
+    /// and the trailing links section when enabled.
+    fn process_collected_links(&self, links: &[CollectedLink], markdown: &mut String) {
+        if links.is_empty() {
+            return;
+        }
 
-        // Process headers
-        if let Some(thead) = table.select(&THEAD_SELECTOR).next() {
-            for th in thead.select(&TH_SELECTOR) {
-                headers.push(th.text().collect::<Vec<_>>().join(" ").trim().to_string());
+        let definition_prefix = match self.options.link_style {
+            LinkStyle::Reference => Some(""),
+            LinkStyle::Footnote => Some("^"),
+            LinkStyle::Inline | LinkStyle::Strip => None,
+        };
+        if let Some(prefix) = definition_prefix {
+            markdown.push('\n');
+            for (index, link) in links.iter().enumerate() {
+                markdown.push_str(&format!("[{}{}]: {}\n", prefix, index + 1, link.url));
             }
         }
 
-        // Process rows
-        for tr in table.select(&TBODY_SELECTOR).next().unwrap().select(&TR_SELECTOR) {
-            let row: Vec<String> = tr.select(&TD_SELECTOR)
-                .map(|td| td.text().collect::<Vec<_>>().join(" ").trim().to_string())
-                .collect();
-            if !row.is_empty() {
-                rows.push(row);
+        if self.options.links_section {
+            markdown.push_str(&self.format_heading(2, "Links"));
+            markdown.push('\n');
+            for link in links {
+                markdown.push_str(&format!("{} [{}]({})\n", self.options.bullet, link.text, link.url));
             }
         }
+    }
+
+    /// Wraps the inline rendering of `element` in `delimiter`, keeping any
+    /// surrounding whitespace outside the delimiters so the emphasis is valid.
+    fn render_delimited(&self, element: ElementRef, delimiter: &str, buffer: &mut String) {
+        let mut inner = String::new();
+        self.render_inline_children(element, &mut inner);
+        let (leading, text, trailing) = split_whitespace_edges(&inner);
+        if text.is_empty() {
+            push_text(buffer, &inner);
+            return;
+        }
+        push_text(buffer, leading);
+        buffer.push_str(delimiter);
+        buffer.push_str(text);
+        buffer.push_str(delimiter);
+        push_text(buffer, trailing);
+    }
 
-        // Output table in markdown format
-        if !headers.is_empty() {
-            markdown.push_str(&format!("|{}|\n", headers.join("|")));
-            markdown.push_str(&format!("|{}|\n", headers.iter().map(|_| "---").collect::<Vec<_>>().join("|")));
+    /// Renders a `ul` or `ol`, keeping nested lists and block content of each
+    /// item indented under its marker.
+    fn process_list(&self, list: ElementRef, markdown: &mut String) {
+        let mut items: Vec<ListItem> = Vec::new();
+        for child in list.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "li" => {
+                    if self.should_skip(child) {
+                        continue;
+                    }
+                    let mut content = String::new();
+                    self.process_children(child, &mut content);
+                    items.push(ListItem {
+                        content: content.trim().to_string(),
+                        value: child.value().attr("value").and_then(|v| v.trim().parse().ok()),
+                        loose: child.children()
+                            .filter_map(ElementRef::wrap)
+                            .any(|c| !is_inline(c.value().name()) && !matches!(c.value().name(), "ul" | "ol")),
+                    });
+                },
+                // Lists nested directly in a list (invalid, but common) belong
+                // to the preceding item.
+                "ul" | "ol" => {
+                    let mut nested = String::new();
+                    self.process_element(child, &mut nested);
+                    match items.last_mut() {
+                        Some(item) => {
+                            item.content.push('\n');
+                            item.content.push_str(nested.trim());
+                        },
+                        None => items.push(ListItem {
+                            content: nested.trim().to_string(),
+                            value: None,
+                            loose: false,
+                        }),
+                    }
+                },
+                _ => {}
+            }
+        }
+        items.retain(|item| !item.content.is_empty());
+        if items.is_empty() {
+            return;
         }
 
-        for row in rows {
-            markdown.push_str(&format!("|{}|\n", row.join("|")));
+        let ordered = list.value().name() == "ol";
+        let reversed = list.value().attr("reversed").is_some();
+        let mut number: i64 = list.value().attr("start")
+            .and_then(|start| start.trim().parse().ok())
+            .unwrap_or(if reversed { items.len() as i64 } else { 1 });
+        let loose = items.iter().any(|item| item.loose);
+
+        markdown.push('\n');
+        for (index, item) in items.iter().enumerate() {
+            if let Some(value) = item.value {
+                number = value;
+            }
+            let marker = if ordered { format!("{}.", number) } else { self.options.bullet.to_string() };
+            let indent = " ".repeat(marker.len() + 1);
+
+            if loose && index > 0 {
+                markdown.push('\n');
+            }
+            let mut lines = item.content.lines().filter(|line| loose || !line.trim().is_empty());
+            markdown.push_str(&format!("{} {}\n", marker, lines.next().unwrap_or_default()));
+            for line in lines {
+                if line.trim().is_empty() {
+                    markdown.push('\n');
+                } else {
+                    markdown.push_str(&format!("{}{}\n", indent, line));
+                }
+            }
+
+            number += if reversed { -1 } else { 1 };
         }
         markdown.push('\n');
     }
 }
 
+struct ListItem {
+    content: String,
+    value: Option<i64>,
+    loose: bool,
+}
+
+/// The first `main`, `article` or content-like element, else the body.
+fn selected_content(document: &Html) -> Vec<Extracted<'_>> {
+    match document.select(&MAIN_CONTENT_SELECTOR).next() {
+        Some(main_content) => vec![Extracted::Element(main_content)],
+        None => vec![full_content(document)],
+    }
+}
+
+fn full_content(document: &Html) -> Extracted<'_> {
+    Extracted::Children(document.select(&BODY_SELECTOR).next().unwrap_or_else(|| document.root_element()))
+}
+
+static INLINE_TAGS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "del", "dfn", "em", "font", "i",
+    "img", "ins", "kbd", "label", "mark", "q", "s", "samp", "small", "span", "strike", "strong",
+    "sub", "sup", "time", "tt", "u", "var", "wbr",
+];
+
+pub(super) fn is_inline(tag_name: &str) -> bool {
+    INLINE_TAGS.contains(&tag_name)
+}
+
+/// Collapses every run of HTML whitespace into a single space, as browsers do
+/// when rendering normal flow text.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(c);
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+/// Appends collapsed text to an inline buffer without doubling the space at
+/// the boundary between adjacent text nodes.
+pub(super) fn push_text(buffer: &mut String, text: &str) {
+    let collapsed = collapse_whitespace(text);
+    let collapsed = if buffer.ends_with([' ', '\n']) {
+        collapsed.trim_start_matches(' ')
+    } else {
+        collapsed.as_str()
+    };
+    buffer.push_str(collapsed);
+}
+
+fn split_whitespace_edges(text: &str) -> (&str, &str, &str) {
+    let trimmed_start = text.trim_start();
+    let leading = &text[..text.len() - trimmed_start.len()];
+    let content = trimmed_start.trim_end();
+    let trailing = &trimmed_start[content.len()..];
+    (leading, content, trailing)
+}
+
+/// Wraps `code` in a backtick fence longer than any backtick run it contains.
+fn code_span(code: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in code.chars() {
+        if c == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    let fence = "`".repeat(longest + 1);
+    if code.starts_with('`') || code.ends_with('`') {
+        format!("{} {} {}", fence, code, fence)
+    } else {
+        format!("{}{}{}", fence, code, fence)
+    }
+}
+
+fn flush_paragraph(inline: &mut String, markdown: &mut String) {
+    let text = inline.trim();
+    if !text.is_empty() {
+        markdown.push_str(text);
+        markdown.push_str("\n\n");
+    }
+    inline.clear();
+}
+
 #[cfg(test)]
 #[path = "markdown_converter_test.rs"]
 mod tests;
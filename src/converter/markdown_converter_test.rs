@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use crate::converter::markdown_converter::MarkdownConverter;
 
 #[test]
@@ -68,7 +70,162 @@ fn test_convert_with_table() {
 
     let markdown_converter = MarkdownConverter::new();
     let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
-    assert!(markdown.contains("|Header 1|Header 2|"));
-    assert!(markdown.contains("|Cell 1|Cell 2|"));
-    assert!(markdown.contains("|---|---|"));
+    assert!(markdown.contains("| Header 1 | Header 2 |"));
+    assert!(markdown.contains("| Cell 1   | Cell 2   |"));
+    assert!(markdown.contains("| -------- | -------- |"));
+}
+
+#[test]
+fn test_convert_inline_formatting() {
+    let html = r#"
+        <html>
+            <body>
+                <p>Some <strong>bold</strong>, <em>italic</em>, <code>code</code> and <del>struck</del> text.</p>
+            </body>
+        </html>
+    "#;
+
+    let markdown_converter = MarkdownConverter::new();
+    let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
+    assert!(markdown.contains("Some **bold**, _italic_, `code` and ~~struck~~ text."));
+}
+
+#[test]
+fn test_convert_links_in_paragraph() {
+    let html = r#"
+        <html>
+            <body>
+                <p>Read the <a href="https://example.com/docs">
+                    documentation</a> before <b><a href="/start">starting</a></b>.</p>
+            </body>
+        </html>
+    "#;
+
+    let markdown_converter = MarkdownConverter::new();
+    let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
+    assert!(markdown.contains("Read the [documentation](https://example.com/docs) before **[starting](/start)**."));
+}
+
+#[test]
+fn test_convert_inline_whitespace() {
+    let html = r#"
+        <html>
+            <body>
+                <p>Keep<strong> spaces </strong>outside   the
+                   delimiters<br>and break lines.</p>
+                <div>Loose text with a <a href="/x">link</a></div>
+            </body>
+        </html>
+    "#;
+
+    let markdown_converter = MarkdownConverter::new();
+    let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
+    assert!(markdown.contains("Keep **spaces** outside the delimiters  \nand break lines."));
+    assert!(markdown.contains("Loose text with a [link](/x)"));
+}
+
+#[test]
+fn test_convert_code_span_with_backticks() {
+    let html = r#"<html><body><p>Use <code>a `tick`</code> here.</p></body></html>"#;
+
+    let markdown_converter = MarkdownConverter::new();
+    let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
+    assert!(markdown.contains("Use `` a `tick` `` here."));
+}
+
+#[test]
+fn test_convert_nested_lists() {
+    let html = r#"
+        <html>
+            <body>
+                <ul>
+                    <li>Fruit
+                        <ol>
+                            <li>Apple</li>
+                            <li>Banana
+                                <ul><li>Ripe</li></ul>
+                            </li>
+                        </ol>
+                    </li>
+                    <li>Vegetables</li>
+                </ul>
+            </body>
+        </html>
+    "#;
+
+    let markdown_converter = MarkdownConverter::new();
+    let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
+    assert!(markdown.contains("* Fruit\n  1. Apple\n  2. Banana\n     * Ripe\n* Vegetables\n"));
+    assert_eq!(markdown.matches("Apple").count(), 1);
+}
+
+#[test]
+fn test_convert_ordered_list_start_and_reversed() {
+    let html = r#"
+        <html>
+            <body>
+                <ol start="4"><li>Four</li><li>Five</li></ol>
+                <ol reversed><li>Three</li><li>Two</li><li>One</li></ol>
+            </body>
+        </html>
+    "#;
+
+    let markdown_converter = MarkdownConverter::new();
+    let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
+    assert!(markdown.contains("4. Four\n5. Five\n"));
+    assert!(markdown.contains("3. Three\n2. Two\n1. One\n"));
+}
+
+#[test]
+fn test_convert_list_item_with_blocks() {
+    let html = r#"
+        <html>
+            <body>
+                <ol>
+                    <li><p>Install it:</p><pre><code class="language-bash">cargo install url2md</code></pre></li>
+                    <li><p>Run it.</p></li>
+                </ol>
+            </body>
+        </html>
+    "#;
+
+    let markdown_converter = MarkdownConverter::new();
+    let markdown = markdown_converter.convert(html).expect("Failed to convert HTML to Markdown");
+    assert!(markdown.contains("1. Install it:\n\n   ```bash\n   cargo install url2md\n   ```\n\n2. Run it.\n"));
+}
+
+#[test]
+fn test_convert_link_rewriter_and_links() {
+    let html = r#"<html><body><main>
+        <p><a href="/docs/api">API</a>, <a href="https://other.example/">other</a> and <a href="/docs/api">API again</a></p>
+    </main></body></html>"#;
+    let source = url::Url::parse("https://example.com/docs/intro").unwrap();
+
+    let converter = MarkdownConverter::new().with_source_url(source.clone());
+    let page = converter.convert_page(html).unwrap();
+    assert_eq!(page.links, ["https://example.com/docs/api", "https://other.example/"]);
+
+    let converter = MarkdownConverter::new()
+        .with_source_url(source)
+        .with_link_rewriter(Arc::new(|url: &str| {
+            (url == "https://example.com/docs/api").then(|| "api.md".to_string())
+        }));
+    let markdown = converter.convert(html).unwrap();
+    assert!(markdown.contains("[API](api.md), [other](https://other.example/) and [API again](api.md)"), "{}", markdown);
+}
+
+#[test]
+fn test_convert_page() {
+    let html = r#"<html><head><title>Gallery</title><meta name="author" content="Ann"></head><body><main>
+        <p><a href="/a">A</a> <a href="/b">B</a> <a href="/a">A again</a></p>
+        <p><img src="one.png" alt="One"><img src="two.png"><img src="one.png"></p>
+    </main></body></html>"#;
+    let converter = MarkdownConverter::new()
+        .with_source_url(url::Url::parse("https://example.com/gallery/").unwrap());
+    let page = converter.convert_page(html).unwrap();
+    assert_eq!(page.markdown, converter.convert(html).unwrap());
+    assert_eq!(page.metadata.title.as_deref(), Some("Gallery"));
+    assert_eq!(page.metadata.author.as_deref(), Some("Ann"));
+    assert_eq!(page.links, ["https://example.com/a", "https://example.com/b"]);
+    assert_eq!(page.images, ["https://example.com/gallery/one.png", "https://example.com/gallery/two.png"]);
 }
@@ -0,0 +1,248 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use once_cell::sync::Lazy;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use super::options::FrontMatter;
+
+static TITLE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("title").unwrap());
+static HTML_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("html").unwrap());
+static META_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("meta[content]").unwrap());
+static CANONICAL_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("link[rel~=\"canonical\"][href]").unwrap());
+static JSON_LD_SELECTOR: Lazy<Selector> = Lazy::new(|| {
+    Selector::parse("script[type=\"application/ld+json\"]").unwrap()
+});
+
+/// Page-level metadata gathered from `<title>`, `<meta>` tags (including
+/// OpenGraph and Dublin Core), `<link rel="canonical">` and JSON-LD.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    pub fetched_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub word_count: usize,
+}
+
+impl PageMetadata {
+    /// Extracts metadata from `document`. `content` is the converted Markdown
+    /// body, used for the word count.
+    pub fn extract(document: &Html, source_url: Option<&Url>, fetched_at: DateTime<Utc>, content: &str) -> Self {
+        let metas = MetaTags::new(document);
+        let json_ld = JsonLd::new(document);
+
+        let title = document.select(&TITLE_SELECTOR)
+            .next()
+            .map(|title| title.text().collect::<Vec<_>>().join(" "))
+            .and_then(non_empty)
+            .or_else(|| metas.get(&["og:title", "twitter:title"]))
+            .or_else(|| json_ld.string("headline"));
+
+        let canonical_url = document.select(&CANONICAL_SELECTOR)
+            .next()
+            .and_then(|link| link.value().attr("href"))
+            .map(str::to_string)
+            .or_else(|| metas.get(&["og:url"]))
+            .and_then(non_empty)
+            .map(|href| match source_url.and_then(|base| base.join(&href).ok()) {
+                Some(absolute) => absolute.to_string(),
+                None => href,
+            });
+
+        let language = document.select(&HTML_SELECTOR)
+            .next()
+            .and_then(|html| html.value().attr("lang"))
+            .map(str::to_string)
+            .and_then(non_empty)
+            .or_else(|| metas.get(&["content-language", "dc.language", "og:locale"]))
+            .or_else(|| json_ld.string("inLanguage"));
+
+        Self {
+            title,
+            canonical_url,
+            source_url: source_url.map(Url::to_string),
+            fetched_at: fetched_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            description: metas.get(&["description", "og:description", "twitter:description", "dc.description"])
+                .or_else(|| json_ld.string("description")),
+            author: metas.get(&["author", "article:author", "dc.creator", "twitter:creator"])
+                .or_else(|| json_ld.author()),
+            published: metas.get(&[
+                "article:published_time", "og:published_time", "datepublished", "date",
+                "pubdate", "publish-date", "dc.date", "dc.date.issued", "dcterms.created",
+            ]).or_else(|| json_ld.string("datePublished")),
+            modified: metas.get(&[
+                "article:modified_time", "og:updated_time", "datemodified", "last-modified",
+                "dc.date.modified", "dcterms.modified",
+            ]).or_else(|| json_ld.string("dateModified")),
+            language,
+            word_count: count_words(content),
+        }
+    }
+
+    /// Renders the metadata as a front matter block, including the trailing
+    /// blank line. Returns an empty string for [`FrontMatter::None`].
+    pub fn to_front_matter(&self, format: FrontMatter) -> String {
+        let (delimiter, separator) = match format {
+            FrontMatter::None => return String::new(),
+            FrontMatter::Yaml => ("---", ": "),
+            FrontMatter::Toml => ("+++", " = "),
+        };
+
+        let mut block = format!("{}\n", delimiter);
+        let fields = [
+            ("title", &self.title),
+            ("canonical_url", &self.canonical_url),
+            ("source_url", &self.source_url),
+            ("fetched_at", &Some(self.fetched_at.clone())),
+            ("description", &self.description),
+            ("author", &self.author),
+            ("published", &self.published),
+            ("modified", &self.modified),
+            ("language", &self.language),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                // JSON string escapes are valid in both YAML double-quoted
+                // and TOML basic strings.
+                block.push_str(&format!("{}{}{}\n", key, separator, Value::from(value.as_str())));
+            }
+        }
+        block.push_str(&format!("word_count{}{}\n", separator, self.word_count));
+        block.push_str(&format!("{}\n\n", delimiter));
+        block
+    }
+}
+
+/// `<meta>` values keyed by lowercase `name`, `property` or `http-equiv`.
+struct MetaTags(Vec<(String, String)>);
+
+impl MetaTags {
+    fn new(document: &Html) -> Self {
+        let tags = document.select(&META_SELECTOR)
+            .filter_map(|meta| {
+                let element = meta.value();
+                let key = element.attr("property")
+                    .or_else(|| element.attr("name"))
+                    .or_else(|| element.attr("itemprop"))
+                    .or_else(|| element.attr("http-equiv"))?;
+                Some((key.trim().to_ascii_lowercase(), element.attr("content")?.trim().to_string()))
+            })
+            .filter(|(_, content)| !content.is_empty())
+            .collect();
+        Self(tags)
+    }
+
+    /// Returns the first non-empty value among `keys`, in order of preference.
+    fn get(&self, keys: &[&str]) -> Option<String> {
+        keys.iter().find_map(|key| {
+            self.0.iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, content)| content.clone())
+        })
+    }
+}
+
+/// The objects of every JSON-LD block on the page, with arrays and `@graph`
+/// containers flattened.
+struct JsonLd(Vec<Value>);
+
+impl JsonLd {
+    fn new(document: &Html) -> Self {
+        let mut objects = Vec::new();
+        for script in document.select(&JSON_LD_SELECTOR) {
+            let text = script.text().collect::<String>();
+            if let Ok(value) = serde_json::from_str::<Value>(text.trim()) {
+                flatten_json_ld(value, &mut objects);
+            }
+        }
+        Self(objects)
+    }
+
+    fn string(&self, key: &str) -> Option<String> {
+        self.0.iter()
+            .filter_map(|object| object.get(key))
+            .find_map(|value| match value {
+                Value::String(s) => non_empty(s.clone()),
+                Value::Object(_) => value.get("name")
+                    .or_else(|| value.get("@value"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .and_then(non_empty),
+                _ => None,
+            })
+    }
+
+    /// `author` may be a string, a Person object or a list of either.
+    fn author(&self) -> Option<String> {
+        self.0.iter()
+            .filter_map(|object| object.get("author"))
+            .find_map(|author| {
+                let names: Vec<String> = match author {
+                    Value::Array(authors) => authors.iter().filter_map(author_name).collect(),
+                    _ => author_name(author).into_iter().collect(),
+                };
+                non_empty(names.join(", "))
+            })
+    }
+}
+
+fn author_name(author: &Value) -> Option<String> {
+    match author {
+        Value::String(name) => non_empty(name.clone()),
+        Value::Object(_) => author.get("name").and_then(Value::as_str).map(str::to_string).and_then(non_empty),
+        _ => None,
+    }
+}
+
+fn flatten_json_ld(value: Value, objects: &mut Vec<Value>) {
+    match value {
+        Value::Array(values) => {
+            for value in values {
+                flatten_json_ld(value, objects);
+            }
+        },
+        Value::Object(mut object) => {
+            if let Some(graph) = object.remove("@graph") {
+                flatten_json_ld(graph, objects);
+            }
+            objects.push(Value::Object(object));
+        },
+        _ => {}
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let trimmed = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed)
+    }
+}
+
+/// Counts words in Markdown, ignoring tokens that are pure syntax such as
+/// list markers, heading hashes and table pipes.
+fn count_words(markdown: &str) -> usize {
+    markdown.split_whitespace()
+        .filter(|token| token.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+#[cfg(test)]
+#[path = "metadata_test.rs"]
+mod tests;
@@ -0,0 +1,90 @@
+use chrono::{TimeZone, Utc};
+use scraper::Html;
+use url::Url;
+
+use crate::converter::markdown_converter::MarkdownConverter;
+use crate::converter::metadata::PageMetadata;
+use crate::converter::options::{ConverterOptions, FrontMatter, LinkStyle};
+
+const ARTICLE: &str = r#"
+    <html lang="en-GB">
+        <head>
+            <title>Launch Notes</title>
+            <link rel="canonical" href="/blog/launch">
+            <meta name="description" content="What shipped in the launch.">
+            <meta property="og:description" content="Ignored in favour of the plain description.">
+            <meta property="article:published_time" content="2024-03-01T09:00:00Z">
+            <script type="application/ld+json">
+                {"@context": "https://schema.org", "@graph": [
+                    {"@type": "WebSite", "name": "Example"},
+                    {"@type": "BlogPosting", "headline": "Launch Notes",
+                     "author": [{"@type": "Person", "name": "Ada"}, {"@type": "Person", "name": "Grace"}],
+                     "dateModified": "2024-03-02"}
+                ]}
+            </script>
+        </head>
+        <body><p>Three words here.</p></body>
+    </html>
+"#;
+
+#[test]
+fn test_extract_metadata() {
+    let document = Html::parse_document(ARTICLE);
+    let source = Url::parse("https://example.com/blog/launch?utm_source=feed").unwrap();
+    let fetched_at = Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap();
+    let metadata = PageMetadata::extract(&document, Some(&source), fetched_at, "Three words here.");
+
+    assert_eq!(metadata.title.as_deref(), Some("Launch Notes"));
+    assert_eq!(metadata.canonical_url.as_deref(), Some("https://example.com/blog/launch"));
+    assert_eq!(metadata.source_url.as_deref(), Some("https://example.com/blog/launch?utm_source=feed"));
+    assert_eq!(metadata.fetched_at, "2024-03-05T12:00:00Z");
+    assert_eq!(metadata.description.as_deref(), Some("What shipped in the launch."));
+    assert_eq!(metadata.author.as_deref(), Some("Ada, Grace"));
+    assert_eq!(metadata.published.as_deref(), Some("2024-03-01T09:00:00Z"));
+    assert_eq!(metadata.modified.as_deref(), Some("2024-03-02"));
+    assert_eq!(metadata.language.as_deref(), Some("en-GB"));
+    assert_eq!(metadata.word_count, 3);
+}
+
+#[test]
+fn test_front_matter_formats() {
+    let metadata = PageMetadata {
+        title: Some("Say \"hi\"".to_string()),
+        fetched_at: "2024-03-05T12:00:00Z".to_string(),
+        word_count: 2,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        metadata.to_front_matter(FrontMatter::Yaml),
+        "---\ntitle: \"Say \\\"hi\\\"\"\nfetched_at: \"2024-03-05T12:00:00Z\"\nword_count: 2\n---\n\n",
+    );
+    assert_eq!(
+        metadata.to_front_matter(FrontMatter::Toml),
+        "+++\ntitle = \"Say \\\"hi\\\"\"\nfetched_at = \"2024-03-05T12:00:00Z\"\nword_count = 2\n+++\n\n",
+    );
+    assert_eq!(metadata.to_front_matter(FrontMatter::None), "");
+}
+
+#[test]
+fn test_converter_writes_front_matter_first() {
+    let options = ConverterOptions::builder().front_matter(FrontMatter::Yaml).build().unwrap();
+    let markdown = MarkdownConverter::with_options(options)
+        .with_source_url(Url::parse("https://example.com/blog/launch").unwrap())
+        .convert(ARTICLE)
+        .unwrap();
+
+    assert!(markdown.starts_with("---\ntitle: \"Launch Notes\"\ncanonical_url: \"https://example.com/blog/launch\"\n"));
+    assert!(markdown.contains("word_count: 3\n---\n\nTitle: Launch Notes\n"));
+}
+
+#[test]
+fn test_word_count_leaves_out_collected_links() {
+    let html = r#"<html><body><main><p>Read <a href="https://example.com/a/long/path">the guide</a></p></main></body></html>"#;
+    for link_style in [LinkStyle::Inline, LinkStyle::Reference, LinkStyle::Footnote] {
+        let options = ConverterOptions::builder().link_style(link_style).links_section(true).build().unwrap();
+        let page = MarkdownConverter::with_options(options).convert_page(html).unwrap();
+        assert!(page.markdown.contains("https://example.com/a/long/path"), "{}", page.markdown);
+        assert_eq!(page.metadata.word_count, 3, "{:?}", link_style);
+    }
+}
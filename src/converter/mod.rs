@@ -1 +1,7 @@
+mod clean;
 pub mod markdown_converter;
+pub mod metadata;
+pub mod options;
+mod readability;
+mod table;
+mod urls;
@@ -0,0 +1,573 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use scraper::Selector;
+use serde::Deserialize;
+
+const DEFAULT_SKIP_TAGS: &[&str] = &[
+    "nav", "footer", "script", "style", "noscript", "iframe", "meta",
+    "link", // For external stylesheets
+    "svg", // Often contains styling
+    "path", // SVG elements
+    "defs", // SVG definitions
+    "symbol", // SVG symbols
+    "use", // SVG use elements
+    "template", // HTML templates that might contain styles
+    "input", // Input elements
+    "button", // Button elements
+    "form", // Form elements
+    "select", // Select elements
+    "option", // Option elements
+    "textarea", // Textarea elements
+];
+
+const DEFAULT_EXCLUDE_SELECTORS: &[&str] = &[
+    // Elements with style-related attributes
+    "[style], [class*=\"style-scope\"]",
+    // Site headers
+    "header.header, header#site-header, header.site-header, header.page-header, header.banner, header#masthead",
+    // Language lists
+    "#p-lang, .interlanguage-link, #p-lang-btn, .language-list, #language-list, .mw-interlanguage-selector",
+    "[class*=\"interwiki\"], [class*=\"language-list\"], [class*=\"lang-list\"], [class*=\"mw-interlanguage\"]",
+    // Infobox/vcard elements
+    ".infobox, .vcard, .infobox.vcard, .infobox.biography.vcard",
+    "[class*=\"infobox\"], [class*=\"vcard\"], [class*=\"metadata\"]",
+];
+
+const DEFAULT_HORIZONTAL_RULE_WIDTH: usize = 74;
+
+/// Implements `FromStr` and `Display` for a fieldless enum from its
+/// lowercase names, so it can be used both as a CLI value and a query value.
+macro_rules! option_enum {
+    ($name:ident { $($variant:ident => $text:literal),+ $(,)? }) => {
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s.to_ascii_lowercase().as_str() {
+                    $($text => Ok($name::$variant),)+
+                    _ => Err(format!(
+                        "unknown {} '{}', expected one of: {}",
+                        stringify!($name), s, [$($text),+].join(", "),
+                    )),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    $($name::$variant => write!(f, $text),)+
+                }
+            }
+        }
+    };
+}
+
+/// How the converter decides which part of the page to convert.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractMode {
+    /// Score the page's blocks and keep the best-scoring article content.
+    Readability,
+    /// Use the first `main`, `article` or content-like element, else the body.
+    #[default]
+    Selector,
+    /// Convert the whole body.
+    Full,
+}
+
+option_enum!(ExtractMode { Readability => "readability", Selector => "selector", Full => "full" });
+
+/// How headings are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadingStyle {
+    /// `# Heading`
+    #[default]
+    Atx,
+    /// `Heading` underlined with `===` or `---` (levels 1 and 2 only; deeper
+    /// levels fall back to ATX).
+    Setext,
+}
+
+option_enum!(HeadingStyle { Atx => "atx", Setext => "setext" });
+
+/// Which character fenced code blocks use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeFence {
+    #[default]
+    Backtick,
+    Tilde,
+}
+
+option_enum!(CodeFence { Backtick => "backtick", Tilde => "tilde" });
+
+impl CodeFence {
+    fn char(self) -> char {
+        match self {
+            CodeFence::Backtick => '`',
+            CodeFence::Tilde => '~',
+        }
+    }
+}
+
+/// How hyperlinks are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    /// `[text](url)`
+    #[default]
+    Inline,
+    /// `[text][1]` with `[1]: url` definitions at the end of the document.
+    Reference,
+    /// `text[^1]` with `[^1]: url` footnotes at the end of the document.
+    Footnote,
+    /// Plain link text with the URL dropped.
+    Strip,
+}
+
+option_enum!(LinkStyle { Inline => "inline", Reference => "reference", Footnote => "footnote", Strip => "strip" });
+
+/// Whether page metadata is written as a front matter block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrontMatter {
+    #[default]
+    None,
+    /// `---` delimited YAML.
+    Yaml,
+    /// `+++` delimited TOML.
+    Toml,
+}
+
+option_enum!(FrontMatter { None => "none", Yaml => "yaml", Toml => "toml" });
+
+/// Output settings for [`MarkdownConverter`](super::markdown_converter::MarkdownConverter).
+///
+/// Built with [`ConverterOptions::builder`]; `Default` gives the converter's
+/// historical output.
+#[derive(Clone, Debug)]
+pub struct ConverterOptions {
+    pub(super) extract_mode: ExtractMode,
+    pub(super) heading_style: HeadingStyle,
+    pub(super) heading_separator: bool,
+    pub(super) horizontal_rule: String,
+    pub(super) bullet: char,
+    pub(super) emphasis: String,
+    pub(super) strong: String,
+    pub(super) code_fence: CodeFence,
+    pub(super) include: Option<Selector>,
+    pub(super) exclude: Option<Selector>,
+    pub(super) skip_tags: Vec<String>,
+    pub(super) preamble: bool,
+    pub(super) link_style: LinkStyle,
+    pub(super) links_section: bool,
+    pub(super) resolve_urls: bool,
+    pub(super) front_matter: FrontMatter,
+}
+
+impl Default for ConverterOptions {
+    fn default() -> Self {
+        ConverterOptions::builder().build().expect("default converter options are valid")
+    }
+}
+
+impl ConverterOptions {
+    pub fn builder() -> ConverterOptionsBuilder {
+        ConverterOptionsBuilder::default()
+    }
+
+    /// Returns a fenced code block delimiter long enough not to be closed by
+    /// anything inside `code`.
+    pub(super) fn fence_for(&self, code: &str) -> String {
+        let fence_char = self.code_fence.char();
+        let mut longest = 0;
+        for line in code.lines() {
+            let run = line.trim_start().chars().take_while(|c| *c == fence_char).count();
+            longest = longest.max(run);
+        }
+        fence_char.to_string().repeat((longest + 1).max(3))
+    }
+}
+
+pub struct ConverterOptionsBuilder {
+    extract_mode: ExtractMode,
+    heading_style: HeadingStyle,
+    heading_separator: bool,
+    horizontal_rule: String,
+    bullet: char,
+    emphasis: char,
+    strong: char,
+    code_fence: CodeFence,
+    include_selectors: Vec<String>,
+    exclude_selectors: Vec<String>,
+    skip_tags: Vec<String>,
+    preamble: bool,
+    link_style: LinkStyle,
+    links_section: bool,
+    resolve_urls: bool,
+    front_matter: FrontMatter,
+}
+
+impl Default for ConverterOptionsBuilder {
+    fn default() -> Self {
+        Self {
+            extract_mode: ExtractMode::default(),
+            heading_style: HeadingStyle::default(),
+            heading_separator: true,
+            horizontal_rule: "-".repeat(DEFAULT_HORIZONTAL_RULE_WIDTH),
+            bullet: '*',
+            emphasis: '_',
+            strong: '*',
+            code_fence: CodeFence::default(),
+            include_selectors: Vec::new(),
+            exclude_selectors: DEFAULT_EXCLUDE_SELECTORS.iter().map(|s| s.to_string()).collect(),
+            skip_tags: DEFAULT_SKIP_TAGS.iter().map(|s| s.to_string()).collect(),
+            preamble: true,
+            link_style: LinkStyle::default(),
+            links_section: false,
+            resolve_urls: true,
+            front_matter: FrontMatter::default(),
+        }
+    }
+}
+
+impl ConverterOptionsBuilder {
+    pub fn extract_mode(mut self, extract_mode: ExtractMode) -> Self {
+        self.extract_mode = extract_mode;
+        self
+    }
+
+    pub fn heading_style(mut self, heading_style: HeadingStyle) -> Self {
+        self.heading_style = heading_style;
+        self
+    }
+
+    /// Whether a horizontal rule is written before `h1` and `h2` headings.
+    pub fn heading_separator(mut self, heading_separator: bool) -> Self {
+        self.heading_separator = heading_separator;
+        self
+    }
+
+    pub fn horizontal_rule(mut self, horizontal_rule: impl Into<String>) -> Self {
+        self.horizontal_rule = horizontal_rule.into();
+        self
+    }
+
+    /// Unordered list marker: `*`, `-` or `+`.
+    pub fn bullet(mut self, bullet: char) -> Self {
+        self.bullet = bullet;
+        self
+    }
+
+    /// Emphasis delimiter: `_` or `*`.
+    pub fn emphasis(mut self, emphasis: char) -> Self {
+        self.emphasis = emphasis;
+        self
+    }
+
+    /// Strong emphasis delimiter character, doubled: `*` or `_`.
+    pub fn strong(mut self, strong: char) -> Self {
+        self.strong = strong;
+        self
+    }
+
+    pub fn code_fence(mut self, code_fence: CodeFence) -> Self {
+        self.code_fence = code_fence;
+        self
+    }
+
+    /// Converts only elements matching `selector`, in place of the extraction
+    /// mode. May be called repeatedly.
+    pub fn include_selector(mut self, selector: impl Into<String>) -> Self {
+        self.include_selectors.push(selector.into());
+        self
+    }
+
+    /// Drops elements matching `selector` from the output. May be called
+    /// repeatedly; adds to the default exclusions.
+    pub fn exclude_selector(mut self, selector: impl Into<String>) -> Self {
+        self.exclude_selectors.push(selector.into());
+        self
+    }
+
+    /// Removes the default exclusions (inline-styled elements, site headers,
+    /// infoboxes and language lists).
+    pub fn clear_exclude_selectors(mut self) -> Self {
+        self.exclude_selectors.clear();
+        self
+    }
+
+    /// Adds a tag whose contents are never converted.
+    pub fn skip_tag(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into().to_ascii_lowercase();
+        if !self.skip_tags.contains(&tag) {
+            self.skip_tags.push(tag);
+        }
+        self
+    }
+
+    /// Removes a tag from the skipped tags, e.g. to convert `nav` or `footer`.
+    pub fn keep_tag(mut self, tag: &str) -> Self {
+        self.skip_tags.retain(|skipped| !skipped.eq_ignore_ascii_case(tag));
+        self
+    }
+
+    /// Whether the `Title:` and `Markdown Content:` lines are written.
+    pub fn preamble(mut self, preamble: bool) -> Self {
+        self.preamble = preamble;
+        self
+    }
+
+    pub fn link_style(mut self, link_style: LinkStyle) -> Self {
+        self.link_style = link_style;
+        self
+    }
+
+    /// Appends a "Links" section listing every distinct link URL in the
+    /// order first seen, whatever the link style.
+    pub fn links_section(mut self, links_section: bool) -> Self {
+        self.links_section = links_section;
+        self
+    }
+
+    /// Whether relative link and image URLs are made absolute against the
+    /// page URL (or its `<base href>`).
+    pub fn resolve_urls(mut self, resolve_urls: bool) -> Self {
+        self.resolve_urls = resolve_urls;
+        self
+    }
+
+    /// Writes title, URLs, dates, author, language and word count as YAML
+    /// or TOML front matter at the top of the output.
+    pub fn front_matter(mut self, front_matter: FrontMatter) -> Self {
+        self.front_matter = front_matter;
+        self
+    }
+
+    pub fn build(self) -> Result<ConverterOptions> {
+        if !matches!(self.bullet, '*' | '-' | '+') {
+            bail!("invalid bullet '{}', expected '*', '-' or '+'", self.bullet);
+        }
+        if !matches!(self.emphasis, '_' | '*') {
+            bail!("invalid emphasis delimiter '{}', expected '_' or '*'", self.emphasis);
+        }
+        if !matches!(self.strong, '_' | '*') {
+            bail!("invalid strong delimiter '{}', expected '*' or '_'", self.strong);
+        }
+
+        Ok(ConverterOptions {
+            extract_mode: self.extract_mode,
+            heading_style: self.heading_style,
+            heading_separator: self.heading_separator,
+            horizontal_rule: self.horizontal_rule,
+            bullet: self.bullet,
+            emphasis: self.emphasis.to_string(),
+            strong: self.strong.to_string().repeat(2),
+            code_fence: self.code_fence,
+            include: parse_selectors(&self.include_selectors)?,
+            exclude: parse_selectors(&self.exclude_selectors)?,
+            skip_tags: self.skip_tags,
+            preamble: self.preamble,
+            link_style: self.link_style,
+            links_section: self.links_section,
+            resolve_urls: self.resolve_urls,
+            front_matter: self.front_matter,
+        })
+    }
+}
+
+/// Combines selectors into a single selector list, or `None` if empty.
+fn parse_selectors(selectors: &[String]) -> Result<Option<Selector>> {
+    if selectors.is_empty() {
+        return Ok(None);
+    }
+    for selector in selectors {
+        Selector::parse(selector).map_err(|e| anyhow!("invalid selector '{}': {}", selector, e))?;
+    }
+    let combined = selectors.join(", ");
+    Selector::parse(&combined)
+        .map(Some)
+        .map_err(|e| anyhow!("invalid selector '{}': {}", combined, e))
+}
+
+/// Converter settings shared by the CLI flags and the server's query
+/// parameters. Unset values keep the converter defaults.
+#[derive(clap::Args, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ConverterArgs {
+    /// Content extraction mode: readability, selector or full
+    #[arg(long, default_value_t = ExtractMode::Selector, global = true)]
+    pub extract: ExtractMode,
+
+    /// Heading style: atx or setext
+    #[arg(long, global = true)]
+    pub heading_style: Option<HeadingStyle>,
+
+    /// Do not write a horizontal rule before h1 and h2 headings
+    #[arg(long, global = true)]
+    pub no_heading_separator: bool,
+
+    /// Text used for horizontal rules
+    #[arg(long, global = true)]
+    pub horizontal_rule: Option<String>,
+
+    /// Unordered list marker: '*', '-' or '+'
+    #[arg(long, global = true)]
+    pub bullet: Option<char>,
+
+    /// Emphasis delimiter: '_' or '*'
+    #[arg(long, global = true)]
+    pub emphasis: Option<char>,
+
+    /// Strong emphasis delimiter: '*' or '_'
+    #[arg(long, global = true)]
+    pub strong: Option<char>,
+
+    /// Code fence style: backtick or tilde
+    #[arg(long, global = true)]
+    pub code_fence: Option<CodeFence>,
+
+    /// Only convert elements matching this CSS selector (repeatable)
+    #[arg(long = "include", value_name = "SELECTOR", global = true)]
+    #[serde(deserialize_with = "one_or_many")]
+    pub include: Vec<String>,
+
+    /// Drop elements matching this CSS selector (repeatable)
+    #[arg(long = "exclude", value_name = "SELECTOR", global = true)]
+    #[serde(deserialize_with = "one_or_many")]
+    pub exclude: Vec<String>,
+
+    /// Never convert the contents of this tag (repeatable)
+    #[arg(long = "skip-tag", value_name = "TAG", global = true)]
+    #[serde(deserialize_with = "one_or_many")]
+    pub skip_tag: Vec<String>,
+
+    /// Convert this tag even though it is skipped by default, e.g. nav (repeatable)
+    #[arg(long = "keep-tag", value_name = "TAG", global = true)]
+    #[serde(deserialize_with = "one_or_many")]
+    pub keep_tag: Vec<String>,
+
+    /// Do not drop site headers, infoboxes, language lists and inline-styled elements
+    #[arg(long, global = true)]
+    pub no_default_excludes: bool,
+
+    /// Do not write the Title and Markdown Content lines
+    #[arg(long, global = true)]
+    pub no_preamble: bool,
+
+    /// Link style: inline, reference, footnote or strip
+    #[arg(long, global = true)]
+    pub link_style: Option<LinkStyle>,
+
+    /// Append a section listing every distinct link
+    #[arg(long, global = true)]
+    pub links_section: bool,
+
+    /// Keep relative link and image URLs instead of resolving them against the page URL
+    #[arg(long, global = true)]
+    pub keep_relative_urls: bool,
+
+    /// Write page metadata as front matter: none, yaml or toml
+    #[arg(long, global = true)]
+    pub front_matter: Option<FrontMatter>,
+}
+
+impl ConverterArgs {
+    /// Reads the options from a URL query string, where the repeatable ones
+    /// are given once per value: `?include=main&include=article`.
+    pub fn from_query(query: &str) -> Result<Self> {
+        let mut lists: [(&str, Vec<String>); 4] =
+            [("include", Vec::new()), ("exclude", Vec::new()), ("skip_tag", Vec::new()), ("keep_tag", Vec::new())];
+        let mut others = url::form_urlencoded::Serializer::new(String::new());
+        for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match lists.iter_mut().find(|(list, _)| *list == name) {
+                Some((_, values)) => values.push(value.into_owned()),
+                None => {
+                    others.append_pair(&name, &value);
+                },
+            }
+        }
+        let mut args: Self = serde_urlencoded::from_str(&others.finish())?;
+        let [(_, include), (_, exclude), (_, skip_tag), (_, keep_tag)] = lists;
+        args.include = include;
+        args.exclude = exclude;
+        args.skip_tag = skip_tag;
+        args.keep_tag = keep_tag;
+        Ok(args)
+    }
+
+    pub fn to_options(&self) -> Result<ConverterOptions> {
+        let mut builder = ConverterOptions::builder()
+            .extract_mode(self.extract)
+            .heading_separator(!self.no_heading_separator)
+            .resolve_urls(!self.keep_relative_urls)
+            .links_section(self.links_section)
+            .preamble(!self.no_preamble);
+        if self.no_default_excludes {
+            builder = builder.clear_exclude_selectors();
+        }
+        if let Some(heading_style) = self.heading_style {
+            builder = builder.heading_style(heading_style);
+        }
+        if let Some(horizontal_rule) = &self.horizontal_rule {
+            builder = builder.horizontal_rule(horizontal_rule.clone());
+        }
+        if let Some(bullet) = self.bullet {
+            builder = builder.bullet(bullet);
+        }
+        if let Some(emphasis) = self.emphasis {
+            builder = builder.emphasis(emphasis);
+        }
+        if let Some(strong) = self.strong {
+            builder = builder.strong(strong);
+        }
+        if let Some(code_fence) = self.code_fence {
+            builder = builder.code_fence(code_fence);
+        }
+        if let Some(link_style) = self.link_style {
+            builder = builder.link_style(link_style);
+        }
+        if let Some(front_matter) = self.front_matter {
+            builder = builder.front_matter(front_matter);
+        }
+        for selector in &self.include {
+            builder = builder.include_selector(selector.clone());
+        }
+        for selector in &self.exclude {
+            builder = builder.exclude_selector(selector.clone());
+        }
+        for tag in &self.skip_tag {
+            builder = builder.skip_tag(tag.clone());
+        }
+        for tag in &self.keep_tag {
+            builder = builder.keep_tag(tag);
+        }
+        builder.build()
+    }
+}
+
+/// Accepts either a single string (a query parameter) or a list of strings.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+#[cfg(test)]
+#[path = "options_test.rs"]
+mod tests;
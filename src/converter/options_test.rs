@@ -0,0 +1,182 @@
+use crate::converter::markdown_converter::MarkdownConverter;
+use crate::converter::options::{CodeFence, ConverterArgs, ConverterOptions, HeadingStyle, LinkStyle};
+
+fn convert(options: ConverterOptions, html: &str) -> String {
+    MarkdownConverter::with_options(options).convert(html).expect("Failed to convert HTML to Markdown")
+}
+
+#[test]
+fn test_default_options_keep_preamble_and_separator() {
+    let markdown = convert(
+        ConverterOptions::default(),
+        "<html><head><title>T</title></head><body><h1>Heading</h1></body></html>",
+    );
+    assert!(markdown.starts_with("Title: T\n\nMarkdown Content:\n"));
+    assert!(markdown.contains(&format!("\n{}\n\n# Heading\n", "-".repeat(74))));
+}
+
+#[test]
+fn test_setext_headings_without_separator() {
+    let options = ConverterOptions::builder()
+        .heading_style(HeadingStyle::Setext)
+        .heading_separator(false)
+        .preamble(false)
+        .build()
+        .unwrap();
+    let markdown = convert(options, "<html><body><h1>Top</h1><h2>Second level</h2><h3>Third</h3></body></html>");
+    assert!(markdown.contains("Top\n===\n"));
+    assert!(markdown.contains("Second level\n------------\n"));
+    assert!(markdown.contains("### Third"));
+    assert!(!markdown.contains("Markdown Content:"));
+    assert!(!markdown.contains(&"-".repeat(74)));
+}
+
+#[test]
+fn test_bullet_and_emphasis_delimiters() {
+    let options = ConverterOptions::builder()
+        .bullet('-')
+        .emphasis('*')
+        .strong('_')
+        .build()
+        .unwrap();
+    let markdown = convert(options, "<html><body><ul><li><em>a</em> and <strong>b</strong></li></ul></body></html>");
+    assert!(markdown.contains("- *a* and __b__"));
+}
+
+#[test]
+fn test_tilde_code_fence() {
+    let options = ConverterOptions::builder().code_fence(CodeFence::Tilde).build().unwrap();
+    let markdown = convert(options, "<html><body><pre><code>~~~\nnested\n~~~</code></pre></body></html>");
+    assert!(markdown.contains("~~~~text\n~~~\nnested\n~~~\n~~~~\n"));
+}
+
+#[test]
+fn test_include_and_exclude_selectors() {
+    let html = r#"
+        <html><body>
+            <div class="post"><p>Keep this.</p><p class="ad">Drop this.</p></div>
+            <div class="other"><p>Not included.</p></div>
+        </body></html>
+    "#;
+    let options = ConverterOptions::builder()
+        .include_selector(".post")
+        .exclude_selector(".ad")
+        .build()
+        .unwrap();
+    let markdown = convert(options, html);
+    assert!(markdown.contains("Keep this."));
+    assert!(!markdown.contains("Drop this."));
+    assert!(!markdown.contains("Not included."));
+}
+
+#[test]
+fn test_clear_default_excludes() {
+    let html = r#"<html><body><p style="color: red">Styled text.</p></body></html>"#;
+    assert!(!convert(ConverterOptions::default(), html).contains("Styled text."));
+
+    let options = ConverterOptions::builder().clear_exclude_selectors().build().unwrap();
+    assert!(convert(options, html).contains("Styled text."));
+}
+
+#[test]
+fn test_reference_links() {
+    let html = r#"
+        <html><body>
+            <p>See <a href="https://a.example">one</a>, <a href="https://b.example">two</a>
+            and <a href="https://a.example">one again</a>.</p>
+        </body></html>
+    "#;
+    let options = ConverterOptions::builder().link_style(LinkStyle::Reference).build().unwrap();
+    let markdown = convert(options, html);
+    assert!(markdown.contains("See [one][1], [two][2] and [one again][1]."));
+    assert!(markdown.ends_with("[1]: https://a.example\n[2]: https://b.example\n"));
+}
+
+#[test]
+fn test_invalid_options_are_rejected() {
+    assert!(ConverterOptions::builder().bullet('x').build().is_err());
+    assert!(ConverterOptions::builder().emphasis('~').build().is_err());
+    assert!(ConverterOptions::builder().include_selector("[[").build().is_err());
+}
+
+#[test]
+fn test_converter_args_from_query() {
+    let args: ConverterArgs = serde_urlencoded::from_str(
+        "heading_style=setext&bullet=-&exclude=.ads&no_preamble=true&link_style=reference",
+    ).unwrap();
+    assert_eq!(args.heading_style, Some(HeadingStyle::Setext));
+    assert_eq!(args.bullet, Some('-'));
+    assert_eq!(args.exclude, vec![".ads".to_string()]);
+    assert!(args.no_preamble);
+    assert_eq!(args.link_style, Some(LinkStyle::Reference));
+    assert!(args.to_options().is_ok());
+}
+
+#[test]
+fn test_converter_args_repeated_query_keys() {
+    let args = ConverterArgs::from_query(
+        "include=main&exclude=.ads&include=article%2C%20.post&keep_tag=nav&bullet=-&keep_tag=aside",
+    ).unwrap();
+    assert_eq!(args.include, ["main", "article, .post"]);
+    assert_eq!(args.exclude, [".ads"]);
+    assert!(args.skip_tag.is_empty());
+    assert_eq!(args.keep_tag, ["nav", "aside"]);
+    assert_eq!(args.bullet, Some('-'));
+    assert!(args.to_options().is_ok());
+
+    assert!(ConverterArgs::from_query("bullet=-&bullet=*").is_err());
+    assert!(ConverterArgs::from_query("no_preamble=maybe").is_err());
+}
+
+#[test]
+fn test_skip_and_keep_tags() {
+    let html = "<html><body><nav><p>Menu text.</p></nav><aside><p>Aside text.</p></aside></body></html>";
+    let markdown = convert(ConverterOptions::default(), html);
+    assert!(!markdown.contains("Menu text."));
+    assert!(markdown.contains("Aside text."));
+
+    let options = ConverterOptions::builder().keep_tag("nav").skip_tag("aside").build().unwrap();
+    let markdown = convert(options, html);
+    assert!(markdown.contains("Menu text."));
+    assert!(!markdown.contains("Aside text."));
+}
+
+const LINKS_HTML: &str = r#"
+    <html><body>
+        <p>Read <a href="https://a.example/very/long/path">the guide</a>, then
+        <a href="https://b.example">the FAQ</a> and <a href="https://a.example/very/long/path">guide again</a>.</p>
+    </body></html>
+"#;
+
+fn convert_links(link_style: LinkStyle, links_section: bool) -> String {
+    let options = ConverterOptions::builder()
+        .link_style(link_style)
+        .links_section(links_section)
+        .preamble(false)
+        .build()
+        .unwrap();
+    convert(options, LINKS_HTML)
+}
+
+#[test]
+fn test_footnote_links() {
+    let markdown = convert_links(LinkStyle::Footnote, false);
+    assert!(markdown.contains("Read the guide[^1], then the FAQ[^2] and guide again[^1]."));
+    assert!(markdown.ends_with("[^1]: https://a.example/very/long/path\n[^2]: https://b.example\n"));
+}
+
+#[test]
+fn test_strip_links() {
+    let markdown = convert_links(LinkStyle::Strip, false);
+    assert!(markdown.contains("Read the guide, then the FAQ and guide again."));
+    assert!(!markdown.contains("https://"));
+}
+
+#[test]
+fn test_links_section_is_deduplicated() {
+    let markdown = convert_links(LinkStyle::Strip, true);
+    assert!(markdown.contains("Read the guide, then the FAQ and guide again."));
+    assert!(markdown.ends_with(
+        "\n## Links\n\n* [the guide](https://a.example/very/long/path)\n* [the FAQ](https://b.example)\n"
+    ));
+}
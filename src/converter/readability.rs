@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use ego_tree::NodeId;
+use scraper::{ElementRef, Html, Node, Selector};
+use once_cell::sync::Lazy;
+
+static BODY_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("body").unwrap());
+static LINK_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("a").unwrap());
+
+/// Tags whose subtrees never contribute to the article.
+const IGNORED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "footer", "aside", "form",
+    "button", "select", "textarea", "iframe", "svg",
+];
+
+/// Tags that make a `div` a container rather than a paragraph.
+const BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "dl", "div", "fieldset", "figure",
+    "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "main",
+    "nav", "ol", "p", "pre", "section", "table", "ul",
+];
+
+const UNLIKELY_CANDIDATES: &[&str] = &[
+    "-ad-", "ad-break", "agegate", "banner", "breadcrumb", "combx", "comment", "community",
+    "consent", "cookie", "cover-wrap", "disqus", "extra", "gdpr", "legends", "menu",
+    "modal", "newsletter", "pager", "pagination", "popup", "promo", "related", "remark",
+    "replies", "rss", "share", "shoutbox", "sidebar", "skyscraper", "social", "sponsor",
+    "subscribe", "supplemental", "yom-remote",
+];
+const MAYBE_CANDIDATES: &[&str] = &["and", "article", "body", "column", "content", "main", "shadow"];
+
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "body", "content", "entry", "hentry", "h-entry", "main", "page", "post",
+    "text", "blog", "story",
+];
+const NEGATIVE_HINTS: &[&str] = &[
+    "-ad-", "banner", "combx", "comment", "com-", "contact", "cookie", "foot", "footer",
+    "footnote", "hidden", "masthead", "media", "meta", "outbrain", "promo", "related",
+    "scroll", "share", "shoutbox", "sidebar", "skyscraper", "sponsor", "shopping", "tags",
+    "tool", "widget",
+];
+
+/// Paragraphs shorter than this are too small to say anything about the page.
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// Finds the main content of a page by scoring paragraphs and crediting their
+/// ancestors, then merging in siblings of the best candidate that look like
+/// part of the same article. Returns the elements in document order, or an
+/// empty vector when nothing scores.
+pub fn extract(document: &Html) -> Vec<ElementRef<'_>> {
+    let body = document.select(&BODY_SELECTOR).next().unwrap_or_else(|| document.root_element());
+
+    let mut paragraphs = Vec::new();
+    collect_paragraphs(body, &mut paragraphs);
+
+    let mut scores: HashMap<NodeId, f64> = HashMap::new();
+    let mut candidates: Vec<ElementRef> = Vec::new();
+    for paragraph in paragraphs {
+        let text = collapsed_text(paragraph);
+        let length = text.chars().count();
+        if length < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+
+        // One point for the paragraph, one per comma, and up to three more
+        // for every hundred characters.
+        let score = 1.0 + text.matches(',').count() as f64 + (length as f64 / 100.0).min(3.0);
+
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(5);
+        for (level, ancestor) in ancestors.enumerate() {
+            if ancestor.value().name() == "html" {
+                break;
+            }
+            let entry = scores.entry(ancestor.id()).or_insert_with(|| {
+                candidates.push(ancestor);
+                initial_score(ancestor)
+            });
+            *entry += match level {
+                0 => score,
+                1 => score / 2.0,
+                _ => score / (level as f64 * 3.0),
+            };
+        }
+    }
+
+    // Pages full of links (navigation, indexes) should lose to prose.
+    for candidate in &candidates {
+        if let Some(score) = scores.get_mut(&candidate.id()) {
+            *score *= 1.0 - link_density(*candidate);
+        }
+    }
+
+    let Some(top) = candidates.iter()
+        .copied()
+        .max_by(|a, b| scores[&a.id()].total_cmp(&scores[&b.id()])) else {
+        return Vec::new();
+    };
+    let top_score = scores[&top.id()];
+
+    let Some(parent) = top.parent().and_then(ElementRef::wrap) else {
+        return vec![top];
+    };
+
+    let threshold = (top_score * 0.2).max(10.0);
+    let top_class = top.value().attr("class").unwrap_or("");
+    parent.children()
+        .filter_map(ElementRef::wrap)
+        .filter(|sibling| {
+            if *sibling == top {
+                return true;
+            }
+            if is_ignored(*sibling) {
+                return false;
+            }
+
+            let mut bonus = 0.0;
+            if !top_class.is_empty() && sibling.value().attr("class") == Some(top_class) {
+                bonus += top_score * 0.2;
+            }
+            if scores.get(&sibling.id()).is_some_and(|score| score + bonus >= threshold) {
+                return true;
+            }
+
+            if sibling.value().name() == "p" {
+                let text = collapsed_text(*sibling);
+                let length = text.chars().count();
+                let density = link_density(*sibling);
+                return (length > 80 && density < 0.25)
+                    || (length > 0 && density == 0.0 && text.contains(". "));
+            }
+            false
+        })
+        .collect()
+}
+
+/// Whether an element's class or id marks it as page chrome (banners,
+/// sidebars, comment threads, ...) rather than article content.
+pub fn is_unlikely_candidate(element: ElementRef) -> bool {
+    if matches!(element.value().name(), "body" | "a" | "article" | "main") {
+        return false;
+    }
+    if element.value().attr("hidden").is_some()
+        || element.value().attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    let hints = class_and_id(element);
+    if hints.is_empty() {
+        return false;
+    }
+    UNLIKELY_CANDIDATES.iter().any(|hint| hints.contains(hint))
+        && !MAYBE_CANDIDATES.iter().any(|hint| hints.contains(hint))
+}
+
+fn is_ignored(element: ElementRef) -> bool {
+    IGNORED_TAGS.contains(&element.value().name()) || is_unlikely_candidate(element)
+}
+
+/// Collects the elements that count as paragraphs: `p`, `pre`, `td` and
+/// `blockquote`, plus `div`s and `section`s that hold only inline content.
+fn collect_paragraphs<'a>(element: ElementRef<'a>, paragraphs: &mut Vec<ElementRef<'a>>) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        if is_ignored(child) {
+            continue;
+        }
+        match child.value().name() {
+            "p" | "pre" | "td" | "blockquote" => paragraphs.push(child),
+            "div" | "section" if !has_block_children(child) => paragraphs.push(child),
+            _ => collect_paragraphs(child, paragraphs),
+        }
+    }
+}
+
+fn has_block_children(element: ElementRef) -> bool {
+    element.children()
+        .filter_map(ElementRef::wrap)
+        .any(|child| BLOCK_TAGS.contains(&child.value().name()))
+}
+
+fn initial_score(element: ElementRef) -> f64 {
+    let tag_score = match element.value().name() {
+        "div" | "article" | "main" => 5.0,
+        "pre" | "td" | "blockquote" | "section" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    tag_score + class_weight(element)
+}
+
+/// +25 for each of class and id that hints at content, -25 for each that
+/// hints at chrome.
+fn class_weight(element: ElementRef) -> f64 {
+    let mut weight = 0.0;
+    for value in [element.value().attr("class"), element.value().attr("id")].into_iter().flatten() {
+        let value = value.to_ascii_lowercase();
+        if NEGATIVE_HINTS.iter().any(|hint| value.contains(hint)) {
+            weight -= 25.0;
+        }
+        if POSITIVE_HINTS.iter().any(|hint| value.contains(hint)) {
+            weight += 25.0;
+        }
+    }
+    weight
+}
+
+fn class_and_id(element: ElementRef) -> String {
+    let class = element.value().attr("class").unwrap_or("");
+    let id = element.value().attr("id").unwrap_or("");
+    format!("{} {}", class, id).trim().to_ascii_lowercase()
+}
+
+fn collapsed_text(element: ElementRef) -> String {
+    let mut text = String::new();
+    for node in element.descendants() {
+        if let Node::Text(t) = node.value() {
+            text.push_str(t);
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Fraction of an element's text that sits inside links.
+fn link_density(element: ElementRef) -> f64 {
+    let length = collapsed_text(element).chars().count();
+    if length == 0 {
+        return 0.0;
+    }
+    let link_length: usize = element.select(&LINK_SELECTOR)
+        .map(|link| collapsed_text(link).chars().count())
+        .sum();
+    link_length as f64 / length as f64
+}
+
+#[cfg(test)]
+#[path = "readability_test.rs"]
+mod tests;
@@ -0,0 +1,76 @@
+use crate::converter::markdown_converter::{ExtractMode, MarkdownConverter};
+use crate::converter::options::ConverterOptions;
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/readability/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+fn convert(name: &str, mode: ExtractMode) -> String {
+    let options = ConverterOptions::builder().extract_mode(mode).build().unwrap();
+    MarkdownConverter::with_options(options)
+        .convert(&fixture(name))
+        .expect("Failed to convert HTML to Markdown")
+}
+
+/// Each fixture lists text that must survive extraction and chrome that must not.
+const CORPUS: &[(&str, &[&str], &[&str])] = &[
+    (
+        "news_article.html",
+        &[
+            "# City Council Approves New Cycling Network",
+            "twelve-kilometre network of protected cycle lanes",
+            "publish the figures every quarter",
+        ],
+        &["We use cookies", "Most read", "Residents react", "All rights reserved"],
+    ),
+    (
+        "blog_post.html",
+        &[
+            "## Notes on async Rust cancellation",
+            "```rust",
+            "cleanup must live in a `Drop` implementation",
+            "A common mitigation is to move the critical section",
+        ],
+        &["3 Comments", "bitten by this exact problem", "ferris.dev"],
+    ),
+    (
+        "docs_page.html",
+        &[
+            "# Configuration",
+            "`WIDGET_CONFIG` environment variable",
+            "| port    | 8080    |",
+            "Unknown keys are rejected at startup",
+        ],
+        &["Frequently asked questions", "Subscribe to our newsletter"],
+    ),
+];
+
+#[test]
+fn test_readability_corpus() {
+    for (name, expected, unexpected) in CORPUS {
+        let markdown = convert(name, ExtractMode::Readability);
+        for text in *expected {
+            assert!(markdown.contains(text), "{}: missing {:?} in\n{}", name, text, markdown);
+        }
+        for text in *unexpected {
+            assert!(!markdown.contains(text), "{}: unexpected {:?} in\n{}", name, text, markdown);
+        }
+    }
+}
+
+#[test]
+fn test_full_mode_keeps_page_chrome() {
+    let markdown = convert("news_article.html", ExtractMode::Full);
+    assert!(markdown.contains("We use cookies"));
+    assert!(markdown.contains("Most read"));
+}
+
+#[test]
+fn test_readability_falls_back_without_candidates() {
+    let options = ConverterOptions::builder().extract_mode(ExtractMode::Readability).build().unwrap();
+    let markdown = MarkdownConverter::with_options(options)
+        .convert("<html><body><h1>Short</h1></body></html>")
+        .unwrap();
+    assert!(markdown.contains("# Short"));
+}
@@ -0,0 +1,354 @@
+use scraper::{ElementRef, Selector};
+use once_cell::sync::Lazy;
+
+use super::markdown_converter::MarkdownConverter;
+
+static NESTED_TABLE_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("td table, th table").unwrap());
+static BLOCK_CONTENT_SELECTOR: Lazy<Selector> = Lazy::new(|| {
+    Selector::parse("ul, ol, dl, pre, blockquote, hr, h1, h2, h3, h4, h5, h6").unwrap()
+});
+static PARAGRAPH_SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("p").unwrap());
+
+/// Upper bounds on spans so a hostile `colspan="100000"` cannot blow up the grid.
+const MAX_COLSPAN: usize = 1000;
+const MAX_ROWSPAN: usize = 65534;
+/// Upper bound on the slots of the whole grid, which many moderately wide
+/// rows could otherwise push to millions. Larger tables are kept as HTML.
+const MAX_CELLS: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alignment {
+    Default,
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    fn from_cell(cell: ElementRef) -> Self {
+        let align = cell.value().attr("align").map(str::to_string).or_else(|| {
+            cell.value().attr("style").and_then(|style| {
+                style.split(';')
+                    .filter_map(|declaration| declaration.split_once(':'))
+                    .find(|(property, _)| property.trim().eq_ignore_ascii_case("text-align"))
+                    .map(|(_, value)| value.trim().to_string())
+            })
+        });
+        match align.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            Some("left") | Some("start") => Alignment::Left,
+            Some("center") => Alignment::Center,
+            Some("right") | Some("end") => Alignment::Right,
+            _ => Alignment::Default,
+        }
+    }
+
+    fn delimiter(self, width: usize) -> String {
+        match self {
+            Alignment::Default => "-".repeat(width),
+            Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+            Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+            Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+        }
+    }
+}
+
+/// A table normalized into a rectangular grid. Cells covered by a `colspan`
+/// or `rowspan` are `None`; the spanning cell itself sits at its top-left slot.
+struct TableGrid<'a> {
+    rows: Vec<Vec<Option<ElementRef<'a>>>>,
+    has_header: bool,
+}
+
+impl<'a> TableGrid<'a> {
+    /// The grid of `table`, or `None` when it would exceed [`MAX_CELLS`].
+    fn new(table: ElementRef<'a>) -> Option<Self> {
+        let mut header_rows = Vec::new();
+        let mut body_rows = Vec::new();
+        let mut footer_rows = Vec::new();
+        for child in table.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "thead" => header_rows.extend(section_rows(child)),
+                "tbody" => body_rows.extend(section_rows(child)),
+                "tfoot" => footer_rows.extend(section_rows(child)),
+                "tr" => body_rows.push(child),
+                _ => {}
+            }
+        }
+
+        let header_count = header_rows.len();
+        let source_rows: Vec<ElementRef> = header_rows.into_iter()
+            .chain(body_rows)
+            .chain(footer_rows)
+            .collect();
+
+        let mut rows: Vec<Vec<Option<ElementRef>>> = Vec::with_capacity(source_rows.len());
+        // Remaining rowspan for each column, carried from rows above.
+        let mut pending: Vec<usize> = Vec::new();
+        let mut cells_total = 0;
+        for (row_index, row) in source_rows.iter().enumerate() {
+            let remaining_rows = source_rows.len() - row_index;
+            let mut grid_row: Vec<Option<ElementRef>> = Vec::new();
+            let mut column = 0;
+            let mut cells = row_cells(*row);
+            // Columns from here on are only written once passed, so the
+            // last one still spanned into this row is known upfront.
+            let last_pending = pending.iter().rposition(|&rows| rows > 0);
+            loop {
+                if cells_total + grid_row.len() > MAX_CELLS {
+                    return None;
+                }
+                if column < pending.len() && pending[column] > 0 {
+                    pending[column] -= 1;
+                    grid_row.push(None);
+                    column += 1;
+                    continue;
+                }
+                let Some(cell) = cells.next() else {
+                    if last_pending.is_some_and(|last| column <= last) {
+                        grid_row.push(None);
+                        column += 1;
+                        continue;
+                    }
+                    break;
+                };
+                let colspan = span_attr(cell, "colspan").clamp(1, MAX_COLSPAN);
+                let rowspan = match span_attr(cell, "rowspan") {
+                    // rowspan="0" extends to the end of the table.
+                    0 => remaining_rows,
+                    rows => rows.min(MAX_ROWSPAN).min(remaining_rows),
+                };
+                for offset in 0..colspan {
+                    grid_row.push(if offset == 0 { Some(cell) } else { None });
+                    if pending.len() <= column {
+                        pending.resize(column + 1, 0);
+                    }
+                    pending[column] = rowspan - 1;
+                    column += 1;
+                }
+            }
+            cells_total += grid_row.len();
+            rows.push(grid_row);
+        }
+
+        // The header is the first row left once empty rows are dropped: a
+        // thead row, or a row of th cells. Any further thead rows are kept
+        // as ordinary rows rather than dropped.
+        let has_header = rows.iter()
+            .position(|row| !row.is_empty())
+            .is_some_and(|index| index < header_count || is_header_row(source_rows[index]));
+        rows.retain(|row| !row.is_empty());
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if rows.len().saturating_mul(columns) > MAX_CELLS {
+            return None;
+        }
+        for row in &mut rows {
+            row.resize(columns, None);
+        }
+
+        Some(Self { rows, has_header })
+    }
+
+    fn columns(&self) -> usize {
+        self.rows.first().map(Vec::len).unwrap_or(0)
+    }
+
+    /// Alignment per column, taken from the first cell in each column that
+    /// declares one.
+    fn alignments(&self) -> Vec<Alignment> {
+        (0..self.columns())
+            .map(|column| {
+                self.rows.iter()
+                    .filter_map(|row| row[column])
+                    .map(Alignment::from_cell)
+                    .find(|alignment| *alignment != Alignment::Default)
+                    .unwrap_or(Alignment::Default)
+            })
+            .collect()
+    }
+
+    fn has_block_content(&self) -> bool {
+        self.rows.iter().flatten().flatten().any(|cell| {
+            cell.select(&BLOCK_CONTENT_SELECTOR).next().is_some()
+                || cell.select(&PARAGRAPH_SELECTOR).nth(1).is_some()
+        })
+    }
+}
+
+fn section_rows(section: ElementRef) -> impl Iterator<Item = ElementRef> {
+    section.children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| child.value().name() == "tr")
+}
+
+fn row_cells(row: ElementRef) -> impl Iterator<Item = ElementRef> {
+    row.children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| matches!(child.value().name(), "td" | "th"))
+}
+
+/// Whether `row` has cells and they are all `th`.
+fn is_header_row(row: ElementRef) -> bool {
+    let mut cells = row_cells(row).peekable();
+    cells.peek().is_some() && cells.all(|cell| cell.value().name() == "th")
+}
+
+fn span_attr(cell: ElementRef, name: &str) -> usize {
+    cell.value().attr(name)
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(1)
+}
+
+/// Escapes text so it stays inside a single GFM table cell.
+fn escape_cell(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '|' => escaped.push_str("\\|"),
+            '\n' => {
+                let trimmed_len = escaped.trim_end().len();
+                escaped.truncate(trimmed_len);
+                escaped.push_str("<br>");
+            },
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl MarkdownConverter {
+    /// Renders a table as a GFM pipe table, falling back to raw HTML for
+    /// nested tables and tables too large to lay out, and to a list for
+    /// cells with block content, none of which a pipe table can express.
+    pub(super) fn process_table(&self, table: ElementRef, markdown: &mut String) {
+        let grid = match table.select(&NESTED_TABLE_SELECTOR).next() {
+            None => TableGrid::new(table),
+            Some(_) => None,
+        };
+        let Some(grid) = grid else {
+            markdown.push_str(&table.html());
+            markdown.push_str("\n\n");
+            return;
+        };
+
+        if let Some(caption) = table.children()
+            .filter_map(ElementRef::wrap)
+            .find(|child| child.value().name() == "caption") {
+            let caption = self.render_inline(caption);
+            if !caption.is_empty() {
+                markdown.push_str(&format!("{}\n\n", caption));
+            }
+        }
+
+        if grid.columns() == 0 {
+            return;
+        }
+
+        if grid.has_block_content() {
+            self.process_table_as_list(&grid, markdown);
+        } else {
+            self.process_table_as_pipe_table(&grid, markdown);
+        }
+    }
+
+    fn process_table_as_pipe_table(&self, grid: &TableGrid, markdown: &mut String) {
+        let mut rows: Vec<Vec<String>> = grid.rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.map(|cell| escape_cell(&self.render_inline(cell))).unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        if !grid.has_header {
+            rows.insert(0, vec![String::new(); grid.columns()]);
+        }
+
+        let alignments = grid.alignments();
+        let widths: Vec<usize> = (0..grid.columns())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(3)
+            })
+            .collect();
+
+        let format_row = |row: &[String]| {
+            let cells: Vec<String> = row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!(" {}{} ", cell, " ".repeat(width - cell.chars().count())))
+                .collect();
+            format!("|{}|\n", cells.join("|"))
+        };
+
+        markdown.push_str(&format_row(&rows[0]));
+        let delimiters: Vec<String> = alignments.iter()
+            .zip(&widths)
+            .map(|(alignment, width)| format!(" {} ", alignment.delimiter(*width)))
+            .collect();
+        markdown.push_str(&format!("|{}|\n", delimiters.join("|")));
+        for row in &rows[1..] {
+            markdown.push_str(&format_row(row));
+        }
+        markdown.push('\n');
+    }
+
+    /// Renders each row as a list item whose nested items are the remaining
+    /// cells, labelled with their column header when there is one.
+    fn process_table_as_list(&self, grid: &TableGrid, markdown: &mut String) {
+        let headers: Vec<String> = if grid.has_header {
+            grid.rows[0].iter()
+                .map(|cell| cell.map(|cell| self.render_inline(cell)).unwrap_or_default())
+                .collect()
+        } else {
+            vec![String::new(); grid.columns()]
+        };
+        let body = if grid.has_header { &grid.rows[1..] } else { &grid.rows[..] };
+
+        markdown.push('\n');
+        for row in body {
+            let mut first = true;
+            for (cell, header) in row.iter().zip(&headers) {
+                let Some(cell) = cell else { continue };
+                let mut content = String::new();
+                self.process_children(*cell, &mut content);
+                let content = content.trim();
+                if content.is_empty() {
+                    continue;
+                }
+
+                let bullet = self.options().bullet;
+                let strong = &self.options().strong;
+                let (marker, indent) = if first {
+                    (format!("{} ", bullet), "  ")
+                } else {
+                    (format!("  {} ", bullet), "    ")
+                };
+                first = false;
+                let mut lines = content.lines();
+                if header.is_empty() {
+                    markdown.push_str(&format!("{}{}\n", marker, lines.next().unwrap_or_default()));
+                } else if content.contains('\n') {
+                    // Multi-line content goes below its label so nested blocks
+                    // such as lists keep their own markers.
+                    markdown.push_str(&format!("{}{}{}:{}\n", marker, strong, header, strong));
+                } else {
+                    markdown.push_str(&format!("{}{}{}:{} {}\n", marker, strong, header, strong, content));
+                    continue;
+                }
+                for line in lines {
+                    if line.is_empty() {
+                        markdown.push('\n');
+                    } else {
+                        markdown.push_str(&format!("{}{}\n", indent, line));
+                    }
+                }
+            }
+        }
+        markdown.push('\n');
+    }
+}
+
+#[cfg(test)]
+#[path = "table_test.rs"]
+mod tests;
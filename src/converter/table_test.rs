@@ -0,0 +1,157 @@
+use crate::converter::markdown_converter::MarkdownConverter;
+use crate::converter::options::ConverterOptions;
+
+fn convert(html: &str) -> String {
+    MarkdownConverter::new().convert(html).expect("Failed to convert HTML to Markdown")
+}
+
+#[test]
+fn test_table_without_tbody_or_thead() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <tr><th>Name</th><th>Age</th></tr>
+                <tr><td>Alice</td><td>30</td></tr>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("| Name  | Age |\n| ----- | --- |\n| Alice | 30  |\n"));
+}
+
+#[test]
+fn test_table_synthesizes_header() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <tr><td>a</td><td>b</td></tr>
+                <tr><td>c</td><td>d</td></tr>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("|     |     |\n| --- | --- |\n| a   | b   |\n| c   | d   |\n"));
+}
+
+#[test]
+fn test_table_th_in_body_rows() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <thead><tr><th>Key</th><th>Value</th></tr></thead>
+                <tbody><tr><th>Host</th><td>example.com</td></tr></tbody>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("| Host | example.com |"));
+}
+
+#[test]
+fn test_table_colspan_and_rowspan() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <tr><th colspan="2">Wide</th><th>C</th></tr>
+                <tr><td rowspan="2">Tall</td><td>1</td><td>2</td></tr>
+                <tr><td>3</td><td>4</td></tr>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("| Wide |     | C   |\n"));
+    assert!(markdown.contains("| Tall | 1   | 2   |\n"));
+    assert!(markdown.contains("|      | 3   | 4   |\n"));
+}
+
+#[test]
+fn test_table_escapes_pipes_and_newlines() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <tr><th>Expr</th></tr>
+                <tr><td>a | b<br>c</td></tr>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("| a \\| b<br>c |"));
+}
+
+#[test]
+fn test_table_alignment() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <tr><th align="left">L</th><th style="text-align: center">C</th><th align="right">R</th><th>D</th></tr>
+                <tr><td>1</td><td>2</td><td>3</td><td>4</td></tr>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("| :-- | :-: | --: | --- |"));
+}
+
+#[test]
+fn test_nested_table_falls_back_to_html() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <tr><td><table><tr><td>inner</td></tr></table></td></tr>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("<table>"));
+    assert!(markdown.contains("inner"));
+}
+
+#[test]
+fn test_block_content_falls_back_to_list() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <tr><th>Step</th><th>Details</th></tr>
+                <tr><td>Install</td><td><ul><li>Download</li><li>Unpack</li></ul></td></tr>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("* **Step:** Install\n  * **Details:**\n    * Download\n    * Unpack\n"));
+}
+
+#[test]
+fn test_list_fallback_uses_strong_delimiter() {
+    let options = ConverterOptions::builder().strong('_').build().unwrap();
+    let markdown = MarkdownConverter::with_options(options).convert(r#"
+        <html><body>
+            <table>
+                <tr><th>Step</th><th>Details</th></tr>
+                <tr><td>Install</td><td><ul><li>Download</li><li>Unpack</li></ul></td></tr>
+            </table>
+        </body></html>
+    "#).unwrap();
+    assert!(markdown.contains("* __Step:__ Install\n  * __Details:__\n    * Download\n    * Unpack\n"), "{}", markdown);
+}
+
+#[test]
+fn test_table_empty_thead_row_is_not_a_header() {
+    let markdown = convert(r#"
+        <html><body>
+            <table>
+                <thead><tr></tr></thead>
+                <tbody>
+                    <tr><td>a</td><td>b</td></tr>
+                    <tr><td>c</td><td>d</td></tr>
+                </tbody>
+            </table>
+        </body></html>
+    "#);
+    assert!(markdown.contains("|     |     |\n| --- | --- |\n| a   | b   |\n| c   | d   |\n"), "{}", markdown);
+}
+
+#[test]
+fn test_oversized_table_falls_back_to_html() {
+    let row = format!("<tr>{}</tr>", r#"<td colspan="1000">x</td>"#.repeat(5));
+    let html = format!(
+        r#"<html><body><table><tr><td rowspan="65534">tall</td></tr>{}</table></body></html>"#,
+        row.repeat(200)
+    );
+    let started = std::time::Instant::now();
+    let markdown = convert(&html);
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(markdown.contains("<table>"), "{}", &markdown[..200.min(markdown.len())]);
+    assert!(!markdown.contains("| ---"));
+}
@@ -0,0 +1,78 @@
+use scraper::ElementRef;
+use url::{ParseError, Url};
+
+/// Attributes lazy-loading scripts use to hold the real image URL while
+/// `src` points at a placeholder.
+const LAZY_SRC_ATTRIBUTES: &[&str] = &["data-src", "data-lazy-src", "data-original", "data-url"];
+const LAZY_SRCSET_ATTRIBUTES: &[&str] = &["data-srcset", "data-lazy-srcset"];
+
+/// Resolves `url` against `base`. Absolute URLs, fragment-only links and
+/// anything that fails to join are returned unchanged.
+pub fn resolve(base: Option<&Url>, url: &str) -> String {
+    let url = url.trim();
+    let Some(base) = base else {
+        return url.to_string();
+    };
+    if url.is_empty() || url.starts_with('#') {
+        return url.to_string();
+    }
+    match Url::parse(url) {
+        Err(ParseError::RelativeUrlWithoutBase) => base.join(url)
+            .map(|resolved| resolved.to_string())
+            .unwrap_or_else(|_| url.to_string()),
+        _ => url.to_string(),
+    }
+}
+
+/// Computes the base URL for a document: the page URL, overridden by a
+/// `<base href>` (which may itself be relative to the page URL).
+pub fn document_base(source_url: Option<&Url>, base_href: Option<&str>) -> Option<Url> {
+    match (source_url, base_href.map(str::trim).filter(|href| !href.is_empty())) {
+        (Some(source), Some(href)) => source.join(href).ok().or_else(|| Some(source.clone())),
+        (None, Some(href)) => Url::parse(href).ok(),
+        (Some(source), None) => Some(source.clone()),
+        (None, None) => None,
+    }
+}
+
+/// Picks the best candidate from a `srcset`: the largest width (`640w`) or
+/// pixel density (`2x`) descriptor.
+pub fn best_srcset_candidate(srcset: &str) -> Option<&str> {
+    srcset.split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_whitespace();
+            let url = parts.next()?;
+            let size = parts.next()
+                .and_then(|descriptor| {
+                    descriptor.strip_suffix('w')
+                        .or_else(|| descriptor.strip_suffix('x'))
+                        .and_then(|value| value.parse::<f64>().ok())
+                })
+                .unwrap_or(1.0);
+            Some((url, size))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(url, _)| url)
+}
+
+/// Finds the URL an `img` actually displays, preferring lazy-loading
+/// attributes over a placeholder `src` and falling back to `srcset`.
+pub fn image_source<'a>(img: ElementRef<'a>) -> Option<&'a str> {
+    let element = img.value();
+    let attr = |name: &str| element.attr(name).map(str::trim).filter(|value| !value.is_empty());
+
+    let lazy_src = LAZY_SRC_ATTRIBUTES.iter().find_map(|name| attr(name));
+    let src = attr("src").filter(|src| !src.starts_with("data:"));
+    let srcset = || {
+        LAZY_SRCSET_ATTRIBUTES.iter()
+            .chain(["srcset"].iter())
+            .find_map(|name| attr(name))
+            .and_then(best_srcset_candidate)
+    };
+
+    lazy_src.or(src).or_else(srcset).or_else(|| attr("src"))
+}
+
+#[cfg(test)]
+#[path = "urls_test.rs"]
+mod tests;
@@ -0,0 +1,88 @@
+use scraper::{Html, Selector};
+use url::Url;
+
+use crate::converter::markdown_converter::MarkdownConverter;
+use crate::converter::options::ConverterOptions;
+use crate::converter::urls::{best_srcset_candidate, document_base, image_source, resolve};
+
+#[test]
+fn test_resolve() {
+    let base = Url::parse("https://example.com/docs/guide/intro.html").unwrap();
+    assert_eq!(resolve(Some(&base), "/docs/intro"), "https://example.com/docs/intro");
+    assert_eq!(resolve(Some(&base), "../img.png"), "https://example.com/docs/img.png");
+    assert_eq!(resolve(Some(&base), "//cdn.example.com/a.js"), "https://cdn.example.com/a.js");
+    assert_eq!(resolve(Some(&base), "#section"), "#section");
+    assert_eq!(resolve(Some(&base), "mailto:me@example.com"), "mailto:me@example.com");
+    assert_eq!(resolve(Some(&base), "https://other.example/x"), "https://other.example/x");
+    assert_eq!(resolve(None, "/docs/intro"), "/docs/intro");
+}
+
+#[test]
+fn test_document_base_honors_base_href() {
+    let source = Url::parse("https://example.com/a/page.html").unwrap();
+    assert_eq!(
+        document_base(Some(&source), Some("/static/")).unwrap().as_str(),
+        "https://example.com/static/",
+    );
+    assert_eq!(document_base(Some(&source), None).unwrap(), source);
+    assert_eq!(
+        document_base(None, Some("https://cdn.example.com/")).unwrap().as_str(),
+        "https://cdn.example.com/",
+    );
+    assert!(document_base(None, Some("relative/")).is_none());
+}
+
+#[test]
+fn test_best_srcset_candidate() {
+    assert_eq!(best_srcset_candidate("small.jpg 320w, large.jpg 1024w, medium.jpg 640w"), Some("large.jpg"));
+    assert_eq!(best_srcset_candidate("a.png, b.png 2x"), Some("b.png"));
+    assert_eq!(best_srcset_candidate(""), None);
+}
+
+#[test]
+fn test_image_source_prefers_lazy_attributes() {
+    let html = Html::parse_fragment(r#"
+        <img id="lazy" src="data:image/gif;base64,R0lGOD" data-src="/real.jpg">
+        <img id="srcset" src="data:image/gif;base64,R0lGOD" srcset="/a.jpg 1x, /b.jpg 2x">
+        <img id="plain" src="/plain.jpg">
+    "#);
+    let image = |id: &str| {
+        let selector = Selector::parse(&format!("#{}", id)).unwrap();
+        image_source(html.select(&selector).next().unwrap()).map(str::to_string)
+    };
+    assert_eq!(image("lazy").as_deref(), Some("/real.jpg"));
+    assert_eq!(image("srcset").as_deref(), Some("/b.jpg"));
+    assert_eq!(image("plain").as_deref(), Some("/plain.jpg"));
+}
+
+#[test]
+fn test_converter_resolves_relative_urls() {
+    let html = r#"
+        <html><body>
+            <p><a href="/docs/intro">Intro</a> <img src="../img.png" alt="Diagram"></p>
+            <video poster="poster.jpg" src="clip.mp4"></video>
+        </body></html>
+    "#;
+    let source = Url::parse("https://example.com/guide/start/").unwrap();
+
+    let markdown = MarkdownConverter::new().with_source_url(source.clone()).convert(html).unwrap();
+    assert!(markdown.contains("[Intro](https://example.com/docs/intro)"));
+    assert!(markdown.contains("![Diagram](https://example.com/guide/img.png)"));
+    assert!(markdown.contains("[![Video](https://example.com/guide/start/poster.jpg)](https://example.com/guide/start/clip.mp4)"));
+
+    let options = ConverterOptions::builder().resolve_urls(false).build().unwrap();
+    let markdown = MarkdownConverter::with_options(options).with_source_url(source).convert(html).unwrap();
+    assert!(markdown.contains("[Intro](/docs/intro)"));
+    assert!(markdown.contains("![Diagram](../img.png)"));
+}
+
+#[test]
+fn test_converter_honors_base_href() {
+    let html = r#"
+        <html><head><base href="https://cdn.example.com/assets/"></head>
+        <body><p><img src="logo.png" alt="Logo"></p></body></html>
+    "#;
+    let source = Url::parse("https://example.com/page").unwrap();
+    let markdown = MarkdownConverter::new().with_source_url(source).convert(html).unwrap();
+    assert!(markdown.contains("![Logo](https://cdn.example.com/assets/logo.png)"));
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// Buckets kept before full ones are dropped; a full bucket is the same
+/// as no bucket.
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// Who may use the server and how often.
+#[derive(Clone, Debug, Default)]
+pub struct AccessConfig {
+    /// Keys accepted as `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+    /// Without any, the server is open.
+    pub api_keys: Vec<String>,
+    /// Limit for each API key, or each IP address when there are no keys.
+    pub rate_limit: Option<RateLimit>,
+    /// Limit for each IP address, whatever key it uses, checked before the
+    /// key so guessing keys is throttled too.
+    pub ip_rate_limit: Option<RateLimit>,
+    /// Origins browsers may call the server from; any origin when empty.
+    pub cors_origins: Vec<HeaderValue>,
+    /// Reverse proxies whose `X-Forwarded-For` or `Forwarded` header names
+    /// the client. Requests from other addresses are limited by their own.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// A token bucket: up to `burst` requests at once, refilled at
+/// `per_minute` requests a minute.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// `per_minute` requests a minute, all of which may come at once.
+    pub fn per_minute(per_minute: u32) -> Self {
+        Self { per_minute, burst: per_minute }
+    }
+}
+
+/// Why a request was turned away.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Denied {
+    /// No API key, or an unknown one.
+    Unauthorized,
+    /// The client is out of requests until `retry_after` has passed.
+    RateLimited { retry_after: Duration },
+}
+
+/// Enforces an [`AccessConfig`], keeping the rate limit state.
+#[derive(Debug, Default)]
+pub struct Access {
+    config: AccessConfig,
+    clients: Limiter,
+    ips: Limiter,
+}
+
+impl Access {
+    pub fn new(config: AccessConfig) -> Self {
+        Self { config, clients: Limiter::default(), ips: Limiter::default() }
+    }
+
+    /// Checks a request from `peer` with `headers` against the IP limit,
+    /// the API keys and the client limit, in that order.
+    pub fn check(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> Result<(), Denied> {
+        let ip = self.client_ip(headers, peer).map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+        if let Some(limit) = self.config.ip_rate_limit {
+            self.ips.take(&ip, limit)?;
+        }
+        let client = if self.config.api_keys.is_empty() {
+            format!("ip {}", ip)
+        } else {
+            let key = presented_key(headers).ok_or(Denied::Unauthorized)?;
+            let index = self.config.api_keys.iter()
+                .position(|known| constant_time_eq(known.as_bytes(), key.as_bytes()))
+                .ok_or(Denied::Unauthorized)?;
+            format!("key {}", index)
+        };
+        if let Some(limit) = self.config.rate_limit {
+            self.clients.take(&client, limit)?;
+        }
+        Ok(())
+    }
+
+    /// The address a request comes from: `peer`, or when that is a trusted
+    /// proxy, the nearest address before it in the forwarding headers. Those
+    /// further on were sent by the client and could be anything.
+    fn client_ip(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+        let trusted = |ip: &IpAddr| self.config.trusted_proxies.contains(ip);
+        let mut client = peer?;
+        if !trusted(&client) {
+            return Some(client);
+        }
+        for hop in forwarded_for(headers).into_iter().rev() {
+            let Some(ip) = hop else {
+                break;
+            };
+            client = ip;
+            if !trusted(&client) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    /// CORS for the configured origins. Credentials are never allowed, so
+    /// API keys cannot be sent by a browser on behalf of a visitor.
+    pub fn cors_layer(&self) -> CorsLayer {
+        let origins = if self.config.cors_origins.is_empty() {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(self.config.cors_origins.iter().cloned())
+        };
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers([header::RETRY_AFTER, header::HeaderName::from_static("x-cache")])
+    }
+}
+
+/// The key sent as a bearer token or in `X-API-Key`.
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(value) = headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()) {
+        let (scheme, token) = value.trim().split_once(' ')?;
+        return scheme.eq_ignore_ascii_case("bearer").then(|| token.trim());
+    }
+    headers.get("x-api-key").and_then(|value| value.to_str().ok()).map(str::trim)
+}
+
+/// The addresses in `X-Forwarded-For`, or failing that the `for=` of
+/// `Forwarded`, client first. Hidden or malformed ones are `None`.
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let elements = |name: &'static str| {
+        headers.get_all(name).iter().filter_map(|value| value.to_str().ok()).flat_map(|value| value.split(','))
+    };
+    if headers.contains_key("x-forwarded-for") {
+        return elements("x-forwarded-for").map(parse_node).collect();
+    }
+    elements("forwarded")
+        .map(|element| {
+            element.split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| parse_node(node))
+        })
+        .collect()
+}
+
+/// An address as proxies write it: `192.0.2.1`, `192.0.2.1:4711`,
+/// `2001:db8::1` or `"[2001:db8::1]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.rsplit_once(':')?.0.parse::<std::net::Ipv4Addr>().ok().map(IpAddr::V4)
+}
+
+/// Compares without returning early, so response times do not reveal how
+/// much of a key was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Token buckets by client.
+#[derive(Debug, Default)]
+struct Limiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Limiter {
+    /// Takes a token from the bucket of `client`, or tells how long until
+    /// one is available.
+    fn take(&self, client: &str, limit: RateLimit) -> Result<(), Denied> {
+        let rate = f64::from(limit.per_minute) / 60.0;
+        let burst = f64::from(limit.burst.max(1));
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_IDLE_BUCKETS {
+            buckets.retain(|_, bucket| refill(bucket, now, rate, burst) < burst);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket { tokens: burst, updated: now });
+        let tokens = refill(bucket, now, rate, burst);
+        *bucket = Bucket { tokens, updated: now };
+        if tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = if rate > 0.0 { (1.0 - tokens) / rate } else { 60.0 };
+            Err(Denied::RateLimited { retry_after: Duration::from_secs_f64(wait) })
+        }
+    }
+}
+
+fn refill(bucket: &Bucket, now: Instant, rate: f64, burst: f64) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * rate).min(burst)
+}
+
+#[cfg(test)]
+#[path = "access_test.rs"]
+mod tests;
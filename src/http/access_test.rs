@@ -0,0 +1,151 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use axum::http::{HeaderMap, HeaderValue};
+
+use super::{constant_time_eq, parse_node, presented_key, Access, AccessConfig, Denied, RateLimit};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    pairs.iter()
+        .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_str(value).unwrap()))
+        .collect()
+}
+
+fn ip(ip: &str) -> Option<IpAddr> {
+    Some(ip.parse().unwrap())
+}
+
+#[test]
+fn test_presented_key() {
+    assert_eq!(presented_key(&headers(&[("authorization", "Bearer secret")])), Some("secret"));
+    assert_eq!(presented_key(&headers(&[("authorization", "bearer  secret ")])), Some("secret"));
+    assert_eq!(presented_key(&headers(&[("x-api-key", "secret")])), Some("secret"));
+    assert_eq!(presented_key(&headers(&[("authorization", "Basic c2VjcmV0")])), None);
+    assert_eq!(presented_key(&headers(&[("authorization", "secret")])), None);
+    assert_eq!(presented_key(&HeaderMap::new()), None);
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"secret", b"secret"));
+    assert!(!constant_time_eq(b"secret", b"secreT"));
+    assert!(!constant_time_eq(b"secret", b"secret2"));
+    assert!(!constant_time_eq(b"", b"secret"));
+}
+
+#[test]
+fn test_check_api_keys() {
+    let access = Access::new(AccessConfig {
+        api_keys: vec!["one".to_string(), "two".to_string()],
+        ..AccessConfig::default()
+    });
+    assert_eq!(access.check(&headers(&[("authorization", "Bearer one")]), None), Ok(()));
+    assert_eq!(access.check(&headers(&[("x-api-key", "two")]), None), Ok(()));
+    assert_eq!(access.check(&headers(&[("x-api-key", "three")]), None), Err(Denied::Unauthorized));
+    assert_eq!(access.check(&HeaderMap::new(), None), Err(Denied::Unauthorized));
+
+    // Without keys, the server is open
+    assert_eq!(Access::default().check(&HeaderMap::new(), None), Ok(()));
+}
+
+#[test]
+fn test_rate_limit_per_key() {
+    let access = Access::new(AccessConfig {
+        api_keys: vec!["one".to_string(), "two".to_string()],
+        rate_limit: Some(RateLimit::per_minute(2)),
+        ..AccessConfig::default()
+    });
+    let one = headers(&[("x-api-key", "one")]);
+    assert_eq!(access.check(&one, ip("192.0.2.1")), Ok(()));
+    // The same key from another address shares the bucket
+    assert_eq!(access.check(&one, ip("192.0.2.2")), Ok(()));
+    let Err(Denied::RateLimited { retry_after }) = access.check(&one, ip("192.0.2.1")) else {
+        panic!("expected the third request to be limited");
+    };
+    // One token comes back every 30 seconds
+    assert!(retry_after > Duration::from_secs(29) && retry_after <= Duration::from_secs(30), "{:?}", retry_after);
+
+    assert_eq!(access.check(&headers(&[("x-api-key", "two")]), ip("192.0.2.1")), Ok(()));
+}
+
+#[test]
+fn test_rate_limit_per_ip() {
+    // Without keys, the client limit applies to each address
+    let access = Access::new(AccessConfig { rate_limit: Some(RateLimit::per_minute(1)), ..AccessConfig::default() });
+    assert_eq!(access.check(&HeaderMap::new(), ip("192.0.2.1")), Ok(()));
+    assert!(matches!(access.check(&HeaderMap::new(), ip("192.0.2.1")), Err(Denied::RateLimited { .. })));
+    assert_eq!(access.check(&HeaderMap::new(), ip("2001:db8::1")), Ok(()));
+
+    // The IP limit is checked before the key, so wrong keys use it up too
+    let access = Access::new(AccessConfig {
+        api_keys: vec!["one".to_string()],
+        ip_rate_limit: Some(RateLimit::per_minute(2)),
+        ..AccessConfig::default()
+    });
+    let wrong = headers(&[("x-api-key", "wrong")]);
+    assert_eq!(access.check(&wrong, ip("192.0.2.1")), Err(Denied::Unauthorized));
+    assert_eq!(access.check(&wrong, ip("192.0.2.1")), Err(Denied::Unauthorized));
+    assert!(matches!(
+        access.check(&headers(&[("x-api-key", "one")]), ip("192.0.2.1")),
+        Err(Denied::RateLimited { .. })
+    ));
+    assert_eq!(access.check(&headers(&[("x-api-key", "one")]), ip("192.0.2.2")), Ok(()));
+}
+
+#[test]
+fn test_parse_node() {
+    assert_eq!(parse_node(" 192.0.2.1"), ip("192.0.2.1"));
+    assert_eq!(parse_node("192.0.2.1:4711"), ip("192.0.2.1"));
+    assert_eq!(parse_node("2001:db8::1"), ip("2001:db8::1"));
+    assert_eq!(parse_node("\"[2001:db8::1]:4711\""), ip("2001:db8::1"));
+    assert_eq!(parse_node("_hidden"), None);
+    assert_eq!(parse_node("unknown"), None);
+}
+
+#[test]
+fn test_client_ip_behind_trusted_proxy() {
+    let access = Access::new(AccessConfig {
+        trusted_proxies: vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()],
+        ..AccessConfig::default()
+    });
+    let client = |pairs: &[(&'static str, &str)], peer: &str| access.client_ip(&headers(pairs), ip(peer));
+
+    // Only a trusted peer is believed
+    assert_eq!(client(&[("x-forwarded-for", "192.0.2.1")], "10.0.0.1"), ip("192.0.2.1"));
+    assert_eq!(client(&[("x-forwarded-for", "192.0.2.1")], "198.51.100.1"), ip("198.51.100.1"));
+    assert_eq!(client(&[], "10.0.0.1"), ip("10.0.0.1"));
+
+    // The nearest untrusted hop wins, whatever the client put before it
+    assert_eq!(client(&[("x-forwarded-for", "203.0.113.9, 192.0.2.1, 10.0.0.2")], "10.0.0.1"), ip("192.0.2.1"));
+    assert_eq!(client(&[("x-forwarded-for", "10.0.0.2")], "10.0.0.1"), ip("10.0.0.2"));
+    assert_eq!(client(&[("x-forwarded-for", "garbage, 10.0.0.2")], "10.0.0.1"), ip("10.0.0.2"));
+
+    assert_eq!(client(&[("forwarded", "for=192.0.2.1;proto=https")], "10.0.0.1"), ip("192.0.2.1"));
+    assert_eq!(
+        client(&[("forwarded", "for=203.0.113.9, for=\"[2001:db8::1]:4711\";by=10.0.0.1")], "10.0.0.1"),
+        ip("2001:db8::1")
+    );
+    // X-Forwarded-For is preferred when both are present
+    assert_eq!(
+        client(&[("x-forwarded-for", "192.0.2.1"), ("forwarded", "for=192.0.2.2")], "10.0.0.1"),
+        ip("192.0.2.1")
+    );
+}
+
+#[test]
+fn test_rate_limit_per_forwarded_ip() {
+    let access = Access::new(AccessConfig {
+        ip_rate_limit: Some(RateLimit::per_minute(1)),
+        trusted_proxies: vec!["10.0.0.1".parse().unwrap()],
+        ..AccessConfig::default()
+    });
+    let from = |client: &str| headers(&[("x-forwarded-for", client)]);
+    assert_eq!(access.check(&from("192.0.2.1"), ip("10.0.0.1")), Ok(()));
+    assert_eq!(access.check(&from("192.0.2.2"), ip("10.0.0.1")), Ok(()));
+    assert!(matches!(access.check(&from("192.0.2.1"), ip("10.0.0.1")), Err(Denied::RateLimited { .. })));
+    // Clients cannot dodge the limit by naming another address themselves
+    assert!(matches!(
+        access.check(&from("192.0.2.1, 192.0.2.3"), ip("192.0.2.1")),
+        Err(Denied::RateLimited { .. })
+    ));
+}
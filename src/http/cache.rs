@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use url::Url;
+
+use super::client::{FetchedHtml, Fetcher};
+
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+/// Results kept in memory.
+pub const DEFAULT_CACHE_SIZE: usize = 256;
+/// How long files outlive their TTL on disk, for revalidation, before they
+/// are pruned.
+const DISK_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+/// Smallest gap between two scans of the cache directory for old files.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The `ETag` and `Last-Modified` of a response, sent back as
+/// `If-None-Match` and `If-Modified-Since` to revalidate it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// A converted page and what is needed to tell whether it is still current.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedPage {
+    /// The converted output.
+    pub content: String,
+    /// The page URL after redirects.
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub validators: Validators,
+    /// When the page was last fetched or revalidated.
+    pub stored_at: SystemTime,
+}
+
+impl CachedPage {
+    pub fn new(content: String, page: &FetchedHtml) -> Self {
+        Self {
+            content,
+            url: page.url.to_string(),
+            status: page.status,
+            content_type: page.content_type.clone(),
+            validators: page.validators.clone(),
+            stored_at: SystemTime::now(),
+        }
+    }
+}
+
+/// Whether a result came from the cache, reported as `X-Cache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// Fresh, or stale but confirmed unchanged by the server.
+    Hit,
+    Miss,
+}
+
+impl CacheStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheConfig {
+    /// How long a result is used without asking the server.
+    pub ttl: Duration,
+    /// Results kept in memory, least recently used evicted first.
+    pub capacity: usize,
+    /// Also keep results in this directory, one JSON file each.
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { ttl: DEFAULT_CACHE_TTL, capacity: DEFAULT_CACHE_SIZE, dir: None }
+    }
+}
+
+/// Converted pages keyed by URL and conversion settings, in memory and
+/// optionally on disk. Stale pages with validators are revalidated with a
+/// conditional request rather than fetched again.
+#[derive(Debug)]
+pub struct Cache {
+    config: CacheConfig,
+    memory: Mutex<Lru>,
+    /// When old files were last removed from the cache directory.
+    pruned_at: Mutex<Option<Instant>>,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    /// Each page with the tick of its last use.
+    entries: HashMap<String, (CachedPage, u64)>,
+    tick: u64,
+}
+
+impl Lru {
+    fn get(&mut self, key: &str) -> Option<CachedPage> {
+        self.tick += 1;
+        let (page, used) = self.entries.get_mut(key)?;
+        *used = self.tick;
+        Some(page.clone())
+    }
+
+    fn insert(&mut self, key: String, page: CachedPage, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if self.entries.len() >= capacity && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (page, self.tick));
+    }
+}
+
+/// The on-disk form of an entry; the key guards against hash collisions.
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    key: String,
+    page: CachedPage,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self { config, memory: Mutex::default(), pruned_at: Mutex::default() }
+    }
+
+    /// The page stored under `key`, fresh or not.
+    pub async fn get(&self, key: &str) -> Option<CachedPage> {
+        if let Some(page) = self.memory.lock().unwrap().get(key) {
+            return Some(page);
+        }
+        let path = self.path(key)?;
+        let bytes = tokio::fs::read(&path).await.ok()?;
+        match serde_json::from_slice::<DiskEntry>(&bytes) {
+            Ok(entry) if entry.key == key => {
+                self.memory.lock().unwrap().insert(key.to_string(), entry.page.clone(), self.config.capacity);
+                Some(entry.page)
+            },
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Ignoring unreadable cache file {}: {}", path.display(), e);
+                None
+            },
+        }
+    }
+
+    /// Stores `page`. Failing to write it to disk is logged, not returned:
+    /// the result is still good to use.
+    pub async fn put(&self, key: &str, page: CachedPage) {
+        self.memory.lock().unwrap().insert(key.to_string(), page.clone(), self.config.capacity);
+        let Some(path) = self.path(key) else {
+            return;
+        };
+        let entry = DiskEntry { key: key.to_string(), page };
+        if let Err(e) = write_atomically(&path, &serde_json::to_vec(&entry).unwrap_or_default()).await {
+            warn!("Failed to write cache file {}: {:#}", path.display(), e);
+        }
+        self.prune().await;
+    }
+
+    /// Removes the files not written for [`DISK_RETENTION`] past the TTL,
+    /// at most once per [`PRUNE_INTERVAL`], so the directory does not grow
+    /// forever.
+    async fn prune(&self) {
+        let Some(dir) = &self.config.dir else {
+            return;
+        };
+        {
+            let mut pruned_at = self.pruned_at.lock().unwrap();
+            if pruned_at.is_some_and(|at| at.elapsed() < PRUNE_INTERVAL) {
+                return;
+            }
+            *pruned_at = Some(Instant::now());
+        }
+        let max_age = self.config.ttl.saturating_add(DISK_RETENTION);
+        let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+            return;
+        };
+        let mut removed = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if !matches!(path.extension().and_then(|extension| extension.to_str()), Some("json" | "tmp")) {
+                continue;
+            }
+            let expired = entry.metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > max_age);
+            if expired && tokio::fs::remove_file(&path).await.is_ok() {
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            debug!("Pruned {} old cache files from {}", removed, dir.display());
+        }
+    }
+
+    pub fn is_fresh(&self, page: &CachedPage) -> bool {
+        page.stored_at.elapsed().is_ok_and(|age| age < self.config.ttl)
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        let dir = self.config.dir.as_ref()?;
+        Some(dir.join(format!("{:016x}.json", fnv1a(key.as_bytes()))))
+    }
+}
+
+/// Fetches `url` and converts it with `convert`, going through `cache` when
+/// given. `variant` names everything besides the URL that changes the
+/// output, such as the format and converter options; the fetcher adds its
+/// own [`cache_scope`](Fetcher::cache_scope), and bypasses the cache when
+/// it sends credentials. Only successful responses not marked `no-store`
+/// are stored.
+///
+/// Fetch errors are returned as [`FetchError`](super::client::FetchError)s
+/// inside the `anyhow::Error`.
+pub async fn fetch_converted<F>(
+    cache: Option<&Cache>,
+    fetcher: &Fetcher,
+    url: &Url,
+    variant: &str,
+    convert: F,
+) -> Result<(CachedPage, Option<CacheStatus>)>
+where
+    F: FnOnce(&FetchedHtml) -> Result<String>,
+{
+    let Some((cache, scope)) = cache.zip(fetcher.cache_scope()) else {
+        let page = fetcher.fetch_page(url).await?;
+        return Ok((CachedPage::new(convert(&page)?, &page), None));
+    };
+
+    let key = match scope.is_empty() {
+        true => format!("{} {}", variant, url),
+        false => format!("{} {} {}", scope, variant, url),
+    };
+    let page = match cache.get(&key).await {
+        Some(cached) if cache.is_fresh(&cached) => {
+            debug!("Cache hit for {}", url);
+            return Ok((cached, Some(CacheStatus::Hit)));
+        },
+        Some(cached) if !cached.validators.is_empty() => {
+            match fetcher.revalidate_page(url, &cached.validators).await? {
+                None => {
+                    debug!("Revalidated cached {}", url);
+                    let cached = CachedPage { stored_at: SystemTime::now(), ..cached };
+                    cache.put(&key, cached.clone()).await;
+                    return Ok((cached, Some(CacheStatus::Hit)));
+                },
+                Some(page) => page,
+            }
+        },
+        _ => fetcher.fetch_page(url).await?,
+    };
+
+    let converted = CachedPage::new(convert(&page)?, &page);
+    if (200..300).contains(&page.status) && !page.no_store {
+        cache.put(&key, converted.clone()).await;
+    }
+    Ok((converted, Some(CacheStatus::Miss)))
+}
+
+/// Writes through a temporary file, so concurrent readers never see a
+/// partial entry.
+async fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let temporary = path.with_extension(format!("{}.tmp", fastrand::u64(..)));
+    tokio::fs::write(&temporary, contents).await?;
+    if let Err(e) = tokio::fs::rename(&temporary, path).await {
+        let _ = tokio::fs::remove_file(&temporary).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// 64-bit FNV-1a, stable across builds unlike the standard library hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Cache settings shared by the CLI flags, `URL2MD_*` environment variables
+/// and the `[cache]` table of the configuration file.
+#[derive(clap::Args, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheArgs {
+    /// Always fetch and convert pages instead of using cached results
+    #[arg(long, env = "URL2MD_NO_CACHE", global = true)]
+    pub no_cache: bool,
+
+    /// Also keep cached results in this directory, across runs [default: memory only]
+    #[arg(long, env = "URL2MD_CACHE_DIR", value_name = "DIR", global = true)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Seconds a cached result is used before revalidating it [default: 300]
+    #[arg(long, env = "URL2MD_CACHE_TTL", value_name = "SECS", global = true)]
+    pub cache_ttl: Option<f64>,
+
+    /// Results kept in memory [default: 256]
+    #[arg(long, env = "URL2MD_CACHE_SIZE", value_name = "ENTRIES", global = true)]
+    pub cache_size: Option<usize>,
+}
+
+impl CacheArgs {
+    /// Fills settings missing from `self` with those from `fallback`.
+    pub fn or(self, fallback: CacheArgs) -> CacheArgs {
+        CacheArgs {
+            no_cache: self.no_cache || fallback.no_cache,
+            cache_dir: self.cache_dir.or(fallback.cache_dir),
+            cache_ttl: self.cache_ttl.or(fallback.cache_ttl),
+            cache_size: self.cache_size.or(fallback.cache_size),
+        }
+    }
+
+    /// The cache configuration, or `None` with `--no-cache`.
+    pub fn to_config(&self) -> Result<Option<CacheConfig>> {
+        if self.no_cache {
+            return Ok(None);
+        }
+        let ttl = match self.cache_ttl {
+            None => DEFAULT_CACHE_TTL,
+            Some(secs) if secs >= 0.0 => Duration::try_from_secs_f64(secs)
+                .map_err(|_| anyhow!("invalid cache TTL '{}': too large", secs))?,
+            Some(secs) => bail!("invalid cache TTL '{}': must not be negative", secs),
+        };
+        Ok(Some(CacheConfig {
+            ttl,
+            capacity: self.cache_size.unwrap_or(DEFAULT_CACHE_SIZE),
+            dir: self.cache_dir.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+#[path = "cache_test.rs"]
+mod tests;
@@ -0,0 +1,249 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use anyhow::anyhow;
+use url::Url;
+
+use super::{fetch_converted, Cache, CacheArgs, CacheConfig, CacheStatus, CachedPage, Validators};
+use crate::http::client::Fetcher;
+use crate::http::config::FetchConfig;
+
+fn page(content: &str) -> CachedPage {
+    CachedPage {
+        content: content.to_string(),
+        url: "https://example.com/".to_string(),
+        status: 200,
+        content_type: Some("text/html".to_string()),
+        validators: Validators { etag: Some("\"v1\"".to_string()), last_modified: None },
+        stored_at: SystemTime::now(),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_cache_evicts_least_recently_used() {
+    let cache = Cache::new(CacheConfig { capacity: 2, ..CacheConfig::default() });
+    cache.put("a", page("A")).await;
+    cache.put("b", page("B")).await;
+    assert_eq!(cache.get("a").await.unwrap().content, "A");
+    cache.put("c", page("C")).await;
+
+    assert!(cache.get("b").await.is_none());
+    assert_eq!(cache.get("a").await.unwrap().content, "A");
+    assert_eq!(cache.get("c").await.unwrap().content, "C");
+
+    let disabled = Cache::new(CacheConfig { capacity: 0, ..CacheConfig::default() });
+    disabled.put("a", page("A")).await;
+    assert!(disabled.get("a").await.is_none());
+}
+
+#[tokio::test]
+async fn test_disk_cache_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = CacheConfig { dir: Some(dir.path().join("cache")), ..CacheConfig::default() };
+    let stored = page("A");
+    Cache::new(config.clone()).put("markdown https://example.com/", stored.clone()).await;
+
+    let files: Vec<PathBuf> = std::fs::read_dir(dir.path().join("cache")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].extension().unwrap(), "json");
+
+    let cache = Cache::new(config);
+    assert_eq!(cache.get("markdown https://example.com/").await, Some(stored));
+    assert!(cache.get("markdown https://example.com/other").await.is_none());
+
+    // Unreadable files are ignored
+    std::fs::write(&files[0], "not json").unwrap();
+    assert!(Cache::new(CacheConfig { dir: Some(dir.path().join("cache")), ..CacheConfig::default() })
+        .get("markdown https://example.com/")
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_fetch_converted_uses_fresh_entries() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("<p>page</p>")
+        .expect(1)
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+    let fetcher = Fetcher::default();
+    let cache = Cache::new(CacheConfig::default());
+
+    let (first, status) = fetch_converted(Some(&cache), &fetcher, &url, "upper", |page| Ok(page.html.to_uppercase()))
+        .await
+        .unwrap();
+    assert_eq!(first.content, "<P>PAGE</P>");
+    assert_eq!(status, Some(CacheStatus::Miss));
+
+    let (second, status) = fetch_converted(Some(&cache), &fetcher, &url, "upper", |_| unreachable!())
+        .await
+        .unwrap();
+    assert_eq!(second, first);
+    assert_eq!(status, Some(CacheStatus::Hit));
+    mock.assert_async().await;
+
+    // Other conversion settings are cached separately
+    let _again = server.mock("GET", "/").with_body("<p>page</p>").create_async().await;
+    let (other, status) = fetch_converted(Some(&cache), &fetcher, &url, "lower", |page| Ok(page.html.to_lowercase()))
+        .await
+        .unwrap();
+    assert_eq!(other.content, "<p>page</p>");
+    assert_eq!(status, Some(CacheStatus::Miss));
+}
+
+#[tokio::test]
+async fn test_fetch_converted_revalidates_stale_entries() {
+    let mut server = mockito::Server::new_async().await;
+    let _first = server.mock("GET", "/")
+        .with_header("etag", "\"v1\"")
+        .with_header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+        .with_body("<p>v1</p>")
+        .expect(1)
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+    let fetcher = Fetcher::default();
+    let cache = Cache::new(CacheConfig { ttl: Duration::ZERO, ..CacheConfig::default() });
+
+    let (page, _) = fetch_converted(Some(&cache), &fetcher, &url, "", |page| Ok(page.html.clone())).await.unwrap();
+    assert_eq!(page.validators.etag.as_deref(), Some("\"v1\""));
+
+    // Unchanged: the cached result is used
+    let not_modified = server.mock("GET", "/")
+        .match_header("if-none-match", "\"v1\"")
+        .match_header("if-modified-since", "Wed, 21 Oct 2015 07:28:00 GMT")
+        .with_status(304)
+        .expect(1)
+        .create_async()
+        .await;
+    let (revalidated, status) = fetch_converted(Some(&cache), &fetcher, &url, "", |_| Err(anyhow!("not converted")))
+        .await
+        .unwrap();
+    assert_eq!(revalidated.content, "<p>v1</p>");
+    assert_eq!(status, Some(CacheStatus::Hit));
+    not_modified.assert_async().await;
+    not_modified.remove_async().await;
+
+    // Changed: the new page is converted and stored
+    let _changed = server.mock("GET", "/")
+        .with_header("etag", "\"v2\"")
+        .with_body("<p>v2</p>")
+        .create_async()
+        .await;
+    let (changed, status) = fetch_converted(Some(&cache), &fetcher, &url, "", |page| Ok(page.html.clone())).await.unwrap();
+    assert_eq!(changed.content, "<p>v2</p>");
+    assert_eq!(status, Some(CacheStatus::Miss));
+    assert_eq!(cache.get(&format!(" {}", url)).await.unwrap().validators.etag.as_deref(), Some("\"v2\""));
+}
+
+#[tokio::test]
+async fn test_disk_cache_prunes_old_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = dir.path().join("0123456789abcdef.json");
+    let recent = dir.path().join("fedcba9876543210.json");
+    let other = dir.path().join("notes.txt");
+    let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+    for path in [&old, &recent, &other] {
+        std::fs::write(path, "{}").unwrap();
+    }
+    for path in [&old, &other] {
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(two_days_ago).unwrap();
+    }
+
+    let cache = Cache::new(CacheConfig { dir: Some(dir.path().to_path_buf()), ..CacheConfig::default() });
+    cache.put("a", page("A")).await;
+    assert!(!old.exists());
+    assert!(recent.exists());
+    assert!(other.exists());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+}
+
+#[tokio::test]
+async fn test_fetch_converted_scopes_entries_to_fetch_settings() {
+    let mut server = mockito::Server::new_async().await;
+    let page = server.mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<p>page</p>")
+        .expect(3)
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap().join("/page").unwrap();
+    let cache = Cache::new(CacheConfig::default());
+    let fetch = |config: FetchConfig| {
+        let cache = &cache;
+        let url = &url;
+        async move {
+            let fetcher = Fetcher::new(config).unwrap();
+            fetch_converted(Some(cache), &fetcher, url, "", |page| Ok(page.html.clone())).await.unwrap().1
+        }
+    };
+
+    assert_eq!(fetch(FetchConfig::default()).await, Some(CacheStatus::Miss));
+    assert_eq!(fetch(FetchConfig::default()).await, Some(CacheStatus::Hit));
+    // Another user agent may be served another page
+    let agent = || FetchConfig { user_agent: "other".to_string(), ..FetchConfig::default() };
+    assert_eq!(fetch(agent()).await, Some(CacheStatus::Miss));
+    assert_eq!(fetch(agent()).await, Some(CacheStatus::Hit));
+    // Pages fetched with credentials are neither stored nor looked up
+    let cookie = FetchConfig { cookie: Some("session=secret".to_string()), ..FetchConfig::default() };
+    assert_eq!(fetch(cookie).await, None);
+    page.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_fetch_converted_skips_uncacheable_responses() {
+    let mut server = mockito::Server::new_async().await;
+    let _private = server.mock("GET", "/private")
+        .with_header("cache-control", "private, no-store")
+        .with_body("<p>secret</p>")
+        .create_async()
+        .await;
+    let base = Url::parse(&server.url()).unwrap();
+    let fetcher = Fetcher::default();
+    let cache = Cache::new(CacheConfig::default());
+
+    let url = base.join("/private").unwrap();
+    fetch_converted(Some(&cache), &fetcher, &url, "", |page| Ok(page.html.clone())).await.unwrap();
+    assert!(cache.get(&format!(" {}", url)).await.is_none());
+
+    // Failures are not cached and keep their type
+    let missing = base.join("/missing").unwrap();
+    let error = fetch_converted(Some(&cache), &fetcher, &missing, "", |page| Ok(page.html.clone()))
+        .await
+        .unwrap_err();
+    assert!(error.downcast_ref::<crate::http::FetchError>().is_some());
+
+    // Without a cache, nothing is reported
+    let (_, status) = fetch_converted(None, &fetcher, &url, "", |page| Ok(page.html.clone())).await.unwrap();
+    assert_eq!(status, None);
+}
+
+#[test]
+fn test_cache_args_to_config() {
+    // Results stay in memory unless a directory is given
+    let config = CacheArgs::default().to_config().unwrap().unwrap();
+    assert_eq!(config, CacheConfig::default());
+
+    let args = CacheArgs {
+        cache_dir: Some(PathBuf::from("cache")),
+        cache_ttl: Some(0.0),
+        cache_size: Some(10),
+        ..CacheArgs::default()
+    };
+    let config = args.to_config().unwrap().unwrap();
+    assert_eq!(config, CacheConfig { ttl: Duration::ZERO, capacity: 10, dir: Some(PathBuf::from("cache")) });
+
+    assert!(CacheArgs { no_cache: true, ..CacheArgs::default() }.to_config().unwrap().is_none());
+    assert!(CacheArgs { cache_ttl: Some(-1.0), ..CacheArgs::default() }.to_config().is_err());
+
+    let merged = CacheArgs { cache_ttl: Some(5.0), ..CacheArgs::default() }
+        .or(CacheArgs { cache_ttl: Some(60.0), cache_size: Some(3), no_cache: true, ..CacheArgs::default() });
+    assert_eq!(merged.cache_ttl, Some(5.0));
+    assert_eq!(merged.cache_size, Some(3));
+    assert!(merged.no_cache);
+}
@@ -0,0 +1,103 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+use url::Url;
+
+/// How many leading bytes are scanned for a `<meta>` charset declaration.
+/// The HTML spec prescans 1024 bytes; real pages often put the tag after
+/// long comments or scripts, so a little more is examined.
+const PRESCAN_LIMIT: usize = 4096;
+
+/// Decodes an HTML document to UTF-8, determining the encoding the way a
+/// browser does: an explicit `override_encoding`, then a byte order mark,
+/// the Content-Type `charset`, a `<meta>` declaration and finally a
+/// statistical guess from the bytes themselves.
+pub fn decode_html(
+    bytes: &[u8],
+    content_type: Option<&str>,
+    override_encoding: Option<&'static Encoding>,
+    url: Option<&Url>,
+) -> String {
+    if let Some(encoding) = override_encoding {
+        let (html, _) = encoding.decode_with_bom_removal(bytes);
+        return html.into_owned();
+    }
+    let encoding = Encoding::for_bom(bytes)
+        .map(|(encoding, _)| encoding)
+        .or_else(|| content_type.and_then(content_type_charset))
+        .or_else(|| prescan_meta_charset(bytes))
+        .unwrap_or_else(|| detect_encoding(bytes, url));
+    // `decode` strips a BOM, and lets it win over the other sources.
+    let (html, _, _) = encoding.decode(bytes);
+    html.into_owned()
+}
+
+/// Parses an encoding label such as `shift_jis` or `latin1`.
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("unknown encoding '{}'", label))
+}
+
+/// The encoding named by the `charset` parameter of a Content-Type value.
+pub fn content_type_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';')
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, charset)| Encoding::for_label(charset.trim().trim_matches(['"', '\'']).as_bytes()))
+}
+
+/// Looks for `<meta charset>` or `<meta http-equiv="Content-Type"
+/// content="...; charset=...">` near the start of the document. Both forms
+/// carry a `charset=` token inside the tag, so one scan covers them.
+pub fn prescan_meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = bytes[..bytes.len().min(PRESCAN_LIMIT)].to_ascii_lowercase();
+    let mut rest = head.as_slice();
+    while let Some(start) = find(rest, b"<meta") {
+        let tag = &rest[start..];
+        let end = tag.iter().position(|&b| b == b'>').unwrap_or(tag.len());
+        if let Some(encoding) = meta_tag_charset(&tag[..end]) {
+            return Some(encoding);
+        }
+        rest = &tag[end..];
+    }
+    None
+}
+
+fn meta_tag_charset(tag: &[u8]) -> Option<&'static Encoding> {
+    let position = find(tag, b"charset")?;
+    let value = tag[position + b"charset".len()..].trim_ascii_start().strip_prefix(b"=")?;
+    let value = value.trim_ascii_start();
+    let value = value.strip_prefix(b"\"")
+        .or_else(|| value.strip_prefix(b"'"))
+        .unwrap_or(value);
+    let end = value.iter()
+        .position(|&b| b.is_ascii_whitespace() || matches!(b, b'"' | b'\'' | b';' | b'/'))
+        .unwrap_or(value.len());
+    let encoding = Encoding::for_label(&value[..end])?;
+    // A document that was readable enough to find this tag as ASCII cannot
+    // actually be UTF-16, and x-user-defined is treated as windows-1252.
+    Some(match encoding {
+        e if e == UTF_16BE || e == UTF_16LE => UTF_8,
+        e if e == X_USER_DEFINED => WINDOWS_1252,
+        e => e,
+    })
+}
+
+/// Guesses the encoding of undeclared content, using the top-level domain
+/// as a hint the way browsers do for legacy pages.
+fn detect_encoding(bytes: &[u8], url: Option<&Url>) -> &'static Encoding {
+    let tld = url
+        .and_then(Url::host_str)
+        .and_then(|host| host.rsplit('.').next())
+        .filter(|tld| tld.bytes().all(|b| b.is_ascii_alphabetic()))
+        .map(str::to_ascii_lowercase);
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(tld.as_deref().map(str::as_bytes), true)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+#[path = "charset_test.rs"]
+mod tests;
@@ -0,0 +1,70 @@
+use encoding_rs::{GBK, SHIFT_JIS, UTF_8, WINDOWS_1251, WINDOWS_1252};
+use url::Url;
+
+use crate::http::charset::{content_type_charset, decode_html, parse_encoding, prescan_meta_charset};
+
+#[test]
+fn test_content_type_charset() {
+    assert_eq!(content_type_charset("text/html; charset=Shift_JIS"), Some(SHIFT_JIS));
+    assert_eq!(content_type_charset("text/html;charset=\"gbk\""), Some(GBK));
+    assert_eq!(content_type_charset("text/html"), None);
+    assert_eq!(content_type_charset("text/html; charset=bogus"), None);
+}
+
+#[test]
+fn test_prescan_meta_charset() {
+    assert_eq!(prescan_meta_charset(b"<html><head><meta charset=\"windows-1251\">"), Some(WINDOWS_1251));
+    assert_eq!(prescan_meta_charset(b"<META CHARSET=gbk>"), Some(GBK));
+    assert_eq!(
+        prescan_meta_charset(b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift_JIS\">"),
+        Some(SHIFT_JIS)
+    );
+    assert_eq!(
+        prescan_meta_charset(b"<meta name=\"viewport\" content=\"width=device-width\"><meta charset='utf-8'>"),
+        Some(UTF_8)
+    );
+    // UTF-16 in a meta tag is nonsensical and means UTF-8
+    assert_eq!(prescan_meta_charset(b"<meta charset=\"utf-16\">"), Some(UTF_8));
+    assert_eq!(prescan_meta_charset(b"<meta charset=\"x-user-defined\">"), Some(WINDOWS_1252));
+    assert_eq!(prescan_meta_charset(b"<p>charset=gbk</p>"), None);
+}
+
+#[test]
+fn test_decode_html_precedence() {
+    let (body, _, _) = WINDOWS_1251.encode("<meta charset=\"windows-1251\"><p>Привет, мир</p>");
+
+    // The meta declaration is used when the header says nothing
+    assert!(decode_html(&body, Some("text/html"), None, None).contains("Привет, мир"));
+    // The header charset wins over the meta tag
+    assert!(!decode_html(&body, Some("text/html; charset=iso-8859-1"), None, None).contains("Привет"));
+    // An explicit override wins over both
+    let decoded = decode_html(&body, Some("text/html; charset=iso-8859-1"), Some(WINDOWS_1251), None);
+    assert!(decoded.contains("Привет, мир"));
+}
+
+#[test]
+fn test_decode_html_bom() {
+    let mut body = vec![0xEF, 0xBB, 0xBF];
+    body.extend_from_slice("<p>naïve</p>".as_bytes());
+    // The BOM wins over a wrong header charset and is stripped
+    assert_eq!(decode_html(&body, Some("text/html; charset=windows-1252"), None, None), "<p>naïve</p>");
+
+    let mut body = vec![0xFF, 0xFE];
+    body.extend("<p>hi</p>".encode_utf16().flat_map(u16::to_le_bytes));
+    assert_eq!(decode_html(&body, None, None, None), "<p>hi</p>");
+}
+
+#[test]
+fn test_decode_html_detects_undeclared_encoding() {
+    let text = "<html><body><p>日本語のテキストです。文字コードの宣言がないページでも、正しく読み込めることを確認します。</p></body></html>";
+    let (body, _, _) = SHIFT_JIS.encode(text);
+    let url = Url::parse("https://example.jp/page.html").unwrap();
+    assert_eq!(decode_html(&body, Some("text/html"), None, Some(&url)), text);
+}
+
+#[test]
+fn test_parse_encoding() {
+    assert_eq!(parse_encoding("latin1"), Ok(WINDOWS_1252));
+    assert_eq!(parse_encoding(" Shift_JIS "), Ok(SHIFT_JIS));
+    assert!(parse_encoding("klingon").is_err());
+}
@@ -1,24 +1,586 @@
-use anyhow::Result;
-use reqwest::Client;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use reqwest::{header, redirect, Certificate, Client, Proxy, Response, StatusCode};
+use tokio::sync::OnceCell;
+use tokio::time::Instant;
+use tracing::{info, warn};
 use url::Url;
-use once_cell::sync::Lazy;
-
-static CLIENT: Lazy<Client> = Lazy::new(|| {
-    Client::builder()
-        .gzip(true)
-        .brotli(true)
-        .deflate(true)
-        .build()
-        .expect("Failed to create HTTP client")
-});
-
-pub async fn fetch_html(url: &Url) -> Result<String> {
-    let response = CLIENT.get(url.as_str())
-        .header("Accept-Encoding", "gzip, deflate, br")
-        .send()
-        .await?;
-    let html = response.text().await?;
-    Ok(html)
+
+use super::cache::Validators;
+use super::charset;
+use super::config::FetchConfig;
+use super::metrics::Metrics;
+use super::policy::PolicyError;
+use super::robots::Robots;
+
+/// Largest `robots.txt` read; RFC 9309 asks crawlers to parse at least 500 KiB.
+const MAX_ROBOTS_SIZE: usize = 512 * 1024;
+
+#[derive(Debug)]
+pub enum FetchError {
+    /// The host name could not be resolved.
+    Dns(reqwest::Error),
+    /// The TCP connection could not be established.
+    Connect(reqwest::Error),
+    /// The request or response took too long. Read timeouts are enforced
+    /// here rather than by reqwest, so carry no underlying error.
+    Timeout(Option<reqwest::Error>),
+    /// The TLS handshake or certificate validation failed.
+    Tls(reqwest::Error),
+    /// The server answered with a non-2xx status. `retry_after` holds the
+    /// delay the server asked for, if any.
+    Status { status: u16, url: Url, retry_after: Option<Duration> },
+    /// The response is not an HTML (or other text) document.
+    NotHtml { content_type: String },
+    /// The response body exceeds [`FetchConfig::max_body_size`].
+    TooLarge { limit: usize },
+    /// The site's `robots.txt` does not allow fetching the URL.
+    Disallowed { url: Url },
+    /// The URL, a redirect or the address its host resolves to is refused
+    /// by the [`UrlPolicy`](super::policy::UrlPolicy).
+    Blocked { url: Url, reason: Box<PolicyError> },
+    /// Any other request failure.
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Dns(e) => write!(f, "DNS lookup failed: {}", root_cause(e)),
+            FetchError::Connect(e) => write!(f, "connection failed: {}", root_cause(e)),
+            FetchError::Timeout(_) => write!(f, "request timed out"),
+            FetchError::Tls(e) => write!(f, "TLS error: {}", root_cause(e)),
+            FetchError::Status { status, url, .. } => write!(f, "{} returned HTTP status {}", url, status),
+            FetchError::NotHtml { content_type } => write!(f, "unsupported content type '{}'", content_type),
+            FetchError::TooLarge { limit } => write!(f, "response body exceeds {} bytes", limit),
+            FetchError::Disallowed { url } => write!(f, "{} is disallowed by robots.txt", url),
+            FetchError::Blocked { url, reason } => write!(f, "{} is blocked: {}", url, reason),
+            FetchError::Request(e) => write!(f, "request failed: {}", e),
+        }
+    }
+}
+
+impl StdError for FetchError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            FetchError::Timeout(Some(e)) => Some(e),
+            FetchError::Dns(e)
+            | FetchError::Connect(e)
+            | FetchError::Tls(e)
+            | FetchError::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl FetchError {
+    /// Whether the failure is likely transient: throttling or overload
+    /// responses, timeouts and connections that failed or dropped.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FetchError::Status { status, .. } => is_retryable_status(*status),
+            FetchError::Timeout(_) | FetchError::Connect(_) => true,
+            FetchError::Request(e) => e.is_request() || e.is_body(),
+            _ => false,
+        }
+    }
+
+    /// A short name for the kind of failure, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FetchError::Dns(_) => "dns",
+            FetchError::Connect(_) => "connect",
+            FetchError::Timeout(_) => "timeout",
+            FetchError::Tls(_) => "tls",
+            FetchError::Status { .. } => "status",
+            FetchError::NotHtml { .. } => "not_html",
+            FetchError::TooLarge { .. } => "too_large",
+            FetchError::Disallowed { .. } => "disallowed",
+            FetchError::Blocked { .. } => "blocked",
+            FetchError::Request(_) => "request",
+        }
+    }
+}
+
+fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 429 | 502 | 503 | 504)
+}
+
+impl From<reqwest::Error> for FetchError {
+    /// Classifies a transport error. reqwest does not expose DNS or TLS
+    /// failures as distinct kinds, so those are recognised from the messages
+    /// of the underlying hyper and TLS errors.
+    fn from(error: reqwest::Error) -> Self {
+        if let (Some(reason), Some(url)) = (policy_error(&error), error.url()) {
+            return FetchError::Blocked { url: url.clone(), reason: Box::new(reason) };
+        }
+        if error.is_timeout() {
+            return FetchError::Timeout(Some(error));
+        }
+        if error.is_connect() {
+            let chain = error_chain(&error).to_ascii_lowercase();
+            if chain.contains("dns error") || chain.contains("failed to lookup address") {
+                return FetchError::Dns(error);
+            }
+            if ["certificate", "tls", "ssl", "handshake"].iter().any(|hint| chain.contains(hint)) {
+                return FetchError::Tls(error);
+            }
+            return FetchError::Connect(error);
+        }
+        FetchError::Request(error)
+    }
+}
+
+/// The policy violation behind a refused redirect or resolved address,
+/// which reqwest reports as a redirect or connection error.
+fn policy_error(error: &(dyn StdError + 'static)) -> Option<PolicyError> {
+    let mut source = Some(error);
+    while let Some(cause) = source {
+        if let Some(reason) = cause.downcast_ref::<PolicyError>() {
+            return Some(reason.clone());
+        }
+        source = cause.source();
+    }
+    None
+}
+
+fn error_chain(error: &(dyn StdError + 'static)) -> String {
+    let mut messages = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        messages.push(cause.to_string());
+        source = cause.source();
+    }
+    messages.join(": ")
+}
+
+fn root_cause(error: &(dyn StdError + 'static)) -> String {
+    let mut cause = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+/// Fetches pages with an HTTP client built from a [`FetchConfig`]. Build
+/// one and share it: the client keeps a connection pool.
+#[derive(Clone, Debug)]
+pub struct Fetcher {
+    client: Client,
+    config: FetchConfig,
+    hosts: Arc<HostState>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+/// Per-host state shared by clones of a [`Fetcher`].
+#[derive(Debug, Default)]
+struct HostState {
+    /// Parsed `robots.txt` by origin, fetched once per origin.
+    robots: Mutex<HashMap<String, Arc<OnceCell<Robots>>>>,
+    /// The earliest start of the next request to each host.
+    next_request: Mutex<HashMap<String, Instant>>,
+}
+
+/// A decoded page and the response it came from.
+#[derive(Clone, Debug)]
+pub struct FetchedHtml {
+    pub html: String,
+    /// The URL after redirects.
+    pub url: Url,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub validators: Validators,
+    /// The response has `Cache-Control: no-store`.
+    pub no_store: bool,
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Self::new(FetchConfig::default()).expect("Failed to create HTTP client")
+    }
+}
+
+impl Fetcher {
+    pub fn new(config: FetchConfig) -> Result<Self> {
+        let mut headers = header::HeaderMap::new();
+        for (name, value) in &config.headers {
+            headers.append(name.clone(), value.clone());
+        }
+        if let Some(cookie) = &config.cookie {
+            headers.insert(header::COOKIE, header::HeaderValue::from_str(cookie).context("invalid cookie")?);
+        }
+
+        let redirect_policy = match config.max_redirects {
+            0 => redirect::Policy::none(),
+            max => {
+                let policy = config.policy.clone();
+                redirect::Policy::custom(move |attempt| {
+                    if let Err(reason) = policy.check_url(attempt.url()) {
+                        attempt.error(reason)
+                    } else if attempt.previous().len() > max {
+                        attempt.error("too many redirects")
+                    } else {
+                        attempt.follow()
+                    }
+                })
+            },
+        };
+        let mut builder = Client::builder()
+            .gzip(true)
+            .brotli(true)
+            .deflate(true)
+            .user_agent(config.user_agent.as_str())
+            .default_headers(headers)
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .redirect(redirect_policy)
+            .danger_accept_invalid_certs(config.accept_invalid_certs);
+        if config.policy.block_private {
+            // A proxy resolves and connects to the host itself, out of
+            // reach of the resolver below, so private addresses could be
+            // fetched through it. The environment proxies are ignored.
+            if let Some(proxy) = &config.proxy {
+                bail!("proxy '{}' cannot be used while private addresses are blocked", proxy);
+            }
+            builder = builder.no_proxy();
+        } else if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(Proxy::all(proxy).with_context(|| format!("invalid proxy '{}'", proxy))?);
+        }
+        if let Some(path) = &config.ca_cert {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("failed to read CA certificate {}", path.display()))?;
+            let certificate = Certificate::from_pem(&pem)
+                .with_context(|| format!("invalid CA certificate {}", path.display()))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        if let Some(resolver) = config.policy.resolver() {
+            builder = builder.dns_resolver(resolver);
+        }
+        let client = builder.build().context("Failed to create HTTP client")?;
+        Ok(Self { client, config, hosts: Arc::default(), metrics: None })
+    }
+
+    /// See [`FetchConfig::cache_scope`].
+    pub fn cache_scope(&self) -> Option<String> {
+        self.config.cache_scope()
+    }
+
+    /// Records the duration, size and failures of fetches in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Fetches `url`, retrying transient failures according to the
+    /// configured [`RetryPolicy`](super::config::RetryPolicy).
+    pub async fn fetch_html(&self, url: &Url) -> Result<String, FetchError> {
+        self.fetch_page(url).await.map(|page| page.html)
+    }
+
+    /// Like [`fetch_html`](Self::fetch_html), but also returns where the
+    /// page ended up after redirects and how it was served.
+    pub async fn fetch_page(&self, url: &Url) -> Result<FetchedHtml, FetchError> {
+        let page = self.with_retries(url, |last_attempt| self.fetch_once(url, last_attempt, None)).await?;
+        Ok(page.expect("only conditional requests are answered with 304"))
+    }
+
+    /// Fetches `url` again unless it is unchanged since it was served with
+    /// `validators`, in which case the server answers 304 and `None` is
+    /// returned.
+    pub async fn revalidate_page(&self, url: &Url, validators: &Validators) -> Result<Option<FetchedHtml>, FetchError> {
+        self.with_retries(url, |last_attempt| self.fetch_once(url, last_attempt, Some(validators))).await
+    }
+
+    /// Fetches `url` as raw bytes, for documents other than pages such as
+    /// sitemaps. Any error status fails, whatever
+    /// [`FetchConfig::fail_on_status`] says.
+    pub async fn fetch_bytes(&self, url: &Url) -> Result<Vec<u8>, FetchError> {
+        self.with_retries(url, |_| async {
+            let response = self.send(url, true, true, None).await?;
+            self.read_body(response, self.config.max_body_size).await
+        }).await
+    }
+
+    /// Runs `attempt` until it succeeds, fails permanently or the retry
+    /// policy gives up, after checking `robots.txt` and waiting for the host
+    /// when configured. URLs refused by the policy fail without a request.
+    /// `attempt` is told whether it is the last one. The whole fetch is
+    /// recorded in the metrics, if any.
+    async fn with_retries<T, F, Fut>(&self, url: &Url, attempt_fn: F) -> Result<T, FetchError>
+    where
+        F: Fn(bool) -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let started = Instant::now();
+        let result = self.retry(url, attempt_fn).await;
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_fetch(started.elapsed(), result.as_ref().err());
+        }
+        result
+    }
+
+    /// The retry loop of [`with_retries`](Self::with_retries).
+    async fn retry<T, F, Fut>(&self, url: &Url, attempt_fn: F) -> Result<T, FetchError>
+    where
+        F: Fn(bool) -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        self.config.policy.check_url(url)
+            .map_err(|reason| FetchError::Blocked { url: url.clone(), reason: Box::new(reason) })?;
+        let mut host_delay = self.config.host_delay;
+        if self.config.respect_robots {
+            let robots = self.robots(url).await;
+            let path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            if !robots.is_allowed(&path) {
+                return Err(FetchError::Disallowed { url: url.clone() });
+            }
+            host_delay = host_delay.max(robots.crawl_delay());
+        }
+
+        let policy = &self.config.retry;
+        let mut attempt = 1;
+        loop {
+            let last_attempt = attempt >= policy.max_attempts;
+            if let Some(delay) = host_delay {
+                self.wait_for_host(url, delay).await;
+            }
+            match attempt_fn(last_attempt).await {
+                Err(error) if !last_attempt && error.is_retryable() => {
+                    let retry_after = match &error {
+                        FetchError::Status { retry_after, .. } => *retry_after,
+                        _ => None,
+                    };
+                    let delay = policy.delay(attempt, retry_after);
+                    warn!(
+                        "Attempt {}/{} for {} failed: {}; retrying in {:.1}s",
+                        attempt, policy.max_attempts, url, error, delay.as_secs_f64()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// The `robots.txt` rules for the origin of `url`, fetched on first use.
+    async fn robots(&self, url: &Url) -> Robots {
+        let cell = {
+            let mut robots = self.hosts.robots.lock().unwrap();
+            Arc::clone(robots.entry(url.origin().ascii_serialization()).or_default())
+        };
+        cell.get_or_init(|| self.fetch_robots(url)).await.clone()
+    }
+
+    /// Fetches `robots.txt`: a missing file (4xx) allows everything, a
+    /// server error disallows everything. A failed request also allows
+    /// everything, leaving the page request to report the problem. The
+    /// request waits for the host and is recorded in the metrics like
+    /// page requests.
+    async fn fetch_robots(&self, url: &Url) -> Robots {
+        let Ok(robots_url) = url.join("/robots.txt") else {
+            return Robots::allow_all();
+        };
+        if let Some(delay) = self.config.host_delay {
+            self.wait_for_host(&robots_url, delay).await;
+        }
+        let started = Instant::now();
+        let result = self.read_robots(&robots_url).await;
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_fetch(started.elapsed(), result.as_ref().err());
+        }
+        let (status, body) = match result {
+            Ok(response) => response,
+            Err(FetchError::TooLarge { .. }) => return Robots::allow_all(),
+            Err(e) => {
+                warn!("Failed to fetch {}: {}", robots_url, e);
+                return Robots::allow_all();
+            },
+        };
+        let robots = if status.is_success() {
+            Robots::parse(&String::from_utf8_lossy(&body), &self.config.user_agent)
+        } else if status.is_server_error() {
+            Robots::disallow_all()
+        } else {
+            Robots::allow_all()
+        };
+        info!("Loaded {} (HTTP {})", robots_url, status.as_u16());
+        robots
+    }
+
+    /// Requests `robots_url`, reading the body of a successful response.
+    async fn read_robots(&self, robots_url: &Url) -> Result<(StatusCode, Vec<u8>), FetchError> {
+        let response = with_read_timeout(self.config.read_timeout, self.client.get(robots_url.as_str()).send()).await??;
+        let status = response.status();
+        let body = if status.is_success() { self.read_body(response, MAX_ROBOTS_SIZE).await? } else { Vec::new() };
+        Ok((status, body))
+    }
+
+    /// Waits until at least `delay` has passed since the previous request
+    /// to the host of `url`. Slots are reserved in order, so concurrent
+    /// requests to one host are spaced out rather than released together.
+    async fn wait_for_host(&self, url: &Url, delay: Duration) {
+        let host = format!("{}:{}", url.host_str().unwrap_or(""), url.port_or_known_default().unwrap_or(0));
+        let start = {
+            let mut next_request = self.hosts.next_request.lock().unwrap();
+            let now = Instant::now();
+            let start = next_request.get(&host).copied().filter(|next| *next > now).unwrap_or(now);
+            next_request.insert(host, start + delay);
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// A single attempt. Retryable error statuses are reported as errors
+    /// even when the policy converts error pages, unless this is the last
+    /// attempt. `None` means a conditional request found the page unchanged.
+    async fn fetch_once(
+        &self,
+        url: &Url,
+        last_attempt: bool,
+        validators: Option<&Validators>,
+    ) -> Result<Option<FetchedHtml>, FetchError> {
+        let config = &self.config;
+        let response = self.send(url, config.fail_on_status, last_attempt, validators).await?;
+        if validators.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        check_content_type(&response)?;
+        let content_type = response.headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        let validators = Validators { etag: header(header::ETAG), last_modified: header(header::LAST_MODIFIED) };
+        let no_store = header(header::CACHE_CONTROL)
+            .is_some_and(|value| value.split(',').any(|directive| directive.trim().eq_ignore_ascii_case("no-store")));
+        let final_url = response.url().clone();
+        let status = response.status().as_u16();
+        let body = self.read_body(response, config.max_body_size).await?;
+        Ok(Some(FetchedHtml {
+            html: charset::decode_html(&body, content_type.as_deref(), config.encoding, Some(&final_url)),
+            url: final_url,
+            status,
+            content_type,
+            validators,
+            no_store,
+        }))
+    }
+
+    /// Reads the body of `response`, counting it in the metrics.
+    async fn read_body(&self, response: Response, limit: usize) -> Result<Vec<u8>, FetchError> {
+        let body = read_body(response, limit, self.config.read_timeout).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.add_fetched_bytes(body.len());
+        }
+        Ok(body)
+    }
+
+    /// Sends a GET request, conditional when `validators` are given, turning
+    /// an error status into [`FetchError::Status`] if `fail_on_status` is set
+    /// or it is worth retrying.
+    async fn send(
+        &self,
+        url: &Url,
+        fail_on_status: bool,
+        last_attempt: bool,
+        validators: Option<&Validators>,
+    ) -> Result<Response, FetchError> {
+        let mut request = self.client.get(url.as_str());
+        if let Some(validators) = validators {
+            if let Some(etag) = &validators.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = with_read_timeout(self.config.read_timeout, request.send()).await??;
+
+        let status = response.status();
+        if validators.is_some() && status == StatusCode::NOT_MODIFIED {
+            return Ok(response);
+        }
+        let retryable = !last_attempt && is_retryable_status(status.as_u16());
+        if !status.is_success() && (fail_on_status || retryable) {
+            return Err(FetchError::Status {
+                status: status.as_u16(),
+                url: response.url().clone(),
+                retry_after: retry_after(&response),
+            });
+        }
+        Ok(response)
+    }
+}
+
+/// Parses `Retry-After`, given either as seconds or as an HTTP date. Only
+/// meaningful on 429 and 503 responses.
+fn retry_after(response: &Response) -> Option<Duration> {
+    if !matches!(response.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) {
+        return None;
+    }
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()
+            .map(|date| date.duration_since(SystemTime::now()).unwrap_or_default()),
+    }
+}
+
+/// Awaits `future`, failing with [`FetchError::Timeout`] if it takes longer
+/// than `limit`.
+async fn with_read_timeout<F: Future>(limit: Option<Duration>, future: F) -> Result<F::Output, FetchError> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future).await.map_err(|_| FetchError::Timeout(None)),
+        None => Ok(future.await),
+    }
+}
+
+/// Accepts HTML, XHTML, other XML and plain text. A missing Content-Type is
+/// given the benefit of the doubt.
+fn check_content_type(response: &Response) -> Result<(), FetchError> {
+    let Some(content_type) = response.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok()) else {
+        return Ok(());
+    };
+    let essence = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let accepted = essence.is_empty()
+        || essence.starts_with("text/")
+        || essence == "application/xhtml+xml"
+        || essence == "application/xml"
+        || essence.ends_with("+xml");
+    if accepted {
+        Ok(())
+    } else {
+        Err(FetchError::NotHtml { content_type: content_type.to_string() })
+    }
+}
+
+/// Reads the body in chunks, giving up as soon as it exceeds `limit` rather
+/// than buffering an arbitrarily large download.
+async fn read_body(mut response: Response, limit: usize, read_timeout: Option<Duration>) -> Result<Vec<u8>, FetchError> {
+    if response.content_length().is_some_and(|length| length > limit as u64) {
+        return Err(FetchError::TooLarge { limit });
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = with_read_timeout(read_timeout, response.chunk()).await?? {
+        if body.len() + chunk.len() > limit {
+            return Err(FetchError::TooLarge { limit });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
 }
 
 pub async fn save_to_file(content: &str, path: &str) -> Result<()> {
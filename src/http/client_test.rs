@@ -1,35 +1,509 @@
-#[cfg(test)]
-mod tests {
-    use crate::http::client::{fetch_html, save_to_file};
-    use url::Url;
-    use tempfile;
-    use tokio;
-
-    #[tokio::test]
-    async fn test_save_to_file() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        let file_path = temp_dir.path().join("test.md");
-        let content = "Test content";
-
-        save_to_file(content, file_path.to_str().unwrap()).await.unwrap();
-
-        let saved_content = tokio::fs::read_to_string(file_path).await.unwrap();
-        assert_eq!(saved_content, content);
-    }
+use crate::http::client::{save_to_file, FetchError, Fetcher};
+use std::time::Duration;
 
-    #[tokio::test]
-    async fn test_fetch_html() {
-        // This test requires an internet connection
-        let url = Url::parse("https://example.com").unwrap();
-        let result = fetch_html(&url).await.unwrap();
-        assert!(result.contains("<html"));
-        assert!(result.contains("</html>"));
-    }
+use crate::http::config::{FetchConfig, RetryPolicy};
+use crate::http::policy::{HostPattern, PolicyError, UrlPolicy};
+use url::Url;
+
+#[tokio::test]
+async fn test_save_to_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file_path = temp_dir.path().join("test.md");
+    let content = "Test content";
+
+    save_to_file(content, file_path.to_str().unwrap()).await.unwrap();
+
+    let saved_content = tokio::fs::read_to_string(file_path).await.unwrap();
+    assert_eq!(saved_content, content);
+}
+
+#[tokio::test]
+async fn test_fetch_html() {
+    // This test requires an internet connection
+    let url = Url::parse("https://example.com").unwrap();
+    let result = Fetcher::default().fetch_html(&url).await.unwrap();
+    assert!(result.contains("<html"));
+    assert!(result.contains("</html>"));
+}
+
+#[tokio::test]
+async fn test_fetch_html_not_found() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server.mock("GET", "/not-found")
+        .with_status(404)
+        .with_header("content-type", "text/html")
+        .with_body("<html><body>Not found</body></html>")
+        .create_async()
+        .await;
+    let url = Url::parse(&format!("{}/not-found", server.url())).unwrap();
+
+    let result = Fetcher::default().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::Status { status: 404, .. })));
+
+    // Error pages are still converted when the policy allows it
+    let config = FetchConfig { fail_on_status: false, ..FetchConfig::default() };
+    let html = Fetcher::new(config).unwrap().fetch_html(&url).await.unwrap();
+    assert!(html.contains("Not found"));
+}
+
+#[tokio::test]
+async fn test_fetch_html_rejects_non_html() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server.mock("GET", "/file.pdf")
+        .with_header("content-type", "application/pdf")
+        .with_body("%PDF-1.7")
+        .create_async()
+        .await;
+    let url = Url::parse(&format!("{}/file.pdf", server.url())).unwrap();
+
+    let result = Fetcher::default().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::NotHtml { content_type }) if content_type == "application/pdf"));
+}
+
+#[tokio::test]
+async fn test_fetch_html_too_large() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("x".repeat(2048))
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let config = FetchConfig { max_body_size: 1024, ..FetchConfig::default() };
+    let result = Fetcher::new(config).unwrap().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::TooLarge { limit: 1024 })));
+}
+
+#[tokio::test]
+async fn test_fetch_html_decodes_header_charset() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server.mock("GET", "/")
+        .with_header("content-type", "text/html; charset=windows-1252")
+        .with_body(b"<p>caf\xe9</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let html = Fetcher::default().fetch_html(&url).await.unwrap();
+    assert!(html.contains("café"));
+}
 
-    #[tokio::test]
-    async fn test_fetch_html_not_found() {
-        let url = Url::parse("https://example.com/not-found").unwrap();
-        let result = fetch_html(&url).await;
-        assert!(result.is_ok()); // Even 404 pages return HTML
+#[tokio::test]
+async fn test_fetch_html_connection_refused() {
+    // Bind and drop a listener to get a local port nothing listens on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let url = Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap();
+
+    let config = FetchConfig { retry: RetryPolicy { max_attempts: 1, ..RetryPolicy::default() }, ..FetchConfig::default() };
+    let result = Fetcher::new(config).unwrap().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::Connect(_))));
+}
+
+#[tokio::test]
+async fn test_fetch_html_decodes_meta_charset() {
+    let mut server = mockito::Server::new_async().await;
+    let (body, _, _) = encoding_rs::GBK.encode("<meta charset=\"gbk\"><p>中文页面</p>");
+    let _mock = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body(body)
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let html = Fetcher::default().fetch_html(&url).await.unwrap();
+    assert!(html.contains("中文页面"));
+}
+
+#[tokio::test]
+async fn test_fetch_html_sends_configured_headers() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server.mock("GET", "/")
+        .match_header("user-agent", "Mozilla/5.0 (compatible; test)")
+        .match_header("accept-language", "de")
+        .match_header("cookie", "session=abc")
+        .with_header("content-type", "text/html")
+        .with_body("<p>ok</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let config = FetchConfig {
+        user_agent: "Mozilla/5.0 (compatible; test)".to_string(),
+        headers: vec![("accept-language".parse().unwrap(), "de".parse().unwrap())],
+        cookie: Some("session=abc".to_string()),
+        ..FetchConfig::default()
+    };
+    let html = Fetcher::new(config).unwrap().fetch_html(&url).await.unwrap();
+    assert_eq!(html, "<p>ok</p>");
+}
+
+#[tokio::test]
+async fn test_fetch_html_redirect_limit() {
+    let mut server = mockito::Server::new_async().await;
+    let _old = server.mock("GET", "/old")
+        .with_status(301)
+        .with_header("location", "/new")
+        .create_async()
+        .await;
+    let _new = server.mock("GET", "/new")
+        .with_header("content-type", "text/html")
+        .with_body("<p>moved</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&format!("{}/old", server.url())).unwrap();
+
+    let html = Fetcher::default().fetch_html(&url).await.unwrap();
+    assert_eq!(html, "<p>moved</p>");
+
+    let config = FetchConfig { max_redirects: 0, ..FetchConfig::default() };
+    let result = Fetcher::new(config).unwrap().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::Status { status: 301, .. })));
+}
+
+#[tokio::test]
+async fn test_fetch_html_read_timeout() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_chunked_body(|writer| {
+            std::thread::sleep(Duration::from_millis(500));
+            writer.write_all(b"<p>late</p>")
+        })
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let config = FetchConfig {
+        read_timeout: Some(Duration::from_millis(100)),
+        ..FetchConfig::default()
+    };
+    let result = Fetcher::new(config).unwrap().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::Timeout(_))));
+}
+
+#[test]
+fn test_fetcher_rejects_invalid_config() {
+    let config = FetchConfig { ca_cert: Some("/nonexistent/ca.pem".into()), ..FetchConfig::default() };
+    assert!(Fetcher::new(config).is_err());
+}
+
+fn fast_retries(max_attempts: u32) -> FetchConfig {
+    FetchConfig {
+        retry: RetryPolicy {
+            max_attempts,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            jitter: false,
+        },
+        ..FetchConfig::default()
     }
 }
+
+#[tokio::test]
+async fn test_fetch_html_retries_transient_status() {
+    let mut server = mockito::Server::new_async().await;
+    let unavailable = server.mock("GET", "/")
+        .with_status(503)
+        .expect(2)
+        .create_async()
+        .await;
+    let ok = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("<p>recovered</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let html = Fetcher::new(fast_retries(3)).unwrap().fetch_html(&url).await.unwrap();
+    assert_eq!(html, "<p>recovered</p>");
+    unavailable.assert_async().await;
+    ok.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_fetch_html_gives_up_after_max_attempts() {
+    let mut server = mockito::Server::new_async().await;
+    let throttled = server.mock("GET", "/")
+        .with_status(429)
+        .with_header("retry-after", "0")
+        .expect(3)
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let result = Fetcher::new(fast_retries(3)).unwrap().fetch_html(&url).await;
+    assert!(matches!(
+        result,
+        Err(FetchError::Status { status: 429, retry_after: Some(delay), .. }) if delay.is_zero()
+    ));
+    throttled.assert_async().await;
+
+    // With error pages allowed, the last response is converted
+    let config = FetchConfig { fail_on_status: false, ..fast_retries(2) };
+    assert!(Fetcher::new(config).unwrap().fetch_html(&url).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_fetch_html_honors_retry_after() {
+    let mut server = mockito::Server::new_async().await;
+    let _unavailable = server.mock("GET", "/")
+        .with_status(503)
+        .with_header("retry-after", "1")
+        .expect(1)
+        .create_async()
+        .await;
+    let _ok = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("<p>ok</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let config = FetchConfig {
+        retry: RetryPolicy { max_delay: Duration::from_secs(5), ..fast_retries(2).retry },
+        ..FetchConfig::default()
+    };
+    let started = std::time::Instant::now();
+    Fetcher::new(config).unwrap().fetch_html(&url).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_fetch_html_does_not_retry_permanent_errors() {
+    let mut server = mockito::Server::new_async().await;
+    let missing = server.mock("GET", "/")
+        .with_status(404)
+        .expect(1)
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let result = Fetcher::new(fast_retries(3)).unwrap().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::Status { status: 404, .. })));
+    missing.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_fetch_html_respects_robots_txt() {
+    let mut server = mockito::Server::new_async().await;
+    let robots = server.mock("GET", "/robots.txt")
+        .with_body("User-agent: *\nDisallow: /private\nAllow: /private/open\n")
+        .expect(1)
+        .create_async()
+        .await;
+    let private = server.mock("GET", "/private/secret")
+        .expect(0)
+        .create_async()
+        .await;
+    let _open = server.mock("GET", "/private/open")
+        .with_header("content-type", "text/html")
+        .with_body("<p>open</p>")
+        .create_async()
+        .await;
+    let base = Url::parse(&server.url()).unwrap();
+
+    let fetcher = Fetcher::new(FetchConfig { respect_robots: true, ..FetchConfig::default() }).unwrap();
+    let result = fetcher.fetch_html(&base.join("/private/secret").unwrap()).await;
+    assert!(matches!(result, Err(FetchError::Disallowed { .. })));
+    assert_eq!(fetcher.fetch_html(&base.join("/private/open").unwrap()).await.unwrap(), "<p>open</p>");
+
+    // robots.txt is fetched once and shared by clones
+    let clone = fetcher.clone();
+    assert!(clone.fetch_html(&base.join("/private/secret").unwrap()).await.is_err());
+    robots.assert_async().await;
+    private.assert_async().await;
+
+    // Without the option, robots.txt is ignored
+    let result = Fetcher::default().fetch_html(&base.join("/private/secret").unwrap()).await;
+    assert!(!matches!(result, Err(FetchError::Disallowed { .. })));
+}
+
+#[tokio::test]
+async fn test_fetch_html_robots_txt_status() {
+    let config = FetchConfig { respect_robots: true, ..FetchConfig::default() };
+
+    // A missing robots.txt allows everything
+    let mut server = mockito::Server::new_async().await;
+    let _robots = server.mock("GET", "/robots.txt").with_status(404).create_async().await;
+    let _page = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("<p>ok</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+    assert!(Fetcher::new(config.clone()).unwrap().fetch_html(&url).await.is_ok());
+
+    // A server error disallows everything
+    let mut server = mockito::Server::new_async().await;
+    let _robots = server.mock("GET", "/robots.txt").with_status(503).create_async().await;
+    let url = Url::parse(&server.url()).unwrap();
+    let result = Fetcher::new(config).unwrap().fetch_html(&url).await;
+    assert!(matches!(result, Err(FetchError::Disallowed { .. })));
+}
+
+#[tokio::test]
+async fn test_fetch_html_host_delay() {
+    let mut server = mockito::Server::new_async().await;
+    let _page = server.mock("GET", mockito::Matcher::Any)
+        .with_header("content-type", "text/html")
+        .with_body("<p>ok</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let config = FetchConfig { host_delay: Some(Duration::from_millis(300)), ..FetchConfig::default() };
+    let fetcher = Fetcher::new(config).unwrap();
+    let started = std::time::Instant::now();
+    let (first, second) = tokio::join!(fetcher.fetch_html(&url), fetcher.fetch_html(&url));
+    first.unwrap();
+    second.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn test_fetch_html_robots_txt_waits_for_host() {
+    let mut server = mockito::Server::new_async().await;
+    let _robots = server.mock("GET", "/robots.txt").with_body("User-agent: *\nAllow: /\n").create_async().await;
+    let _page = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("<p>ok</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let config = FetchConfig {
+        respect_robots: true,
+        host_delay: Some(Duration::from_millis(300)),
+        ..FetchConfig::default()
+    };
+    let fetcher = Fetcher::new(config).unwrap();
+    let started = std::time::Instant::now();
+    fetcher.fetch_html(&url).await.unwrap();
+    // robots.txt and the page are two requests to the host
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn test_fetch_html_honors_crawl_delay() {
+    let mut server = mockito::Server::new_async().await;
+    let _robots = server.mock("GET", "/robots.txt")
+        .with_body("User-agent: url2md\nCrawl-delay: 0.3\n")
+        .create_async()
+        .await;
+    let _page = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("<p>ok</p>")
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let fetcher = Fetcher::new(FetchConfig { respect_robots: true, ..FetchConfig::default() }).unwrap();
+    let started = std::time::Instant::now();
+    fetcher.fetch_html(&url).await.unwrap();
+    fetcher.fetch_html(&url).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn test_fetch_bytes() {
+    let mut server = mockito::Server::new_async().await;
+    let _archive = server.mock("GET", "/sitemap.xml.gz")
+        .with_header("content-type", "application/gzip")
+        .with_body([0x1f, 0x8b, 0x08])
+        .create_async()
+        .await;
+    let _missing = server.mock("GET", "/missing.xml")
+        .with_status(404)
+        .with_body("<urlset/>")
+        .create_async()
+        .await;
+    let base = Url::parse(&server.url()).unwrap();
+
+    // Any content type is accepted, but error statuses always fail
+    let fetcher = Fetcher::new(FetchConfig { fail_on_status: false, ..FetchConfig::default() }).unwrap();
+    let body = fetcher.fetch_bytes(&base.join("/sitemap.xml.gz").unwrap()).await.unwrap();
+    assert_eq!(body, [0x1f, 0x8b, 0x08]);
+    let result = fetcher.fetch_bytes(&base.join("/missing.xml").unwrap()).await;
+    assert!(matches!(result, Err(FetchError::Status { status: 404, .. })));
+}
+
+#[tokio::test]
+async fn test_fetch_html_blocks_private_addresses() {
+    let mut server = mockito::Server::new_async().await;
+    let page = server.mock("GET", "/")
+        .with_header("content-type", "text/html")
+        .with_body("<p>internal</p>")
+        .expect(0)
+        .create_async()
+        .await;
+    let url = Url::parse(&server.url()).unwrap();
+
+    let policy = UrlPolicy { block_private: true, ..UrlPolicy::default() };
+    let fetcher = Fetcher::new(FetchConfig { policy, ..FetchConfig::default() }).unwrap();
+    let result = fetcher.fetch_html(&url).await;
+    assert!(
+        matches!(&result, Err(FetchError::Blocked { reason, .. }) if matches!(**reason, PolicyError::PrivateAddress { .. })),
+        "{:?}",
+        result
+    );
+    assert!(!result.unwrap_err().is_retryable());
+
+    let file = Url::parse("file:///etc/passwd").unwrap();
+    let result = fetcher.fetch_html(&file).await;
+    assert!(matches!(result, Err(FetchError::Blocked { reason, .. }) if matches!(*reason, PolicyError::Scheme(_))));
+    page.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_fetcher_refuses_proxy_when_blocking_private_addresses() {
+    // The proxy would be handed internal host names without them being
+    // resolved here, so a fetcher blocking private addresses refuses it
+    let mut proxy = mockito::Server::new_async().await;
+    let forwarded = proxy.mock("GET", "/")
+        .match_header("host", "internal.example")
+        .with_header("content-type", "text/html")
+        .with_body("<p>internal</p>")
+        .create_async()
+        .await;
+    let config = FetchConfig {
+        proxy: Some(proxy.url()),
+        policy: UrlPolicy { block_private: true, ..UrlPolicy::default() },
+        ..FetchConfig::default()
+    };
+    let error = Fetcher::new(config.clone()).unwrap_err();
+    assert!(error.to_string().contains("private addresses are blocked"), "{}", error);
+
+    let config = FetchConfig { policy: UrlPolicy::default(), ..config };
+    let html = Fetcher::new(config).unwrap().fetch_html(&Url::parse("http://internal.example/").unwrap()).await.unwrap();
+    assert_eq!(html, "<p>internal</p>");
+    forwarded.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_fetch_html_checks_redirects() {
+    let mut server = mockito::Server::new_async().await;
+    let port = Url::parse(&server.url()).unwrap().port().unwrap();
+    let _redirect = server.mock("GET", "/old")
+        .with_status(302)
+        .with_header("location", &format!("http://localhost:{}/new", port))
+        .create_async()
+        .await;
+    let new = server.mock("GET", "/new")
+        .with_header("content-type", "text/html")
+        .with_body("<p>moved</p>")
+        .expect(0)
+        .create_async()
+        .await;
+    let url = Url::parse(&format!("{}/old", server.url())).unwrap();
+
+    let policy = UrlPolicy { deny_hosts: vec![HostPattern::parse("localhost").unwrap()], ..UrlPolicy::default() };
+    let fetcher = Fetcher::new(FetchConfig { policy, ..FetchConfig::default() }).unwrap();
+    let result = fetcher.fetch_html(&url).await;
+    assert!(
+        matches!(&result, Err(FetchError::Blocked { reason, .. }) if **reason == PolicyError::DeniedHost("localhost".to_string())),
+        "{:?}",
+        result
+    );
+    new.assert_async().await;
+}
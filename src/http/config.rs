@@ -0,0 +1,523 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use encoding_rs::Encoding;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::Deserialize;
+use url::Url;
+
+use super::access::{AccessConfig, RateLimit};
+use super::policy::{HostPattern, UrlPolicy};
+
+/// Default cap on response bodies, well above any real HTML page.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 20 * 1024 * 1024;
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+pub const DEFAULT_RETRIES: u32 = 2;
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+pub const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Sent unless overridden; some sites reject requests without a User-Agent.
+pub const DEFAULT_USER_AGENT: &str = concat!("url2md/", env!("CARGO_PKG_VERSION"));
+/// Only reachable from this machine unless `--bind` says otherwise.
+pub const DEFAULT_BIND: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+pub const DEFAULT_PORT: u16 = 3000;
+/// How long requests in flight may take to finish after a shutdown signal.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How pages are fetched: the HTTP client settings and the policy applied
+/// to responses by [`Fetcher`](super::client::Fetcher).
+#[derive(Clone, Debug)]
+pub struct FetchConfig {
+    /// Fail with [`FetchError::Status`](super::client::FetchError::Status) on
+    /// non-2xx responses instead of converting the error page.
+    pub fail_on_status: bool,
+    /// Largest response body accepted, in bytes.
+    pub max_body_size: usize,
+    /// Decode the body with this encoding instead of detecting it.
+    pub encoding: Option<&'static Encoding>,
+    /// Time allowed to establish the connection.
+    pub connect_timeout: Duration,
+    /// Longest wait for the server to send the next piece of the response.
+    pub read_timeout: Option<Duration>,
+    /// Time allowed for the whole request, including reading the body.
+    pub timeout: Duration,
+    pub user_agent: String,
+    /// Extra headers sent with every request.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// Value of the `Cookie` header, e.g. `session=abc; theme=dark`.
+    pub cookie: Option<String>,
+    /// Proxy for all requests (`http://`, `https://`, `socks5://` or
+    /// `socks5h://`). Without one, the `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY`
+    /// environment variables are honoured.
+    pub proxy: Option<String>,
+    /// Redirects followed before giving up; 0 disables redirects.
+    pub max_redirects: usize,
+    /// Skip certificate validation. Only meant for testing.
+    pub accept_invalid_certs: bool,
+    /// Extra PEM root certificate to trust, e.g. a corporate CA.
+    pub ca_cert: Option<PathBuf>,
+    /// How transient failures are retried.
+    pub retry: RetryPolicy,
+    /// Check each URL against its site's `robots.txt` and honour its
+    /// `Crawl-delay`.
+    pub respect_robots: bool,
+    /// Smallest gap between the starts of two requests to the same host.
+    pub host_delay: Option<Duration>,
+    /// Which URLs may be fetched at all.
+    pub policy: UrlPolicy,
+}
+
+impl FetchConfig {
+    /// What sets these requests apart for the cache besides the URL: the
+    /// settings that may change the page served, empty when they are all
+    /// defaults. `None` when requests carry extra headers or a cookie,
+    /// which may be credentials: such pages must not be stored and served
+    /// to requests without them.
+    pub fn cache_scope(&self) -> Option<String> {
+        if !self.headers.is_empty() || self.cookie.is_some() {
+            return None;
+        }
+        let mut scope = Vec::new();
+        if self.user_agent != DEFAULT_USER_AGENT {
+            scope.push(format!("user-agent={}", self.user_agent));
+        }
+        if let Some(proxy) = &self.proxy {
+            // The key is written to disk, the proxy password must not be
+            let proxy = match Url::parse(proxy) {
+                Ok(mut url) => {
+                    let _ = url.set_password(None);
+                    url.to_string()
+                },
+                Err(_) => proxy.clone(),
+            };
+            scope.push(format!("proxy={}", proxy));
+        }
+        if let Some(encoding) = self.encoding {
+            scope.push(format!("encoding={}", encoding.name()));
+        }
+        Some(scope.join(" "))
+    }
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            fail_on_status: true,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            encoding: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: None,
+            timeout: DEFAULT_TIMEOUT,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            headers: Vec::new(),
+            cookie: None,
+            proxy: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            accept_invalid_certs: false,
+            ca_cert: None,
+            retry: RetryPolicy::default(),
+            respect_robots: false,
+            host_delay: None,
+            policy: UrlPolicy::default(),
+        }
+    }
+}
+
+/// Where and how the server listens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServeConfig {
+    pub addr: SocketAddr,
+    /// Serve HTTPS with this certificate chain and private key.
+    pub tls: Option<TlsFiles>,
+    /// Time requests in flight get to finish on shutdown before their
+    /// connections are closed.
+    pub shutdown_timeout: Duration,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::new(DEFAULT_BIND, DEFAULT_PORT),
+            tls: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+}
+
+/// PEM files for HTTPS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Retries for transient failures: 429, 502, 503 and 504 responses,
+/// timeouts and dropped connections. Delays grow exponentially from
+/// `initial_delay`, capped at `max_delay`, unless the server asks for a
+/// specific delay with `Retry-After`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first; 1 disables retries.
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Randomise each delay between half and all of its value, so clients
+    /// that failed together do not retry together.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_RETRIES + 1,
+            initial_delay: DEFAULT_RETRY_DELAY,
+            max_delay: DEFAULT_RETRY_MAX_DELAY,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay after failed attempt number `attempt` (starting at 1).
+    /// A `Retry-After` value wins over the backoff but is still capped at
+    /// `max_delay`.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let backoff = self.initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        if self.jitter {
+            backoff.mul_f64(0.5 + fastrand::f64() / 2.0)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// HTTP client settings shared by the CLI flags, `URL2MD_*` environment
+/// variables and the `[fetch]` table of the configuration file. Unset values
+/// keep the [`FetchConfig`] defaults.
+#[derive(clap::Args, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchArgs {
+    /// Seconds allowed to establish a connection [default: 10]
+    #[arg(long, env = "URL2MD_CONNECT_TIMEOUT", value_name = "SECS", global = true)]
+    pub connect_timeout: Option<f64>,
+
+    /// Seconds to wait for the server to send more data
+    #[arg(long, env = "URL2MD_READ_TIMEOUT", value_name = "SECS", global = true)]
+    pub read_timeout: Option<f64>,
+
+    /// Seconds allowed for the whole request [default: 30]
+    #[arg(long, env = "URL2MD_TIMEOUT", value_name = "SECS", global = true)]
+    pub timeout: Option<f64>,
+
+    /// User-Agent header sent with requests
+    #[arg(long, env = "URL2MD_USER_AGENT", global = true)]
+    pub user_agent: Option<String>,
+
+    /// Extra request header as "Name: value" (repeatable)
+    #[arg(short = 'H', long = "header", value_name = "HEADER", global = true)]
+    pub headers: Vec<String>,
+
+    /// Cookies to send, e.g. "session=abc; theme=dark"
+    #[arg(long, env = "URL2MD_COOKIE", global = true)]
+    pub cookie: Option<String>,
+
+    /// Proxy URL (http, https, socks5 or socks5h)
+    #[arg(long, env = "URL2MD_PROXY", global = true)]
+    pub proxy: Option<String>,
+
+    /// Maximum number of redirects to follow, 0 to disable [default: 10]
+    #[arg(long, env = "URL2MD_MAX_REDIRECTS", global = true)]
+    pub max_redirects: Option<usize>,
+
+    /// Accept invalid TLS certificates
+    #[arg(long, env = "URL2MD_INSECURE", global = true)]
+    pub insecure: bool,
+
+    /// Additional PEM root certificate to trust
+    #[arg(long, env = "URL2MD_CA_CERT", value_name = "PATH", global = true)]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Times to retry transient failures, 0 to disable [default: 2]
+    #[arg(long, env = "URL2MD_RETRIES", global = true)]
+    pub retries: Option<u32>,
+
+    /// Seconds before the first retry, doubled for each later one [default: 0.5]
+    #[arg(long, env = "URL2MD_RETRY_DELAY", value_name = "SECS", global = true)]
+    pub retry_delay: Option<f64>,
+
+    /// Longest delay between retries, in seconds, also capping Retry-After [default: 30]
+    #[arg(long, env = "URL2MD_RETRY_MAX_DELAY", value_name = "SECS", global = true)]
+    pub retry_max_delay: Option<f64>,
+
+    /// Skip pages disallowed by the site's robots.txt and honour its Crawl-delay
+    #[arg(long, env = "URL2MD_RESPECT_ROBOTS", global = true)]
+    pub respect_robots: bool,
+
+    /// Minimum seconds between requests to the same host
+    #[arg(long, env = "URL2MD_HOST_DELAY", value_name = "SECS", global = true)]
+    pub host_delay: Option<f64>,
+
+    /// Only fetch from this host, or its subdomains with "*.example.com" (repeatable)
+    #[arg(long = "allow-host", env = "URL2MD_ALLOW_HOSTS", value_name = "HOST", value_delimiter = ',', global = true)]
+    pub allow_hosts: Vec<String>,
+
+    /// Never fetch from this host, or its subdomains with "*.example.com" (repeatable)
+    #[arg(long = "deny-host", env = "URL2MD_DENY_HOSTS", value_name = "HOST", value_delimiter = ',', global = true)]
+    pub deny_hosts: Vec<String>,
+
+    /// Let the server fetch loopback, private and link-local addresses
+    #[arg(long, env = "URL2MD_ALLOW_PRIVATE_NETWORKS", global = true)]
+    pub allow_private_networks: bool,
+}
+
+impl FetchArgs {
+    /// Fills settings missing from `self` with those from `fallback`, e.g.
+    /// command line arguments over the configuration file. Headers from both
+    /// are sent and hosts denied by either are denied, while allowed hosts
+    /// given in `self` replace those of `fallback`.
+    pub fn or(self, fallback: FetchArgs) -> FetchArgs {
+        let mut headers = fallback.headers;
+        headers.extend(self.headers);
+        let mut deny_hosts = fallback.deny_hosts;
+        deny_hosts.extend(self.deny_hosts);
+        let allow_hosts = if self.allow_hosts.is_empty() { fallback.allow_hosts } else { self.allow_hosts };
+        FetchArgs {
+            connect_timeout: self.connect_timeout.or(fallback.connect_timeout),
+            read_timeout: self.read_timeout.or(fallback.read_timeout),
+            timeout: self.timeout.or(fallback.timeout),
+            user_agent: self.user_agent.or(fallback.user_agent),
+            headers,
+            cookie: self.cookie.or(fallback.cookie),
+            proxy: self.proxy.or(fallback.proxy),
+            max_redirects: self.max_redirects.or(fallback.max_redirects),
+            insecure: self.insecure || fallback.insecure,
+            ca_cert: self.ca_cert.or(fallback.ca_cert),
+            retries: self.retries.or(fallback.retries),
+            retry_delay: self.retry_delay.or(fallback.retry_delay),
+            retry_max_delay: self.retry_max_delay.or(fallback.retry_max_delay),
+            respect_robots: self.respect_robots || fallback.respect_robots,
+            host_delay: self.host_delay.or(fallback.host_delay),
+            allow_hosts,
+            deny_hosts,
+            allow_private_networks: self.allow_private_networks || fallback.allow_private_networks,
+        }
+    }
+
+    pub fn to_config(&self) -> Result<FetchConfig> {
+        let defaults = FetchConfig::default();
+        let headers = self.headers.iter()
+            .map(|header| parse_header(header))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(proxy) = &self.proxy {
+            reqwest::Proxy::all(proxy).map_err(|e| anyhow!("invalid proxy '{}': {}", proxy, e))?;
+        }
+        if let Some(cookie) = &self.cookie {
+            HeaderValue::from_str(cookie).map_err(|_| anyhow!("invalid cookie '{}'", cookie))?;
+        }
+        Ok(FetchConfig {
+            connect_timeout: seconds("connect timeout", self.connect_timeout)?.unwrap_or(defaults.connect_timeout),
+            read_timeout: seconds("read timeout", self.read_timeout)?,
+            timeout: seconds("timeout", self.timeout)?.unwrap_or(defaults.timeout),
+            user_agent: self.user_agent.clone().unwrap_or(defaults.user_agent),
+            headers,
+            cookie: self.cookie.clone(),
+            proxy: self.proxy.clone(),
+            max_redirects: self.max_redirects.unwrap_or(defaults.max_redirects),
+            accept_invalid_certs: self.insecure,
+            ca_cert: self.ca_cert.clone(),
+            retry: RetryPolicy {
+                max_attempts: self.retries.map_or(defaults.retry.max_attempts, |retries| retries.saturating_add(1)),
+                initial_delay: delay("retry delay", self.retry_delay)?.unwrap_or(defaults.retry.initial_delay),
+                max_delay: seconds("retry max delay", self.retry_max_delay)?.unwrap_or(defaults.retry.max_delay),
+                jitter: true,
+            },
+            respect_robots: self.respect_robots,
+            host_delay: delay("host delay", self.host_delay)?,
+            policy: UrlPolicy {
+                block_private: false,
+                allow_hosts: host_patterns(&self.allow_hosts)?,
+                deny_hosts: host_patterns(&self.deny_hosts)?,
+            },
+            ..defaults
+        })
+    }
+
+    /// Like [`to_config`](Self::to_config), for fetching URLs given by
+    /// clients of the server: private addresses are blocked unless
+    /// `--allow-private-networks` is set.
+    /// A proxy would resolve host names out of reach of that check, so it
+    /// is refused unless private addresses are allowed.
+    pub fn to_server_config(&self) -> Result<FetchConfig> {
+        let mut config = self.to_config()?;
+        config.policy.block_private = !self.allow_private_networks;
+        if config.policy.block_private && config.proxy.is_some() {
+            bail!("--proxy cannot be used by the server unless --allow-private-networks is set");
+        }
+        Ok(config)
+    }
+}
+
+/// Server settings shared by the `serve` flags, `URL2MD_*` environment
+/// variables and the `[server]` table of the configuration file.
+#[derive(clap::Args, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerArgs {
+    /// Address to listen on, e.g. 0.0.0.0 for all interfaces [default: 127.0.0.1]
+    #[arg(long, env = "URL2MD_BIND", value_name = "ADDR")]
+    pub bind: Option<IpAddr>,
+
+    /// Port to listen on [default: 3000]
+    #[arg(short = 'P', long, env = "URL2MD_PORT")]
+    pub port: Option<u16>,
+
+    /// PEM certificate chain; serves HTTPS together with --tls-key
+    #[arg(long, env = "URL2MD_TLS_CERT", value_name = "PATH", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "URL2MD_TLS_KEY", value_name = "PATH", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Seconds requests in flight get to finish after SIGTERM or Ctrl-C [default: 30]
+    #[arg(long, env = "URL2MD_SHUTDOWN_TIMEOUT", value_name = "SECS")]
+    pub shutdown_timeout: Option<f64>,
+
+    /// API key clients must send as "Authorization: Bearer KEY" or "X-API-Key: KEY" (repeatable)
+    #[arg(long = "api-key", env = "URL2MD_API_KEYS", value_name = "KEY", value_delimiter = ',', hide_env_values = true)]
+    pub api_keys: Vec<String>,
+
+    /// Requests per minute for each API key, or each IP address without keys
+    #[arg(long, env = "URL2MD_RATE_LIMIT", value_name = "REQUESTS")]
+    pub rate_limit: Option<u32>,
+
+    /// Requests per minute for each IP address, whatever its key
+    #[arg(long, env = "URL2MD_IP_RATE_LIMIT", value_name = "REQUESTS")]
+    pub ip_rate_limit: Option<u32>,
+
+    /// Origin allowed to call the server from a browser (repeatable) [default: any]
+    #[arg(long = "cors-origin", env = "URL2MD_CORS_ORIGINS", value_name = "ORIGIN", value_delimiter = ',')]
+    pub cors_origins: Vec<String>,
+
+    /// Address of a reverse proxy whose X-Forwarded-For or Forwarded header names the client (repeatable)
+    #[arg(long = "trusted-proxy", env = "URL2MD_TRUSTED_PROXIES", value_name = "IP", value_delimiter = ',')]
+    pub trusted_proxies: Vec<String>,
+}
+
+impl ServerArgs {
+    /// Fills settings missing from `self` with those from `fallback`. Keys,
+    /// origins and proxies given in `self` replace those of `fallback`.
+    pub fn or(self, fallback: ServerArgs) -> ServerArgs {
+        let or_list = |list: Vec<String>, fallback: Vec<String>| if list.is_empty() { fallback } else { list };
+        ServerArgs {
+            bind: self.bind.or(fallback.bind),
+            port: self.port.or(fallback.port),
+            tls_cert: self.tls_cert.or(fallback.tls_cert),
+            tls_key: self.tls_key.or(fallback.tls_key),
+            shutdown_timeout: self.shutdown_timeout.or(fallback.shutdown_timeout),
+            api_keys: or_list(self.api_keys, fallback.api_keys),
+            rate_limit: self.rate_limit.or(fallback.rate_limit),
+            ip_rate_limit: self.ip_rate_limit.or(fallback.ip_rate_limit),
+            cors_origins: or_list(self.cors_origins, fallback.cors_origins),
+            trusted_proxies: or_list(self.trusted_proxies, fallback.trusted_proxies),
+        }
+    }
+
+    pub fn to_serve_config(&self) -> Result<ServeConfig> {
+        let tls = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some(TlsFiles { cert: cert.clone(), key: key.clone() }),
+            (None, None) => None,
+            _ => bail!("HTTPS needs both a TLS certificate and a TLS key"),
+        };
+        Ok(ServeConfig {
+            addr: SocketAddr::new(self.bind.unwrap_or(DEFAULT_BIND), self.port.unwrap_or(DEFAULT_PORT)),
+            tls,
+            shutdown_timeout: seconds("shutdown timeout", self.shutdown_timeout)?.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
+        })
+    }
+
+    pub fn to_access_config(&self) -> Result<AccessConfig> {
+        let api_keys: Vec<String> = self.api_keys.iter()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+        if let Some(key) = api_keys.iter().find(|key| key.chars().any(|c| c.is_whitespace() || c.is_control())) {
+            bail!("invalid API key '{}…': must not contain spaces", key.chars().take(4).collect::<String>());
+        }
+        let rate_limit = |name: &str, limit: Option<u32>| match limit {
+            Some(0) => bail!("invalid {}: must be at least 1 request per minute", name),
+            limit => Ok(limit.map(RateLimit::per_minute)),
+        };
+        let cors_origins = self.cors_origins.iter()
+            .filter(|origin| origin.trim() != "*")
+            .map(|origin| parse_origin(origin))
+            .collect::<Result<Vec<_>>>()?;
+        let trusted_proxies = self.trusted_proxies.iter()
+            .map(|proxy| proxy.trim().parse().map_err(|_| anyhow!("invalid trusted proxy '{}': expected an IP address", proxy)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(AccessConfig {
+            api_keys,
+            rate_limit: rate_limit("rate limit", self.rate_limit)?,
+            ip_rate_limit: rate_limit("IP rate limit", self.ip_rate_limit)?,
+            // "*" anywhere allows every origin, as no origins do
+            cors_origins: if self.cors_origins.iter().any(|origin| origin.trim() == "*") { Vec::new() } else { cors_origins },
+            trusted_proxies,
+        })
+    }
+}
+
+/// Parses a CORS origin such as `https://app.example.com`, which browsers
+/// send without a path.
+fn parse_origin(origin: &str) -> Result<axum::http::HeaderValue> {
+    let origin = origin.trim().trim_end_matches('/');
+    let url = url::Url::parse(origin).map_err(|e| anyhow!("invalid CORS origin '{}': {}", origin, e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.path() != "/" || url.query().is_some() {
+        bail!("invalid CORS origin '{}': expected a scheme and host, e.g. https://app.example.com", origin);
+    }
+    axum::http::HeaderValue::from_str(origin).map_err(|_| anyhow!("invalid CORS origin '{}'", origin))
+}
+
+/// Parses a `Name: value` header.
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue)> {
+    let (name, value) = header.split_once(':')
+        .ok_or_else(|| anyhow!("invalid header '{}': expected 'Name: value'", header))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .with_context(|| format!("invalid header name in '{}'", header))?;
+    let value = HeaderValue::from_str(value.trim())
+        .with_context(|| format!("invalid header value in '{}'", header))?;
+    Ok((name, value))
+}
+
+fn host_patterns(patterns: &[String]) -> Result<Vec<HostPattern>> {
+    patterns.iter().map(|pattern| HostPattern::parse(pattern)).collect()
+}
+
+fn seconds(name: &str, value: Option<f64>) -> Result<Option<Duration>> {
+    match value {
+        None => Ok(None),
+        Some(secs) if secs > 0.0 => Duration::try_from_secs_f64(secs)
+            .map(Some)
+            .map_err(|_| anyhow!("invalid {} '{}': too large", name, secs)),
+        Some(secs) => bail!("invalid {} '{}': must be a positive number of seconds", name, secs),
+    }
+}
+
+/// Like [`seconds`], but 0 is allowed and turns the delay off.
+fn delay(name: &str, value: Option<f64>) -> Result<Option<Duration>> {
+    match value {
+        Some(0.0) => Ok(Some(Duration::ZERO)),
+        Some(secs) if secs < 0.0 => bail!("invalid {} '{}': must not be negative", name, secs),
+        _ => seconds(name, value),
+    }
+}
+
+#[cfg(test)]
+#[path = "config_test.rs"]
+mod tests;
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::cache::CacheStatus;
+use super::client::FetchError;
+
+/// The `Content-Type` of [`Metrics::render`].
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Server counters and latencies, exposed in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Responses by route and status.
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    fetch_duration: Histogram,
+    conversion_duration: Histogram,
+    fetched_bytes: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    /// Failed fetches by [`FetchError::kind`].
+    upstream_errors: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    /// Counts a response to a request for `route`, the route pattern
+    /// rather than the path so that URLs do not become labels.
+    pub fn count_request(&self, route: &str, status: u16) {
+        *self.requests.lock().unwrap().entry((route.to_string(), status)).or_default() += 1;
+    }
+
+    /// Records a page fetch, including its retries, and how it failed.
+    pub fn observe_fetch(&self, duration: Duration, error: Option<&FetchError>) {
+        self.fetch_duration.observe(duration);
+        if let Some(error) = error {
+            *self.upstream_errors.lock().unwrap().entry(error.kind()).or_default() += 1;
+        }
+    }
+
+    pub fn add_fetched_bytes(&self, bytes: usize) {
+        self.fetched_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Runs `convert`, recording how long it takes.
+    pub fn time_conversion<T>(&self, convert: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = convert();
+        self.conversion_duration.observe(started.elapsed());
+        result
+    }
+
+    pub fn count_cache(&self, status: Option<CacheStatus>) {
+        let counter = match status {
+            Some(CacheStatus::Hit) => &self.cache_hits,
+            Some(CacheStatus::Miss) => &self.cache_misses,
+            None => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Everything recorded so far, in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        header(&mut out, "url2md_http_requests_total", "counter", "Responses sent, by route and status.");
+        for ((route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "url2md_http_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+                escape(route), status, count
+            );
+        }
+
+        header(&mut out, "url2md_fetch_duration_seconds", "histogram", "Time to fetch a page, including retries.");
+        self.fetch_duration.render(&mut out, "url2md_fetch_duration_seconds");
+        header(&mut out, "url2md_conversion_duration_seconds", "histogram", "Time to convert a fetched or posted page.");
+        self.conversion_duration.render(&mut out, "url2md_conversion_duration_seconds");
+
+        header(&mut out, "url2md_fetched_bytes_total", "counter", "Response body bytes read from upstream servers.");
+        let _ = writeln!(out, "url2md_fetched_bytes_total {}", self.fetched_bytes.load(Ordering::Relaxed));
+
+        let hits = self.cache_hits.load(Ordering::Relaxed);
+        let misses = self.cache_misses.load(Ordering::Relaxed);
+        header(&mut out, "url2md_cache_requests_total", "counter", "Cache lookups, by result.");
+        let _ = writeln!(out, "url2md_cache_requests_total{{result=\"hit\"}} {}", hits);
+        let _ = writeln!(out, "url2md_cache_requests_total{{result=\"miss\"}} {}", misses);
+        header(&mut out, "url2md_cache_hit_ratio", "gauge", "Share of cache lookups answered from the cache since startup.");
+        let ratio = if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 };
+        let _ = writeln!(out, "url2md_cache_hit_ratio {}", ratio);
+
+        header(&mut out, "url2md_upstream_errors_total", "counter", "Failed page fetches, by kind of failure.");
+        for (kind, count) in self.upstream_errors.lock().unwrap().iter() {
+            let _ = writeln!(out, "url2md_upstream_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+        out
+    }
+}
+
+/// Observations counted into [`BUCKETS`].
+#[derive(Debug, Default)]
+struct Histogram {
+    state: Mutex<HistogramState>,
+}
+
+#[derive(Debug, Default)]
+struct HistogramState {
+    /// Observations in each bucket alone; cumulated when rendered.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let mut state = self.state.lock().unwrap();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| secs <= *bound) {
+            state.buckets[bucket] += 1;
+        }
+        state.count += 1;
+        state.sum += secs;
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let state = self.state.lock().unwrap();
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(state.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, state.count);
+        let _ = writeln!(out, "{}_sum {}", name, state.sum);
+        let _ = writeln!(out, "{}_count {}", name, state.count);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+#[path = "metrics_test.rs"]
+mod tests;
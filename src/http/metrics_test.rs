@@ -0,0 +1,114 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use url::Url;
+
+use super::{Metrics, CONTENT_TYPE};
+use crate::http::cache::CacheStatus;
+use crate::http::client::Fetcher;
+use crate::http::config::FetchConfig;
+
+#[test]
+fn test_render_without_observations() {
+    let text = Metrics::default().render();
+    for name in [
+        "url2md_http_requests_total counter",
+        "url2md_fetch_duration_seconds histogram",
+        "url2md_conversion_duration_seconds histogram",
+        "url2md_fetched_bytes_total counter",
+        "url2md_cache_requests_total counter",
+        "url2md_cache_hit_ratio gauge",
+        "url2md_upstream_errors_total counter",
+    ] {
+        assert!(text.contains(&format!("# TYPE {}\n", name)), "missing {}", name);
+    }
+    assert!(text.contains("url2md_fetch_duration_seconds_bucket{le=\"+Inf\"} 0\n"));
+    assert!(text.contains("url2md_cache_hit_ratio 0\n"));
+    assert!(CONTENT_TYPE.starts_with("text/plain; version=0.0.4"));
+}
+
+#[test]
+fn test_histogram_buckets_are_cumulative() {
+    let metrics = Metrics::default();
+    for millis in [3, 70, 60_000] {
+        metrics.time_conversion(|| ());
+        metrics.observe_fetch(Duration::from_millis(millis), None);
+    }
+    let text = metrics.render();
+    assert!(text.contains("url2md_fetch_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+    assert!(text.contains("url2md_fetch_duration_seconds_bucket{le=\"0.05\"} 1\n"));
+    assert!(text.contains("url2md_fetch_duration_seconds_bucket{le=\"0.1\"} 2\n"));
+    assert!(text.contains("url2md_fetch_duration_seconds_bucket{le=\"30\"} 2\n"));
+    assert!(text.contains("url2md_fetch_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+    assert!(text.contains("url2md_fetch_duration_seconds_sum 60.073\n"));
+    assert!(text.contains("url2md_fetch_duration_seconds_count 3\n"));
+    assert!(text.contains("url2md_conversion_duration_seconds_count 3\n"));
+}
+
+#[test]
+fn test_requests_and_cache_counts() {
+    let metrics = Metrics::default();
+    metrics.count_request("/*url", 200);
+    metrics.count_request("/*url", 200);
+    metrics.count_request("/api/convert", 429);
+    metrics.count_request("quote\"d", 200);
+    metrics.count_cache(Some(CacheStatus::Hit));
+    metrics.count_cache(Some(CacheStatus::Hit));
+    metrics.count_cache(Some(CacheStatus::Hit));
+    metrics.count_cache(Some(CacheStatus::Miss));
+    metrics.count_cache(None);
+
+    let text = metrics.render();
+    assert!(text.contains("url2md_http_requests_total{route=\"/*url\",status=\"200\"} 2\n"));
+    assert!(text.contains("url2md_http_requests_total{route=\"/api/convert\",status=\"429\"} 1\n"));
+    assert!(text.contains("url2md_http_requests_total{route=\"quote\\\"d\",status=\"200\"} 1\n"));
+    assert!(text.contains("url2md_cache_requests_total{result=\"hit\"} 3\n"));
+    assert!(text.contains("url2md_cache_requests_total{result=\"miss\"} 1\n"));
+    assert!(text.contains("url2md_cache_hit_ratio 0.75\n"));
+}
+
+#[tokio::test]
+async fn test_fetcher_records_metrics() {
+    let mut server = mockito::Server::new_async().await;
+    let _page = server.mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<p>hi</p>")
+        .create_async()
+        .await;
+    let _missing = server.mock("GET", "/missing").with_status(404).create_async().await;
+    let base = Url::parse(&server.url()).unwrap();
+    let metrics = Arc::new(Metrics::default());
+    let fetcher = Fetcher::default().with_metrics(Arc::clone(&metrics));
+
+    fetcher.fetch_page(&base.join("/page").unwrap()).await.unwrap();
+    assert!(fetcher.fetch_page(&base.join("/missing").unwrap()).await.is_err());
+    assert!(fetcher.fetch_page(&Url::parse("ftp://example.com/").unwrap()).await.is_err());
+
+    let text = metrics.render();
+    assert!(text.contains("url2md_fetched_bytes_total 9\n"), "{}", text);
+    assert!(text.contains("url2md_fetch_duration_seconds_count 3\n"));
+    assert!(text.contains("url2md_upstream_errors_total{kind=\"status\"} 1\n"));
+    assert!(text.contains("url2md_upstream_errors_total{kind=\"blocked\"} 1\n"));
+}
+
+#[tokio::test]
+async fn test_fetcher_records_robots_txt() {
+    let mut server = mockito::Server::new_async().await;
+    let _robots = server.mock("GET", "/robots.txt").with_body("User-agent: *\nAllow: /\n").create_async().await;
+    let _page = server.mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<p>hi</p>")
+        .create_async()
+        .await;
+    let metrics = Arc::new(Metrics::default());
+    let fetcher = Fetcher::new(FetchConfig { respect_robots: true, ..FetchConfig::default() })
+        .unwrap()
+        .with_metrics(Arc::clone(&metrics));
+
+    fetcher.fetch_page(&Url::parse(&server.url()).unwrap().join("/page").unwrap()).await.unwrap();
+
+    let text = metrics.render();
+    // 23 bytes of robots.txt and 9 of the page
+    assert!(text.contains("url2md_fetched_bytes_total 32\n"), "{}", text);
+    assert!(text.contains("url2md_fetch_duration_seconds_count 2\n"), "{}", text);
+}
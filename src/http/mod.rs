@@ -1,4 +1,12 @@
+pub mod access;
+pub mod cache;
+pub mod charset;
 pub mod client;
+pub mod config;
+pub mod metrics;
+pub mod policy;
+pub mod robots;
 pub mod server;
 
 pub use client::*;
+pub use config::*;
@@ -0,0 +1,197 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use url::{Host, Url};
+
+/// Which URLs the [`Fetcher`](super::client::Fetcher) may request. Checked
+/// for the requested URL and every redirect; with `block_private`, host
+/// names are also checked once resolved, right before connecting, so a
+/// name cannot be re-pointed at an internal address between the check and
+/// the request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UrlPolicy {
+    /// Refuse loopback, private, link-local and other non-public addresses,
+    /// including cloud metadata endpoints such as `169.254.169.254`.
+    pub block_private: bool,
+    /// When non-empty, only hosts matching one of these are fetched.
+    pub allow_hosts: Vec<HostPattern>,
+    /// Hosts never fetched, even when they match `allow_hosts`.
+    pub deny_hosts: Vec<HostPattern>,
+}
+
+/// A host name or IP address matched exactly, or `*.example.com` matching
+/// every subdomain of `example.com` (but not `example.com` itself).
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostPattern(String);
+
+impl HostPattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let normalized = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+        let unbracketed = normalized.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+            return Ok(Self(ip.to_string()));
+        }
+        let name = normalized.strip_prefix("*.").unwrap_or(&normalized);
+        let valid = !name.is_empty()
+            && name.split('.').all(|label| !label.is_empty())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_');
+        if !valid {
+            bail!("invalid host pattern '{}': expected a host name, an IP address or '*.domain'", pattern);
+        }
+        Ok(Self(normalized))
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self.0.strip_prefix("*.") {
+            Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+            None => host == self.0,
+        }
+    }
+}
+
+/// Why a URL or address was refused.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PolicyError {
+    Scheme(String),
+    DeniedHost(String),
+    HostNotAllowed(String),
+    /// A literal or resolved address outside the public internet.
+    PrivateAddress { host: String, ip: IpAddr },
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Scheme(scheme) => write!(f, "scheme '{}' is not allowed", scheme),
+            PolicyError::DeniedHost(host) => write!(f, "host '{}' is denied", host),
+            PolicyError::HostNotAllowed(host) => write!(f, "host '{}' is not in the allowed hosts", host),
+            PolicyError::PrivateAddress { host, ip } if host == &ip.to_string() => {
+                write!(f, "{} is a private or reserved address", ip)
+            },
+            PolicyError::PrivateAddress { host, ip } => {
+                write!(f, "host '{}' resolves to private or reserved address {}", host, ip)
+            },
+        }
+    }
+}
+
+impl StdError for PolicyError {}
+
+impl UrlPolicy {
+    /// Checks everything known before resolving the host: the scheme, the
+    /// host lists and, for IP addresses and `localhost`, the address.
+    pub fn check_url(&self, url: &Url) -> Result<(), PolicyError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(PolicyError::Scheme(url.scheme().to_string()));
+        }
+        let host = match url.host() {
+            Some(Host::Domain(domain)) => domain.trim_end_matches('.').to_ascii_lowercase(),
+            Some(Host::Ipv4(ip)) => ip.to_string(),
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            None => return Err(PolicyError::HostNotAllowed(String::new())),
+        };
+        if self.deny_hosts.iter().any(|pattern| pattern.matches(&host)) {
+            return Err(PolicyError::DeniedHost(host));
+        }
+        if !self.allow_hosts.is_empty() && !self.allow_hosts.iter().any(|pattern| pattern.matches(&host)) {
+            return Err(PolicyError::HostNotAllowed(host));
+        }
+        if self.block_private {
+            let ip = match url.host() {
+                Some(Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+                Some(Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+                _ if host == "localhost" || host.ends_with(".localhost") => Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                _ => None,
+            };
+            if let Some(ip) = ip.filter(|ip| !is_public(*ip)) {
+                return Err(PolicyError::PrivateAddress { host, ip });
+            }
+        }
+        Ok(())
+    }
+
+    /// A resolver that drops non-public addresses, or `None` when private
+    /// addresses are allowed.
+    pub fn resolver(&self) -> Option<Arc<PublicResolver>> {
+        self.block_private.then(|| Arc::new(PublicResolver))
+    }
+}
+
+/// Resolves host names with the system resolver, keeping only public
+/// addresses. The connection is made to the addresses returned here, which
+/// is what defeats DNS rebinding.
+#[derive(Debug)]
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let public: Vec<SocketAddr> = addrs.iter().copied().filter(|addr| is_public(addr.ip())).collect();
+            match (public.is_empty(), addrs.first()) {
+                (true, Some(addr)) => Err(PolicyError::PrivateAddress { host, ip: addr.ip() }.into()),
+                _ => Ok(Box::new(public.into_iter()) as Addrs),
+            }
+        })
+    }
+}
+
+/// Whether `ip` is a globally routable unicast address. Addresses embedding
+/// an IPv4 address (mapped, NAT64 and 6to4) are judged by that address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_v4(v4);
+            }
+            let segments = ip.segments();
+            let embedded_v4 = |high: u16, low: u16| Ipv4Addr::from(((high as u32) << 16) | low as u32);
+            match segments {
+                // NAT64 (64:ff9b::/96)
+                [0x64, 0xff9b, 0, 0, 0, 0, high, low] => is_public_v4(embedded_v4(high, low)),
+                // 6to4 (2002::/16)
+                [0x2002, high, low, ..] => is_public_v4(embedded_v4(high, low)),
+                // Unspecified, loopback and the deprecated IPv4-compatible range (::/96)
+                [0, 0, 0, 0, 0, 0, _, _] => false,
+                // Discard-only (100::/64)
+                [0x100, 0, 0, 0, ..] => false,
+                // Documentation (2001:db8::/32)
+                [0x2001, 0xdb8, ..] => false,
+                [first, ..] => {
+                    !((first & 0xfe00) == 0xfc00        // Unique local (fc00::/7)
+                        || (first & 0xffc0) == 0xfe80   // Link-local (fe80::/10)
+                        || (first & 0xffc0) == 0xfec0   // Site-local (fec0::/10)
+                        || (first & 0xff00) == 0xff00)  // Multicast
+                },
+            }
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(a == 0                                    // "This network" (0.0.0.0/8)
+        || a == 10                              // Private (10.0.0.0/8)
+        || a == 100 && (64..128).contains(&b)   // Shared address space (100.64.0.0/10)
+        || a == 127                             // Loopback
+        || a == 169 && b == 254                 // Link-local, including cloud metadata
+        || a == 172 && (16..32).contains(&b)    // Private (172.16.0.0/12)
+        || a == 192 && b == 0 && c == 0         // IETF protocol assignments
+        || a == 192 && b == 0 && c == 2         // Documentation (TEST-NET-1)
+        || a == 192 && b == 168                 // Private (192.168.0.0/16)
+        || a == 198 && (18..20).contains(&b)    // Benchmarking (198.18.0.0/15)
+        || a == 198 && b == 51 && c == 100      // Documentation (TEST-NET-2)
+        || a == 203 && b == 0 && c == 113       // Documentation (TEST-NET-3)
+        || a >= 224)                            // Multicast, reserved and broadcast
+}
+
+#[cfg(test)]
+#[path = "policy_test.rs"]
+mod tests;
@@ -0,0 +1,117 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::Resolve;
+use url::Url;
+
+use super::{is_public, HostPattern, PolicyError, UrlPolicy};
+
+fn check(policy: &UrlPolicy, url: &str) -> Result<(), PolicyError> {
+    policy.check_url(&Url::parse(url).unwrap())
+}
+
+fn patterns(patterns: &[&str]) -> Vec<HostPattern> {
+    patterns.iter().map(|pattern| HostPattern::parse(pattern).unwrap()).collect()
+}
+
+#[test]
+fn test_is_public() {
+    let public = |ip: &str| is_public(ip.parse::<IpAddr>().unwrap());
+    for ip in ["93.184.216.34", "8.8.8.8", "100.128.0.1", "172.32.0.1", "2606:2800:220:1::1", "::ffff:93.184.216.34"] {
+        assert!(public(ip), "{} should be public", ip);
+    }
+    for ip in [
+        "0.0.0.0", "10.1.2.3", "100.64.0.1", "127.0.0.1", "127.8.8.8", "169.254.169.254", "172.16.0.1",
+        "172.31.255.255", "192.168.1.1", "192.0.2.1", "198.18.0.1", "224.0.0.1", "255.255.255.255",
+        "::", "::1", "::ffff:127.0.0.1", "::ffff:10.0.0.1", "64:ff9b::a9fe:a9fe", "2002:c0a8:0101::1",
+        "fc00::1", "fd12:3456::1", "fe80::1", "ff02::1", "2001:db8::1",
+    ] {
+        assert!(!public(ip), "{} should not be public", ip);
+    }
+}
+
+#[test]
+fn test_check_url_schemes_and_private_addresses() {
+    let policy = UrlPolicy { block_private: true, ..UrlPolicy::default() };
+    assert_eq!(check(&policy, "https://example.com/page"), Ok(()));
+    assert_eq!(check(&policy, "http://93.184.216.34/"), Ok(()));
+    assert_eq!(check(&policy, "file:///etc/passwd"), Err(PolicyError::Scheme("file".to_string())));
+    assert_eq!(check(&policy, "ftp://example.com/"), Err(PolicyError::Scheme("ftp".to_string())));
+    for url in [
+        "http://127.0.0.1:3000/",
+        "http://169.254.169.254/latest/meta-data/",
+        "http://[::1]/",
+        "http://[::ffff:7f00:1]/",
+        "http://10.0.0.1/",
+        "http://localhost/",
+        "http://api.localhost./",
+        // Numeric forms the URL parser normalizes to 127.0.0.1
+        "http://2130706433/",
+        "http://0x7f.1/",
+    ] {
+        assert!(matches!(check(&policy, url), Err(PolicyError::PrivateAddress { .. })), "{} should be blocked", url);
+    }
+
+    // Without block_private only the scheme is restricted
+    let policy = UrlPolicy::default();
+    assert_eq!(check(&policy, "http://127.0.0.1:3000/"), Ok(()));
+    assert!(check(&policy, "file:///etc/passwd").is_err());
+}
+
+#[test]
+fn test_check_url_host_lists() {
+    let policy = UrlPolicy {
+        allow_hosts: patterns(&["example.com", "*.example.org"]),
+        deny_hosts: patterns(&["secret.example.org"]),
+        ..UrlPolicy::default()
+    };
+    assert_eq!(check(&policy, "https://example.com/"), Ok(()));
+    assert_eq!(check(&policy, "https://EXAMPLE.com./"), Ok(()));
+    assert_eq!(check(&policy, "https://docs.example.org/"), Ok(()));
+    assert_eq!(check(&policy, "https://a.b.example.org/"), Ok(()));
+    assert_eq!(check(&policy, "https://www.example.com/"), Err(PolicyError::HostNotAllowed("www.example.com".to_string())));
+    assert_eq!(check(&policy, "https://example.org/"), Err(PolicyError::HostNotAllowed("example.org".to_string())));
+    assert_eq!(check(&policy, "https://badexample.org/"), Err(PolicyError::HostNotAllowed("badexample.org".to_string())));
+    assert_eq!(
+        check(&policy, "https://secret.example.org/"),
+        Err(PolicyError::DeniedHost("secret.example.org".to_string()))
+    );
+
+    let policy = UrlPolicy { deny_hosts: patterns(&["[::1]", "10.0.0.1"]), ..UrlPolicy::default() };
+    assert!(matches!(check(&policy, "http://[0:0::1]/"), Err(PolicyError::DeniedHost(_))));
+    assert!(matches!(check(&policy, "http://10.0.0.1/"), Err(PolicyError::DeniedHost(_))));
+    assert_eq!(check(&policy, "http://example.com/"), Ok(()));
+}
+
+#[test]
+fn test_host_pattern_parse() {
+    assert_eq!(HostPattern::parse(" Example.COM. ").unwrap(), HostPattern::parse("example.com").unwrap());
+    assert!(HostPattern::parse("*.example.com").is_ok());
+    assert!(HostPattern::parse("::1").is_ok());
+    for invalid in ["", "*", "*.", "example.*", "http://example.com", "example.com/path", "a..b"] {
+        assert!(HostPattern::parse(invalid).is_err(), "'{}' should be invalid", invalid);
+    }
+}
+
+#[test]
+fn test_policy_error_display() {
+    let ip: IpAddr = "10.0.0.1".parse().unwrap();
+    let literal = PolicyError::PrivateAddress { host: "10.0.0.1".to_string(), ip };
+    assert_eq!(literal.to_string(), "10.0.0.1 is a private or reserved address");
+    let resolved = PolicyError::PrivateAddress { host: "intranet".to_string(), ip };
+    assert_eq!(resolved.to_string(), "host 'intranet' resolves to private or reserved address 10.0.0.1");
+}
+
+#[tokio::test]
+async fn test_resolver_drops_private_addresses() {
+    let policy = UrlPolicy { block_private: true, ..UrlPolicy::default() };
+    assert!(UrlPolicy::default().resolver().is_none());
+
+    let resolver = policy.resolver().unwrap();
+    let error = match resolver.resolve(Name::from_str("localhost").unwrap()).await {
+        Ok(addrs) => panic!("resolved to {:?}", addrs.collect::<Vec<_>>()),
+        Err(e) => e,
+    };
+    assert!(matches!(error.downcast_ref::<PolicyError>(), Some(PolicyError::PrivateAddress { .. })));
+}
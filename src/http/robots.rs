@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+/// The rules of a `robots.txt` file that apply to one user agent, following
+/// RFC 9309: the most specific (longest) matching rule wins, `allow` wins
+/// ties, and patterns may use `*` wildcards and a `$` end anchor.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Everything is allowed, as when a site has no `robots.txt`.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Nothing is allowed, as when `robots.txt` is unreachable because of a
+    /// server error.
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule { allow: false, pattern: "/".to_string() }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parses `robots.txt` and keeps the groups for `user_agent`, whose
+    /// product token (`url2md` in `url2md/0.1`) is matched against the
+    /// `User-agent` lines, falling back to the `*` groups.
+    pub fn parse(contents: &str, user_agent: &str) -> Self {
+        let token = product_token(user_agent);
+        let mut groups: Vec<Group> = Vec::new();
+        let mut in_agent_lines = false;
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agent_lines {
+                        groups.push(Group::default());
+                    }
+                    in_agent_lines = true;
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_ascii_lowercase());
+                    }
+                },
+                key @ ("allow" | "disallow") => {
+                    in_agent_lines = false;
+                    // An empty `Disallow:` allows everything, like no rule
+                    if let (Some(group), false) = (groups.last_mut(), value.is_empty()) {
+                        group.rules.push(Rule { allow: key == "allow", pattern: normalize(value) });
+                    }
+                },
+                "crawl-delay" => {
+                    in_agent_lines = false;
+                    if let Some(group) = groups.last_mut() {
+                        group.crawl_delay = value.parse::<f64>()
+                            .ok()
+                            .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        let named: Vec<&Group> = groups.iter().filter(|group| group.agents.contains(&token)).collect();
+        let matching: Vec<&Group> = if named.is_empty() {
+            groups.iter().filter(|group| group.agents.iter().any(|agent| agent == "*")).collect()
+        } else {
+            named
+        };
+        Self {
+            rules: matching.iter().flat_map(|group| group.rules.iter().cloned()).collect(),
+            crawl_delay: matching.iter().find_map(|group| group.crawl_delay),
+        }
+    }
+
+    /// Whether `path` (the URL path with its query) may be fetched.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        let path = normalize(path);
+        self.rules.iter()
+            .filter(|rule| pattern_matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+/// The `Sitemap:` URLs listed in `robots.txt`. They apply to every user
+/// agent, wherever they appear in the file.
+pub fn sitemaps(contents: &str) -> Vec<String> {
+    contents.lines()
+        .filter_map(|line| line.split('#').next()?.split_once(':'))
+        .filter(|(key, _)| key.trim().eq_ignore_ascii_case("sitemap"))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// The name a crawler identifies itself by: `url2md` for `url2md/0.1.0`.
+fn product_token(user_agent: &str) -> String {
+    user_agent.split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Spells a path or pattern one way, so that equivalent ones match (RFC
+/// 9309, section 2.2.2): escaped unreserved characters such as `%7E` are
+/// decoded, other escapes get uppercase hex digits, and non-ASCII
+/// characters are percent-encoded.
+fn normalize(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut normalized = String::with_capacity(path.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                    normalized.push(char::from(byte));
+                } else {
+                    normalized.push_str(&format!("%{:02X}", byte));
+                }
+                i += 3;
+            },
+            (byte, _) => {
+                if byte.is_ascii() {
+                    normalized.push(char::from(byte));
+                } else {
+                    normalized.push_str(&format!("%{:02X}", byte));
+                }
+                i += 1;
+            },
+        }
+    }
+    normalized
+}
+
+/// Matches a robots.txt path pattern: a prefix match where `*` matches any
+/// run of characters and a trailing `$` anchors the end.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = parts.next().and_then(|first| path.strip_prefix(first)) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return !anchored || rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    if anchored {
+        rest.ends_with(last)
+    } else {
+        rest.contains(last)
+    }
+}
+
+#[cfg(test)]
+#[path = "robots_test.rs"]
+mod tests;
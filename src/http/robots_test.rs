@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use super::Robots;
+
+const ROBOTS: &str = "\
+# Example robots.txt
+User-agent: googlebot
+Disallow: /
+
+User-agent: url2md
+User-agent: otherbot
+Disallow: /private
+Allow: /private/docs   # public docs
+Disallow: /*.pdf$
+Crawl-delay: 2
+
+User-agent: *
+Disallow: /search
+";
+
+#[test]
+fn test_parse_named_group() {
+    let robots = Robots::parse(ROBOTS, "url2md/0.1.0");
+    assert!(robots.is_allowed("/"));
+    assert!(robots.is_allowed("/search"));
+    assert!(!robots.is_allowed("/private"));
+    assert!(!robots.is_allowed("/private/secret"));
+    assert!(robots.is_allowed("/private/docs/intro"));
+    assert_eq!(robots.crawl_delay(), Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn test_parse_falls_back_to_wildcard_group() {
+    let robots = Robots::parse(ROBOTS, "Mozilla/5.0 (X11; Linux x86_64)");
+    assert!(robots.is_allowed("/private"));
+    assert!(!robots.is_allowed("/search?q=rust"));
+    assert_eq!(robots.crawl_delay(), None);
+}
+
+#[test]
+fn test_user_agent_is_case_insensitive() {
+    let robots = Robots::parse("User-Agent: URL2MD\nDisallow: /x\n", "url2md");
+    assert!(!robots.is_allowed("/x"));
+}
+
+#[test]
+fn test_patterns() {
+    let robots = Robots::parse(ROBOTS, "url2md");
+    assert!(!robots.is_allowed("/files/report.pdf"));
+    assert!(robots.is_allowed("/files/report.pdf?download=1"));
+    assert!(robots.is_allowed("/files/report.pdfx"));
+
+    let robots = Robots::parse("User-agent: *\nDisallow: /*/edit\n", "url2md");
+    assert!(!robots.is_allowed("/wiki/Page/edit"));
+    assert!(robots.is_allowed("/edit"));
+}
+
+#[test]
+fn test_percent_encoding_is_normalized() {
+    let robots = Robots::parse("User-agent: *\nDisallow: /~joe/\nDisallow: /%e2%82%ac\nDisallow: /a%2fb\n", "url2md");
+    assert!(!robots.is_allowed("/%7Ejoe/index.html"));
+    assert!(!robots.is_allowed("/%7ejoe/"));
+    assert!(!robots.is_allowed("/€uro"));
+    assert!(!robots.is_allowed("/%E2%82%ACuro"));
+    // An escaped slash is not a path separator
+    assert!(!robots.is_allowed("/a%2Fb"));
+    assert!(robots.is_allowed("/a/b"));
+
+    let robots = Robots::parse("User-agent: *\nDisallow: /%7Efoo\nDisallow: /café\n", "url2md");
+    assert!(!robots.is_allowed("/~foo"));
+    assert!(!robots.is_allowed("/caf%C3%A9"));
+}
+
+#[test]
+fn test_allow_wins_ties() {
+    let robots = Robots::parse("User-agent: *\nDisallow: /page\nAllow: /page\n", "url2md");
+    assert!(robots.is_allowed("/page"));
+}
+
+#[test]
+fn test_empty_disallow_allows_everything() {
+    let robots = Robots::parse("User-agent: *\nDisallow:\n", "url2md");
+    assert_eq!(robots, Robots::allow_all());
+}
+
+#[test]
+fn test_disallow_all() {
+    let robots = Robots::disallow_all();
+    assert!(!robots.is_allowed("/"));
+    assert!(!robots.is_allowed("/page"));
+    assert!(robots.is_allowed("/robots.txt"));
+}
+
+#[test]
+fn test_sitemaps() {
+    let contents = "Sitemap: https://example.com/sitemap.xml\nUser-agent: *\nDisallow: /x\nsitemap:https://example.com/news.xml.gz # news\n";
+    assert_eq!(super::sitemaps(contents), ["https://example.com/sitemap.xml", "https://example.com/news.xml.gz"]);
+}
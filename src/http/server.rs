@@ -1,41 +1,401 @@
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
 use axum::{
-    extract::Path,
-    routing::{get},
+    extract::{
+        rejection::{JsonRejection, QueryRejection}, ConnectInfo, DefaultBodyLimit, FromRequest, MatchedPath, Multipart, Path, Query, RawQuery,
+        Request, State,
+    },
+    middleware::{self, Next},
+    routing::{get, post},
+    Json,
     Router,
-    response::Response,
-    http::{StatusCode, header},
-    body::Body,
+    response::{IntoResponse, Response},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    body::{Body, Bytes},
 };
-use serde::Serialize;
+use anyhow::Context;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use tracing::{info, warn};
+use serde::{Deserialize, Serialize};
 use url::Url;
 use crate::converter::markdown_converter::MarkdownConverter;
-use super::client::fetch_html;
+use crate::converter::metadata::PageMetadata;
+use crate::converter::options::ConverterArgs;
+use super::access::{Access, AccessConfig, Denied};
+use super::cache::{fetch_converted, Cache, CacheStatus};
+use super::charset;
+use super::client::{FetchError, FetchedHtml, Fetcher};
+use super::config::{ServeConfig, DEFAULT_MAX_BODY_SIZE};
+use super::metrics::{self, Metrics};
 
 #[derive(Serialize)]
 pub struct ErrorResponse {
     error: String,
 }
 
-pub fn create_router() -> Router {
+/// An error answered with a JSON [`ErrorResponse`].
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(ErrorResponse { error: self.1 })).into_response()
+    }
+}
+
+/// Body of `POST /api/convert`. Either `url` is fetched, or `html` is
+/// converted as is, with `url` (if given) resolving its relative links.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ConvertRequest {
+    url: Option<String>,
+    html: Option<String>,
+    #[serde(default)]
+    options: ConverterArgs,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConvertResponse {
+    markdown: String,
+    title: Option<String>,
+    metadata: PageMetadata,
+    links: Vec<String>,
+    images: Vec<String>,
+    /// The page URL after redirects, or the URL given with `html`.
+    url: Option<String>,
+    /// Upstream status and Content-Type; absent when `html` was posted.
+    status: Option<u16>,
+    content_type: Option<String>,
+    /// Always present in responses, but left out of cached results.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    timing: Option<Timing>,
+}
+
+/// Query parameters of `POST /api/convert/html` besides the converter
+/// options.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct HtmlParams {
+    /// Resolves the relative links of the posted HTML.
+    base_url: Option<String>,
+}
+
+/// The `format` query parameter of `GET /{url}`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct FormatParams {
+    format: Option<OutputFormat>,
+}
+
+/// What `GET /{url}` answers with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Markdown,
+    /// The text of the extracted content.
+    Text,
+    /// The structured result of `POST /api/convert`.
+    Json,
+    /// The extracted content as cleaned HTML.
+    Html,
+}
+
+impl OutputFormat {
+    /// In order of preference when the client likes several equally.
+    const ALL: [OutputFormat; 4] = [OutputFormat::Markdown, OutputFormat::Text, OutputFormat::Json, OutputFormat::Html];
+
+    fn media_type(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "text/markdown",
+            OutputFormat::Text => "text/plain",
+            OutputFormat::Json => "application/json",
+            OutputFormat::Html => "text/html",
+        }
+    }
+
+    /// The format an `Accept` header ranks highest, Markdown when there is
+    /// no header, or `None` when no format is acceptable. Each format takes
+    /// the quality of the most specific media range matching it.
+    fn negotiate(accept: Option<&str>) -> Option<Self> {
+        let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+            return Some(OutputFormat::Markdown);
+        };
+        let ranges: Vec<(String, f32)> = accept.split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let media_range = parts.next()?.trim().to_ascii_lowercase();
+                let quality = parts
+                    .filter_map(|param| param.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                    .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok())?;
+                Some((media_range, quality))
+            })
+            .collect();
+
+        let mut best: Option<(OutputFormat, f32)> = None;
+        for format in OutputFormat::ALL {
+            let media_type = format.media_type();
+            let main_type = media_type.split('/').next().unwrap_or("");
+            let quality = ranges.iter()
+                .filter_map(|(range, quality)| {
+                    let specificity = if range == media_type {
+                        3
+                    } else if range.strip_suffix("/*") == Some(main_type) {
+                        2
+                    } else if range == "*/*" {
+                        1
+                    } else {
+                        return None;
+                    };
+                    Some((specificity, *quality))
+                })
+                .max_by_key(|(specificity, _)| *specificity)
+                .map_or(0.0, |(_, quality)| quality);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+        best.map(|(format, _)| format)
+    }
+}
+
+/// Milliseconds spent fetching, converting and in total.
+#[derive(Serialize, Debug)]
+pub struct Timing {
+    fetch_ms: Option<f64>,
+    convert_ms: f64,
+    total_ms: f64,
+}
+
+/// Shared by every request handler.
+#[derive(Clone)]
+pub struct AppState {
+    fetcher: Arc<Fetcher>,
+    cache: Option<Arc<Cache>>,
+    access: Arc<Access>,
+    metrics: Arc<Metrics>,
+    /// Cleared on shutdown, so `/readyz` tells load balancers to stop
+    /// sending requests.
+    ready: Arc<AtomicBool>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new(Fetcher::default())
+    }
+}
+
+impl AppState {
+    /// Fetches with `fetcher`, recording its requests in the metrics.
+    pub fn new(fetcher: Fetcher) -> Self {
+        let metrics = Arc::new(Metrics::default());
+        Self {
+            fetcher: Arc::new(fetcher.with_metrics(Arc::clone(&metrics))),
+            cache: None,
+            access: Arc::default(),
+            metrics,
+            ready: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Reuses converted pages for `GET /{url}` and `POST /api/convert`,
+    /// answering with an `X-Cache: HIT` or `MISS` header.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Requires API keys, limits request rates and restricts CORS origins
+    /// as `config` says. Without it, anyone may use the server.
+    pub fn with_access(mut self, config: AccessConfig) -> Self {
+        self.access = Arc::new(Access::new(config));
+        self
+    }
+
+    /// Whether `/readyz` reports the server as ready to take requests.
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+}
+
+pub fn create_router(state: AppState) -> Router {
+    // CORS goes outside access control, so preflight requests (which carry
+    // no credentials) are answered and errors are readable by the page
+    let cors = state.access.cors_layer();
     Router::new()
+        .route("/api/convert", post(api_convert))
+        .route(
+            "/api/convert/html",
+            post(convert_html).layer(DefaultBodyLimit::max(DEFAULT_MAX_BODY_SIZE)),
+        )
         .route("/*url", get(convert_url))
+        .layer(middleware::from_fn_with_state(state.clone(), access_control))
+        // Added after access control, as probes and scrapers carry no API
+        // key. Fixed paths take precedence over the catch-all `/*url`.
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(render_metrics))
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(state.clone(), count_request))
+        .with_state(state)
+}
+
+/// Liveness: the process is up and answering.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness: the server takes new requests, until it starts shutting down.
+async fn readyz(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    if state.ready.load(Ordering::Relaxed) {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "shutting down")
+    }
+}
+
+async fn render_metrics(State(state): State<AppState>) -> Response {
+    ([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], state.metrics.render()).into_response()
+}
+
+/// Counts every response by route and status.
+async fn count_request(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let route = request.extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let response = next.run(request).await;
+    state.metrics.count_request(&route, response.status().as_u16());
+    response
+}
+
+/// Serves `app` on `listener`, over HTTPS when `config` has TLS files,
+/// until `shutdown` completes. New connections are then refused, and
+/// requests in flight get `config.shutdown_timeout` to finish before their
+/// connections are closed.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    config: &ServeConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let tls = match &config.tls {
+        Some(tls) => Some(
+            RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                .await
+                .with_context(|| format!("failed to load TLS certificate {} and key {}", tls.cert.display(), tls.key.display()))?,
+        ),
+        None => None,
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    info!("API server listening on {}://{}", scheme, listener.local_addr()?);
+
+    let handle = Handle::new();
+    let timeout = config.shutdown_timeout;
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            info!(
+                "Shutting down, waiting up to {:.0?} for {} open connections",
+                timeout,
+                handle.connection_count(),
+            );
+            handle.graceful_shutdown(Some(timeout));
+        }
+    });
+
+    // The client address is needed for per-IP rate limits
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(tls) => axum_server::from_tcp_rustls(listener, tls).handle(handle.clone()).serve(service).await?,
+        None => axum_server::from_tcp(listener).handle(handle.clone()).serve(service).await?,
+    }
+    if handle.connection_count() > 0 {
+        warn!("Closed {} connections still open after {:.0?}", handle.connection_count(), timeout);
+    }
+    Ok(())
+}
+
+/// Completes on SIGTERM, or Ctrl-C (SIGINT).
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            },
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            },
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Turns away requests without a valid API key, or over their rate limit.
+/// The client address is known when the server is run with
+/// [`ConnectInfo`]; otherwise all clients share one IP limit.
+async fn access_control(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+    match state.access.check(request.headers(), peer) {
+        Ok(()) => next.run(request).await,
+        Err(Denied::Unauthorized) => {
+            let mut response = ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid API key".to_string()).into_response();
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        },
+        Err(Denied::RateLimited { retry_after }) => {
+            // Whole seconds, rounded up so retrying right then succeeds
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            let mut response = ApiError(StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded".to_string()).into_response();
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs.max(1)));
+            response
+        },
+    }
 }
 
 async fn convert_url(
+    State(state): State<AppState>,
     Path(url): Path<String>,
-) -> Result<Response<Body>, (StatusCode, String)> {
+    RawQuery(query): RawQuery,
+    format_params: Result<Query<FormatParams>, QueryRejection>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, ApiError> {
+    let (params, Query(format_params)) = (converter_args(query)?, format_params.map_err(query_error)?);
+    let accept = headers.get(header::ACCEPT).and_then(|value| value.to_str().ok());
+    let Some(format) = format_params.format.or_else(|| OutputFormat::negotiate(accept)) else {
+        return Err(ApiError(
+            StatusCode::NOT_ACCEPTABLE,
+            "Available formats: text/markdown, text/plain, application/json, text/html".to_string(),
+        ));
+    };
+
     // Get the full URL by removing the leading slash and handling protocol
-    let url_str = if url.starts_with('/') {
-        url[1..].to_string()
-    } else {
-        url
+    let url_str = match url.strip_prefix('/') {
+        Some(stripped) => stripped.to_string(),
+        None => url,
     };
 
     // Handle unencoded URLs by checking for http:// or https://
     let parsed_url = if url_str.contains("://") {
         match Url::parse(&url_str) {
             Ok(url) => url,
-            Err(e) => return Err((
+            Err(e) => return Err(ApiError(
                 StatusCode::BAD_REQUEST,
                 format!("Invalid URL: {}", e),
             )),
@@ -45,41 +405,307 @@ async fn convert_url(
         match urlencoding::decode(&url_str) {
             Ok(decoded) => match Url::parse(&decoded) {
                 Ok(url) => url,
-                Err(e) => return Err((
+                Err(e) => return Err(ApiError(
                     StatusCode::BAD_REQUEST,
                     format!("Invalid URL after decoding: {}", e),
                 )),
             },
-            Err(e) => return Err((
+            Err(e) => return Err(ApiError(
                 StatusCode::BAD_REQUEST,
                 format!("Invalid URL encoding: {}", e),
             )),
         }
     };
 
-    // Fetch HTML content
-    let html = match fetch_html(&parsed_url).await {
-        Ok(content) => content,
-        Err(e) => return Err((
+    let options = match params.to_options() {
+        Ok(options) => options,
+        Err(e) => return Err(ApiError(
             StatusCode::BAD_REQUEST,
-            format!("Failed to fetch URL: {}", e),
+            format!("Invalid converter options: {}", e),
         )),
     };
 
-    // Convert to markdown
-    let converter = MarkdownConverter::new();
-    match converter.convert(&html) {
-        Ok(markdown) => {
-            let response = Response::builder()
-                .header(header::CONTENT_TYPE, "text/markdown; charset=utf-8")
-                .body(Body::from(markdown))
-                .unwrap();
-            Ok(response)
+    // Fetch and convert to the requested format, unless cached
+    let started = Instant::now();
+    let converted = OnceLock::new();
+    let variant = format!("{:?} {:?}", format, options);
+    let convert = |page: &FetchedHtml| state.metrics.time_conversion(|| {
+        let converter = MarkdownConverter::with_options(options.clone())
+            .with_source_url(page.url.clone());
+        match format {
+            OutputFormat::Markdown => converter.convert(&page.html),
+            OutputFormat::Text => Ok(converter.convert_to_text(&page.html)),
+            OutputFormat::Html => Ok(converter.convert_to_html(&page.html)),
+            OutputFormat::Json => {
+                let fetch_time = started.elapsed();
+                let convert_started = Instant::now();
+                let json = page_response(converter, page)?;
+                let _ = converted.set((fetch_time, convert_started.elapsed()));
+                Ok(json)
+            },
+        }
+    });
+    let (page, cache_status) = fetch_converted(state.cache.as_deref(), &state.fetcher, &parsed_url, &variant, convert)
+        .await
+        .map_err(conversion_error)?;
+    state.metrics.count_cache(cache_status);
+
+    let mut response = match format {
+        OutputFormat::Json => timed_response(&page.content, started, converted.get())
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        _ => text_response(format, page.content),
+    };
+    // Without `format`, the response depends on the Accept header
+    response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+    add_cache_header(&mut response, cache_status);
+    Ok(response)
+}
+
+/// The JSON result for a fetched page, without the timing, which belongs
+/// to each request rather than to the cached result.
+fn page_response(converter: MarkdownConverter, page: &FetchedHtml) -> anyhow::Result<String> {
+    let converted = converter.convert_page(&page.html)?;
+    let response = ConvertResponse {
+        markdown: converted.markdown,
+        title: converted.metadata.title.clone(),
+        metadata: converted.metadata,
+        links: converted.links,
+        images: converted.images,
+        url: Some(page.url.to_string()),
+        status: Some(page.status),
+        content_type: page.content_type.clone(),
+        timing: None,
+    };
+    serde_json::to_string(&response).context("failed to serialize the result")
+}
+
+/// Answers with a result of [`page_response`] and the timing of this
+/// request. `converted` holds the fetch and conversion times, and is empty
+/// when the result came from the cache.
+fn timed_response(json: &str, started: Instant, converted: Option<&(Duration, Duration)>) -> anyhow::Result<Response> {
+    let mut response: ConvertResponse = serde_json::from_str(json).context("invalid cached result")?;
+    response.timing = Some(Timing {
+        fetch_ms: converted.map(|(fetch_time, _)| millis(*fetch_time)),
+        convert_ms: converted.map_or(0.0, |(_, convert_time)| millis(*convert_time)),
+        total_ms: millis(started.elapsed()),
+    });
+    Ok(Json(response).into_response())
+}
+
+fn add_cache_header(response: &mut Response, cache_status: Option<CacheStatus>) {
+    if let Some(cache_status) = cache_status {
+        response.headers_mut().insert("x-cache", HeaderValue::from_static(cache_status.as_str()));
+    }
+}
+
+fn markdown_response(markdown: String) -> Response {
+    text_response(OutputFormat::Markdown, markdown)
+}
+
+fn text_response(format: OutputFormat, body: String) -> Response {
+    let content_type = match format {
+        // JSON is always UTF-8 and takes no charset parameter
+        OutputFormat::Json => format.media_type().to_string(),
+        _ => format!("{}; charset=utf-8", format.media_type()),
+    };
+    let mut response = Response::builder().header(header::CONTENT_TYPE, content_type);
+    if format == OutputFormat::Html {
+        // Cleaned pages are third-party content served from this origin:
+        // browsers must not run anything in them or sniff another type.
+        response = response
+            .header(header::CONTENT_SECURITY_POLICY, "sandbox")
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    }
+    response.body(Body::from(body)).unwrap()
+}
+
+/// Converts HTML posted as a `text/html` body, or as the `file` (or `html`)
+/// field of a `multipart/form-data` upload. Nothing is fetched, so the
+/// content never leaves the server.
+async fn convert_html(
+    State(state): State<AppState>,
+    RawQuery(query): RawQuery,
+    html_params: Result<Query<HtmlParams>, QueryRejection>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let (params, Query(html_params)) = (converter_args(query)?, html_params.map_err(query_error)?);
+    let options = params.to_options()
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid converter options: {}", e)))?;
+    let content_type = request.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let essence = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+
+    let (bytes, content_type, base_url) = match essence.as_str() {
+        "multipart/form-data" => {
+            let multipart = Multipart::from_request(request, &()).await
+                .map_err(|e| ApiError(e.status(), e.body_text()))?;
+            let upload = read_upload(multipart).await?;
+            (upload.html, upload.content_type, html_params.base_url.or(upload.base_url))
         },
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            e.to_string(),
+        "text/html" | "application/xhtml+xml" => {
+            let bytes = Bytes::from_request(request, &()).await
+                .map_err(|e| ApiError(e.status(), e.body_text()))?;
+            (bytes, Some(content_type), html_params.base_url)
+        },
+        _ => return Err(ApiError(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Expected a text/html body or a multipart/form-data upload".to_string(),
+        )),
+    };
+    let base_url = base_url.as_deref()
+        .map(Url::parse)
+        .transpose()
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid base URL: {}", e)))?;
+
+    let html = charset::decode_html(&bytes, content_type.as_deref(), None, base_url.as_ref());
+    let mut converter = MarkdownConverter::with_options(options);
+    if let Some(base_url) = base_url {
+        converter = converter.with_source_url(base_url);
+    }
+    let markdown = state.metrics.time_conversion(|| converter.convert(&html))
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(markdown_response(markdown))
+}
+
+/// The parts of a multipart HTML upload.
+struct Upload {
+    html: Bytes,
+    content_type: Option<String>,
+    base_url: Option<String>,
+}
+
+async fn read_upload(mut multipart: Multipart) -> Result<Upload, ApiError> {
+    let mut html = None;
+    let mut base_url = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| ApiError(e.status(), e.body_text()))? {
+        match field.name() {
+            Some("file" | "html") => {
+                let content_type = field.content_type().map(str::to_string);
+                let bytes = field.bytes().await.map_err(|e| ApiError(e.status(), e.body_text()))?;
+                html = Some((bytes, content_type));
+            },
+            Some("base_url") => {
+                base_url = Some(field.text().await.map_err(|e| ApiError(e.status(), e.body_text()))?);
+            },
+            _ => {},
+        }
+    }
+    let Some((html, content_type)) = html else {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "Missing 'file' field with the HTML".to_string()));
+    };
+    Ok(Upload { html, content_type, base_url })
+}
+
+async fn api_convert(
+    State(state): State<AppState>,
+    request: Result<Json<ConvertRequest>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let started = Instant::now();
+    let Json(request) = request.map_err(|e| ApiError(e.status(), e.body_text()))?;
+    let options = request.options.to_options()
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid converter options: {}", e)))?;
+    let url = match request.url.as_deref().map(Url::parse) {
+        Some(Ok(url)) if matches!(url.scheme(), "http" | "https") => Some(url),
+        Some(Ok(url)) => return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported URL scheme '{}'", url.scheme()),
         )),
+        Some(Err(e)) => return Err(ApiError(StatusCode::BAD_REQUEST, format!("Invalid URL: {}", e))),
+        None => None,
+    };
+
+    let (html, source_url) = match (request.html, url) {
+        (Some(html), url) => (html, url),
+        (None, Some(url)) => {
+            let variant = format!("api {:?}", options);
+            let converted = OnceLock::new();
+            let convert = |page: &FetchedHtml| state.metrics.time_conversion(|| {
+                let fetch_time = started.elapsed();
+                let convert_started = Instant::now();
+                let converter = MarkdownConverter::with_options(options.clone())
+                    .with_source_url(page.url.clone());
+                let json = page_response(converter, page)?;
+                let _ = converted.set((fetch_time, convert_started.elapsed()));
+                Ok(json)
+            });
+            let (page, cache_status) = fetch_converted(state.cache.as_deref(), &state.fetcher, &url, &variant, convert)
+                .await
+                .map_err(conversion_error)?;
+            state.metrics.count_cache(cache_status);
+            let mut response = timed_response(&page.content, started, converted.get())
+                .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            add_cache_header(&mut response, cache_status);
+            return Ok(response);
+        },
+        (None, None) => return Err(ApiError(StatusCode::BAD_REQUEST, "Either url or html is required".to_string())),
+    };
+
+    let convert_started = Instant::now();
+    let mut converter = MarkdownConverter::with_options(options);
+    if let Some(source_url) = source_url.clone() {
+        converter = converter.with_source_url(source_url);
+    }
+    let page = state.metrics.time_conversion(|| converter.convert_page(&html))
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let convert_time = convert_started.elapsed();
+
+    Ok(Json(ConvertResponse {
+        markdown: page.markdown,
+        title: page.metadata.title.clone(),
+        metadata: page.metadata,
+        links: page.links,
+        images: page.images,
+        url: source_url.map(String::from),
+        status: None,
+        content_type: None,
+        timing: Some(Timing {
+            fetch_ms: None,
+            convert_ms: millis(convert_time),
+            total_ms: millis(started.elapsed()),
+        }),
+    }).into_response())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The error for a failure of [`fetch_converted`]: an upstream failure, or
+/// an error converting the page.
+fn conversion_error(error: anyhow::Error) -> ApiError {
+    match error.downcast_ref::<FetchError>() {
+        Some(fetch_error) => ApiError(fetch_error_status(fetch_error), format!("Failed to fetch URL: {}", fetch_error)),
+        None => ApiError(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+    }
+}
+
+/// Malformed query parameters.
+fn query_error(rejection: QueryRejection) -> ApiError {
+    ApiError(rejection.status(), rejection.body_text())
+}
+
+/// The converter options in a query string, which may repeat list options.
+fn converter_args(query: Option<String>) -> Result<ConverterArgs, ApiError> {
+    ConverterArgs::from_query(query.as_deref().unwrap_or(""))
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid converter options: {}", e)))
+}
+
+/// Maps an upstream failure to the status returned to our client.
+fn fetch_error_status(error: &FetchError) -> StatusCode {
+    match error {
+        FetchError::Status { status: 404 | 410, .. } => StatusCode::NOT_FOUND,
+        FetchError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        FetchError::NotHtml { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        FetchError::Disallowed { .. } | FetchError::Blocked { .. } => StatusCode::FORBIDDEN,
+        FetchError::Status { .. }
+        | FetchError::Dns(_)
+        | FetchError::Connect(_)
+        | FetchError::Tls(_)
+        | FetchError::TooLarge { .. }
+        | FetchError::Request(_) => StatusCode::BAD_GATEWAY,
     }
 }
 
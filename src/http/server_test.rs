@@ -7,7 +7,7 @@ use tower::ServiceExt;
 
 #[tokio::test]
 async fn test_convert_url_success() {
-    let app = create_router();
+    let app = create_router(AppState::default());
 
     let response = app
         .oneshot(
@@ -28,7 +28,7 @@ async fn test_convert_url_success() {
 
 #[tokio::test]
 async fn test_convert_url_invalid_url() {
-    let app = create_router();
+    let app = create_router(AppState::default());
 
     let response = app
         .oneshot(
@@ -41,11 +41,14 @@ async fn test_convert_url_invalid_url() {
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["error"].as_str().unwrap().starts_with("Invalid URL"), "{}", json);
 }
 
 #[tokio::test]
 async fn test_convert_url_fetch_error() {
-    let app = create_router();
+    let app = create_router(AppState::default());
 
     let response = app
         .oneshot(
@@ -57,12 +60,12 @@ async fn test_convert_url_fetch_error() {
         .await
         .unwrap();
 
-    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
 }
 
 #[tokio::test]
 async fn test_convert_url_encoded() {
-    let app = create_router();
+    let app = create_router(AppState::default());
 
     let response = app
         .oneshot(
@@ -80,3 +83,647 @@ async fn test_convert_url_encoded() {
         "text/markdown; charset=utf-8"
     );
 }
+
+#[tokio::test]
+async fn test_convert_url_upstream_status() {
+    let mut server = mockito::Server::new_async().await;
+    let _missing = server.mock("GET", "/missing")
+        .with_status(404)
+        .create_async()
+        .await;
+    let _broken = server.mock("GET", "/broken")
+        .with_status(500)
+        .create_async()
+        .await;
+    let _image = server.mock("GET", "/image.png")
+        .with_header("content-type", "image/png")
+        .create_async()
+        .await;
+
+    for (path, expected) in [
+        ("missing", StatusCode::NOT_FOUND),
+        ("broken", StatusCode::BAD_GATEWAY),
+        ("image.png", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+    ] {
+        let response = create_router(AppState::default())
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/{}/{}", server.url(), path))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), expected, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_convert_url_blocked() {
+    let policy = crate::http::policy::UrlPolicy { block_private: true, ..Default::default() };
+    let fetcher = Fetcher::new(crate::http::FetchConfig { policy, ..Default::default() }).unwrap();
+    let state = AppState::new(fetcher);
+
+    for uri in [
+        "/http://127.0.0.1:9/",
+        "/http://169.254.169.254/latest/meta-data/",
+        "/http%3A%2F%2F%5B%3A%3A1%5D%2F",
+        "/file:///etc/passwd",
+    ] {
+        let response = create_router(state.clone())
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", uri);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&bytes).contains("is blocked"), "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_convert_url_cache() {
+    let mut server = mockito::Server::new_async().await;
+    let page = server.mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<html><body><main><p>Cached</p></main></body></html>")
+        .expect(2)
+        .create_async()
+        .await;
+    let state = AppState::default().with_cache(crate::http::cache::Cache::new(Default::default()));
+
+    let get = |uri: String| {
+        let state = state.clone();
+        async move {
+            let response = create_router(state)
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let cache = response.headers().get("x-cache").map(|value| value.to_str().unwrap().to_string());
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (cache, String::from_utf8(bytes.to_vec()).unwrap())
+        }
+    };
+
+    let uri = format!("/{}/page", server.url());
+    let (cache, first) = get(uri.clone()).await;
+    assert_eq!(cache.as_deref(), Some("MISS"));
+    let (cache, second) = get(uri.clone()).await;
+    assert_eq!(cache.as_deref(), Some("HIT"));
+    assert_eq!(first, second);
+
+    // Each format and set of options is a separate entry
+    let (cache, _) = get(format!("{}?format=text", uri)).await;
+    assert_eq!(cache.as_deref(), Some("MISS"));
+    let (cache, _) = get(format!("{}?format=text", uri)).await;
+    assert_eq!(cache.as_deref(), Some("HIT"));
+    page.assert_async().await;
+    let metrics = state.metrics.render();
+    assert!(metrics.contains("url2md_cache_requests_total{result=\"hit\"} 2\n"));
+    assert!(metrics.contains("url2md_cache_hit_ratio 0.5\n"));
+
+    // Without a cache there is no header
+    let response = create_router(AppState::default())
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert!(response.headers().get("x-cache").is_none());
+}
+
+#[tokio::test]
+async fn test_cached_json_has_timing_of_each_request() {
+    let mut server = mockito::Server::new_async().await;
+    let page = server.mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<html><body><main><p>Cached</p></main></body></html>")
+        .expect(2)
+        .create_async()
+        .await;
+    let state = AppState::default().with_cache(crate::http::cache::Cache::new(Default::default()));
+    let url = format!("{}/page", server.url());
+
+    let send = |request: Request<Body>| {
+        let state = state.clone();
+        async move {
+            let response = create_router(state).oneshot(request).await.unwrap();
+            let cache = response.headers().get("x-cache").map(|value| value.to_str().unwrap().to_string());
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (cache, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+        }
+    };
+    let get = || Request::builder().uri(format!("/{}?format=json", url)).body(Body::empty()).unwrap();
+    let post = || {
+        Request::builder()
+            .method("POST")
+            .uri("/api/convert")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::json!({ "url": url }).to_string()))
+            .unwrap()
+    };
+
+    for request in [&get as &dyn Fn() -> Request<Body>, &post] {
+        let (cache, first) = send(request()).await;
+        assert_eq!(cache.as_deref(), Some("MISS"));
+        assert!(first["timing"]["fetch_ms"].is_number());
+        let (cache, second) = send(request()).await;
+        assert_eq!(cache.as_deref(), Some("HIT"));
+        assert!(second["timing"]["fetch_ms"].is_null(), "{}", second);
+        assert_eq!(second["timing"]["convert_ms"], 0.0);
+        assert!(second["timing"]["total_ms"].is_number());
+        assert_eq!(first["markdown"], second["markdown"]);
+        assert_eq!(first["url"], second["url"]);
+    }
+    page.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_access_control() {
+    let state = AppState::default().with_access(crate::http::access::AccessConfig {
+        api_keys: vec!["secret".to_string()],
+        rate_limit: Some(crate::http::access::RateLimit::per_minute(1)),
+        ..Default::default()
+    });
+    let send = |request: Request<Body>| create_router(state.clone()).oneshot(request);
+
+    // Checked before the request is handled, so nothing is fetched
+    let response = send(Request::builder().uri("/not_a_valid_url").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get("www-authenticate").unwrap(), "Bearer");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["error"], "Missing or invalid API key");
+
+    let authorized = || {
+        Request::builder()
+            .uri("/not_a_valid_url")
+            .header("authorization", "Bearer secret")
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = send(authorized()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send(authorized()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers().get("retry-after").unwrap().to_str().unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+}
+
+#[tokio::test]
+async fn test_access_control_per_ip() {
+    let state = AppState::default().with_access(crate::http::access::AccessConfig {
+        rate_limit: Some(crate::http::access::RateLimit::per_minute(1)),
+        ..Default::default()
+    });
+    let send = |peer: &str| {
+        let mut request = Request::builder().uri("/not_a_valid_url").body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        create_router(state.clone()).oneshot(request)
+    };
+    assert_eq!(send("192.0.2.1:5000").await.unwrap().status(), StatusCode::BAD_REQUEST);
+    // Another connection from the same address shares the limit
+    assert_eq!(send("192.0.2.1:5001").await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(send("192.0.2.2:5000").await.unwrap().status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_cors_origins() {
+    let state = AppState::default().with_access(crate::http::access::AccessConfig {
+        api_keys: vec!["secret".to_string()],
+        cors_origins: vec![HeaderValue::from_static("https://app.example.com")],
+        ..Default::default()
+    });
+    let preflight = |origin: &str| {
+        Request::builder()
+            .method("OPTIONS")
+            .uri("/api/convert")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "authorization")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Preflight requests carry no key and are answered anyway
+    let response = create_router(state.clone()).oneshot(preflight("https://app.example.com")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("access-control-allow-origin").unwrap(), "https://app.example.com");
+
+    let response = create_router(state.clone()).oneshot(preflight("https://evil.example.com")).await.unwrap();
+    assert!(response.headers().get("access-control-allow-origin").is_none());
+
+    // Without configured origins, any origin is allowed
+    let response = create_router(AppState::default()).oneshot(preflight("https://evil.example.com")).await.unwrap();
+    assert_eq!(response.headers().get("access-control-allow-origin").unwrap(), "*");
+}
+
+#[tokio::test]
+async fn test_health_and_metrics_routes() {
+    let state = AppState::default().with_access(crate::http::access::AccessConfig {
+        api_keys: vec!["secret".to_string()],
+        ..Default::default()
+    });
+    let get = |uri: &str| {
+        create_router(state.clone()).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+    let text = |response: Response| async move {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    };
+
+    // Not taken for URLs to convert, and open without an API key
+    let response = get("/healthz").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(text(response).await, "ok");
+    let response = get("/readyz").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(get("/not_a_valid_url").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+    let response = get("/metrics").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("content-type").unwrap(), crate::http::metrics::CONTENT_TYPE);
+    let metrics = text(response).await;
+    assert!(metrics.contains("url2md_http_requests_total{route=\"/healthz\",status=\"200\"} 1\n"), "{}", metrics);
+    assert!(metrics.contains("url2md_http_requests_total{route=\"/*url\",status=\"401\"} 1\n"));
+
+    // Draining on shutdown
+    state.set_ready(false);
+    let response = get("/readyz").await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(get("/healthz").await.unwrap().status(), StatusCode::OK);
+}
+
+async fn post_json(body: &str) -> (StatusCode, serde_json::Value) {
+    let response = create_router(AppState::default())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/convert")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn test_api_convert_html() {
+    let request = serde_json::json!({
+        "html": "<html lang=\"en\"><head><title>Post</title></head><body><main>\
+                 <p>See <a href=\"/docs\">the docs</a>.</p><img src=\"logo.png\" alt=\"Logo\"></main></body></html>",
+        "url": "https://example.com/blog/",
+        "options": { "no_preamble": true, "link_style": "reference" },
+    });
+    let (status, json) = post_json(&request.to_string()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["markdown"].as_str().unwrap().starts_with("See [the docs][1]."));
+    assert_eq!(json["title"], "Post");
+    assert_eq!(json["metadata"]["language"], "en");
+    assert_eq!(json["links"], serde_json::json!(["https://example.com/docs"]));
+    assert_eq!(json["images"], serde_json::json!(["https://example.com/blog/logo.png"]));
+    assert_eq!(json["url"], "https://example.com/blog/");
+    assert!(json["status"].is_null());
+    assert!(json["timing"]["fetch_ms"].is_null());
+    assert!(json["timing"]["total_ms"].as_f64().unwrap() >= json["timing"]["convert_ms"].as_f64().unwrap());
+}
+
+#[tokio::test]
+async fn test_api_convert_url() {
+    let mut server = mockito::Server::new_async().await;
+    let _moved = server.mock("GET", "/old")
+        .with_status(301)
+        .with_header("location", "/new")
+        .create_async()
+        .await;
+    let _page = server.mock("GET", "/new")
+        .with_header("content-type", "text/html; charset=utf-8")
+        .with_body("<html><head><title>New</title></head><body><p><a href=\"a\">A</a></p></body></html>")
+        .create_async()
+        .await;
+
+    let request = serde_json::json!({ "url": format!("{}/old", server.url()) });
+    let (status, json) = post_json(&request.to_string()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["url"], format!("{}/new", server.url()));
+    assert_eq!(json["status"], 200);
+    assert_eq!(json["content_type"], "text/html; charset=utf-8");
+    assert_eq!(json["title"], "New");
+    assert_eq!(json["links"], serde_json::json!([format!("{}/a", server.url())]));
+    assert!(json["timing"]["fetch_ms"].is_number());
+}
+
+#[tokio::test]
+async fn test_convert_url_resolves_links_after_redirect() {
+    let mut server = mockito::Server::new_async().await;
+    let _moved = server.mock("GET", "/old")
+        .with_status(301)
+        .with_header("location", "/docs/guide")
+        .create_async()
+        .await;
+    let _page = server.mock("GET", "/docs/guide")
+        .with_header("content-type", "text/html")
+        .with_body("<html><body><main><p><a href=\"intro\">Intro</a></p></main></body></html>")
+        .create_async()
+        .await;
+
+    let uri = format!("/{}/old?no_preamble=true", server.url());
+    let response = create_router(AppState::default())
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(body.contains(&format!("[Intro]({}/docs/intro)", server.url())), "{}", body);
+}
+
+#[tokio::test]
+async fn test_api_convert_errors() {
+    let mut server = mockito::Server::new_async().await;
+    let _missing = server.mock("GET", "/missing")
+        .with_status(404)
+        .create_async()
+        .await;
+    let missing = serde_json::json!({ "url": format!("{}/missing", server.url()) }).to_string();
+
+    for (body, expected) in [
+        ("{}", StatusCode::BAD_REQUEST),
+        (r#"{"url": "not a url"}"#, StatusCode::BAD_REQUEST),
+        (r#"{"url": "ftp://example.com/"}"#, StatusCode::BAD_REQUEST),
+        (r#"{"html": "<p>x</p>", "options": {"bullet": "x"}}"#, StatusCode::BAD_REQUEST),
+        (r#"{"html": "<p>x</p>", "unknown": 1}"#, StatusCode::UNPROCESSABLE_ENTITY),
+        ("{not json", StatusCode::BAD_REQUEST),
+        (&missing, StatusCode::NOT_FOUND),
+    ] {
+        let (status, json) = post_json(body).await;
+        assert_eq!(status, expected, "{}", body);
+        assert!(json["error"].is_string(), "{}", body);
+    }
+}
+
+async fn post_html(uri: &str, content_type: &str, body: impl Into<Body>) -> (StatusCode, String) {
+    let response = create_router(AppState::default())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", content_type)
+                .body(body.into())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_convert_html_body() {
+    let html = "<html><body><main><p>Read <a href=\"guide\">the guide</a>.</p></main></body></html>";
+    let (status, markdown) = post_html(
+        "/api/convert/html?base_url=https%3A%2F%2Fcms.example.com%2Fdocs%2F&no_preamble=true",
+        "text/html",
+        html,
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(markdown.trim(), "Read [the guide](https://cms.example.com/docs/guide).");
+
+    // The declared charset is honored
+    let (status, markdown) = post_html(
+        "/api/convert/html?no_preamble=true",
+        "text/html; charset=windows-1252",
+        b"<p>Caf\xe9</p>".to_vec(),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(markdown.trim(), "Café");
+
+    // List options may be repeated
+    let (status, markdown) = post_html(
+        "/api/convert/html?no_preamble=true&include=h2&include=.note",
+        "text/html",
+        "<main><h2>Title</h2><p>Skipped</p><p class=\"note\">Kept</p></main>",
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(markdown.contains("Title") && markdown.contains("Kept"), "{}", markdown);
+    assert!(!markdown.contains("Skipped"), "{}", markdown);
+}
+
+#[tokio::test]
+async fn test_convert_html_multipart() {
+    let body = "--boundary\r\n\
+        Content-Disposition: form-data; name=\"base_url\"\r\n\r\n\
+        https://mail.example.com/\r\n\
+        --boundary\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"message.html\"\r\n\
+        Content-Type: text/html\r\n\r\n\
+        <p>Open <a href=\"/inbox\">your inbox</a></p>\r\n\
+        --boundary--\r\n";
+    let (status, markdown) = post_html(
+        "/api/convert/html?no_preamble=true",
+        "multipart/form-data; boundary=boundary",
+        body,
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(markdown.trim(), "Open [your inbox](https://mail.example.com/inbox)");
+
+    let body = "--boundary\r\nContent-Disposition: form-data; name=\"other\"\r\n\r\nx\r\n--boundary--\r\n";
+    let (status, _) = post_html("/api/convert/html", "multipart/form-data; boundary=boundary", body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_convert_html_errors() {
+    let (status, body) = post_html("/api/convert/html", "application/json", "{}").await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(body.contains("\"error\""));
+
+    let (status, _) = post_html("/api/convert/html?base_url=nope", "text/html", "<p>x</p>").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post_html("/api/convert/html?bullet=x", "text/html", "<p>x</p>").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_negotiate_output_format() {
+    let negotiate = OutputFormat::negotiate;
+    assert_eq!(negotiate(None), Some(OutputFormat::Markdown));
+    assert_eq!(negotiate(Some("*/*")), Some(OutputFormat::Markdown));
+    assert_eq!(negotiate(Some("text/*")), Some(OutputFormat::Markdown));
+    assert_eq!(negotiate(Some("application/json")), Some(OutputFormat::Json));
+    assert_eq!(negotiate(Some("text/plain, text/markdown;q=0.5")), Some(OutputFormat::Text));
+    assert_eq!(
+        negotiate(Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")),
+        Some(OutputFormat::Html)
+    );
+    assert_eq!(negotiate(Some("text/*;q=0.5, application/json")), Some(OutputFormat::Json));
+    assert_eq!(negotiate(Some("*/*, text/markdown;q=0")), Some(OutputFormat::Text));
+    assert_eq!(negotiate(Some("image/png")), None);
+}
+
+#[tokio::test]
+async fn test_convert_url_formats() {
+    let mut server = mockito::Server::new_async().await;
+    let _page = server.mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<html><head><title>Page</title></head><body><main><p>Hello <b>world</b></p></main></body></html>")
+        .create_async()
+        .await;
+
+    let get = |query: &'static str, accept: Option<&'static str>| {
+        let uri = format!("/{}/page{}", server.url(), query);
+        async move {
+            let mut request = Request::builder().uri(uri);
+            if let Some(accept) = accept {
+                request = request.header("accept", accept);
+            }
+            let response = create_router(AppState::default())
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = response.status();
+            let content_type = response.headers()
+                .get("content-type")
+                .map(|value| value.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            let vary = response.headers().get("vary").map(|value| value.to_str().unwrap().to_string());
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, content_type, vary, String::from_utf8(bytes.to_vec()).unwrap())
+        }
+    };
+
+    let (status, content_type, vary, body) = get("", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "text/markdown; charset=utf-8");
+    assert_eq!(vary.as_deref(), Some("accept"));
+    assert!(body.contains("Hello **world**"));
+
+    let (_, content_type, _, body) = get("?format=text", Some("application/json")).await;
+    assert_eq!(content_type, "text/plain; charset=utf-8");
+    assert_eq!(body, "Hello world\n");
+
+    let (_, content_type, _, body) = get("", Some("text/html")).await;
+    assert_eq!(content_type, "text/html; charset=utf-8");
+    assert!(body.contains("<main><p>Hello <b>world</b></p></main>"));
+
+    let (_, content_type, _, body) = get("?no_preamble=true", Some("application/json")).await;
+    assert_eq!(content_type, "application/json");
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["title"], "Page");
+    assert_eq!(json["status"], 200);
+    assert!(json["markdown"].as_str().unwrap().starts_with("Hello **world**"));
+
+    // Errors are JSON, like those of the API routes
+    let (status, content_type, _, body) = get("", Some("image/png")).await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(content_type, "application/json");
+    assert!(serde_json::from_str::<serde_json::Value>(&body).unwrap()["error"].is_string(), "{}", body);
+    let (status, content_type, _, body) = get("?format=pdf", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(content_type, "application/json");
+    assert!(serde_json::from_str::<serde_json::Value>(&body).unwrap()["error"].is_string(), "{}", body);
+}
+
+#[tokio::test]
+async fn test_convert_url_html_is_inert() {
+    let mut server = mockito::Server::new_async().await;
+    let _page = server.mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<html><body><main><p>Hi</p><script>alert(document.cookie)</script></main></body></html>")
+        .create_async()
+        .await;
+
+    let uri = format!("/{}/page?format=html&keep_tag=script", server.url());
+    let response = create_router(AppState::default())
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-security-policy"], "sandbox");
+    assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(body.contains("<p>Hi</p>"));
+    assert!(!body.contains("<script>"));
+}
+
+/// Serves a router whose `/slow` answers after `delay` on a free port,
+/// until the returned sender is used or dropped.
+fn start_server(
+    delay: Duration,
+    config: crate::http::ServeConfig,
+) -> (String, tokio::sync::oneshot::Sender<()>, tokio::task::JoinHandle<anyhow::Result<()>>) {
+    let app = Router::new().route(
+        "/slow",
+        get(move || async move {
+            tokio::time::sleep(delay).await;
+            "done"
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let scheme = if config.tls.is_some() { "https" } else { "http" };
+    let url = format!("{}://{}/slow", scheme, listener.local_addr().unwrap());
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        serve(listener, app, &config, async {
+            let _ = stopped.await;
+        })
+        .await
+    });
+    (url, stop, server)
+}
+
+/// Gets `url` on a connection of its own, closed once the body is read.
+async fn get_text(url: String) -> reqwest::Result<String> {
+    let client = reqwest::Client::builder().no_proxy().danger_accept_invalid_certs(true).build()?;
+    client.get(url).send().await?.text().await
+}
+
+#[tokio::test]
+async fn test_serve_drains_requests_on_shutdown() {
+    let (url, stop, server) = start_server(Duration::from_millis(300), crate::http::ServeConfig::default());
+    let request = tokio::spawn(get_text(url));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    stop.send(()).unwrap();
+
+    assert_eq!(request.await.unwrap().unwrap(), "done");
+    let result = tokio::time::timeout(Duration::from_secs(5), server).await.expect("server did not stop");
+    result.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_serve_shutdown_timeout() {
+    let config = crate::http::ServeConfig { shutdown_timeout: Duration::from_millis(200), ..Default::default() };
+    let (url, stop, server) = start_server(Duration::from_secs(30), config);
+    let request = tokio::spawn(get_text(url));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    stop.send(()).unwrap();
+
+    // The request is cut off instead of holding up the shutdown
+    let result = tokio::time::timeout(Duration::from_secs(5), server).await.expect("server did not stop");
+    result.unwrap().unwrap();
+    assert!(request.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn test_serve_tls() {
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls");
+    let tls = crate::http::TlsFiles { cert: fixtures.join("cert.pem"), key: fixtures.join("key.pem") };
+    let config = crate::http::ServeConfig { tls: Some(tls), ..Default::default() };
+    let (url, stop, server) = start_server(Duration::ZERO, config);
+    assert!(url.starts_with("https://"));
+    assert_eq!(get_text(url).await.unwrap(), "done");
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+
+    // Unreadable files are reported before listening
+    let tls = crate::http::TlsFiles { cert: fixtures.join("missing.pem"), key: fixtures.join("key.pem") };
+    let config = crate::http::ServeConfig { tls: Some(tls), ..Default::default() };
+    let (_, _stop, server) = start_server(Duration::ZERO, config);
+    let error = server.await.unwrap().unwrap_err();
+    assert!(error.to_string().contains("failed to load TLS certificate"), "{:#}", error);
+}
@@ -2,16 +2,20 @@ mod cli;
 mod converter;
 mod http;
 
-use std::net::SocketAddr;
-use tower_http::cors::{CorsLayer, Any};
+use std::sync::Arc;
 use tracing::info;
-use anyhow::Result;
-use tokio::net::TcpListener;
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 
-use crate::cli::Args;
-use crate::converter::markdown_converter::MarkdownConverter;
-use crate::http::{fetch_html, save_to_file};
+use crate::cli::batch::{read_input_list, Conversion};
+use crate::cli::args::Command;
+use crate::cli::crawl::{parse_patterns, CrawlScope, Crawler};
+use crate::cli::input::Input;
+use crate::cli::sitemap::{read_sitemaps, SitemapFilter};
+use crate::cli::{Args, ConfigFile};
+use crate::http::cache::{Cache, CacheArgs};
+use crate::http::server::{shutdown_signal, AppState};
+use crate::http::{save_to_file, FetchConfig, Fetcher};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -22,38 +26,117 @@ async fn main() -> Result<()> {
 
     // Parse command line arguments
     let args = Args::parse();
+    let config_file = ConfigFile::load(args.config.as_deref())?;
+    let fetch_args = args.fetch.clone().or(config_file.fetch);
+    let fetch_config = fetch_args.to_config()?;
+    let cache_args = args.cache.clone().or(config_file.cache);
 
-    if args.url.is_none() {
-        // Start API server mode
-        let app = http::server::create_router()
-            .layer(
-                CorsLayer::new()
-                    .allow_origin(Any)
-                    .allow_methods(Any)
-                    .allow_headers(Any),
-            );
-
-        let addr = SocketAddr::from(([127, 0, 0, 1], args.port));
-        let listener = TcpListener::bind(addr).await?;
-        info!("API server listening on http://localhost:{}", args.port);
-        
-        axum::serve(listener, app.into_make_service())
+    if let Some(Command::Crawl(crawl)) = &args.command {
+        let conversion = cli_conversion(&args, fetch_config, &cache_args)?;
+        let scope = CrawlScope {
+            max_depth: crawl.max_depth,
+            max_pages: usize::try_from(crawl.max_pages).unwrap_or(usize::MAX),
+            include: parse_patterns(&crawl.include_urls)?,
+            exclude: parse_patterns(&crawl.exclude_urls)?,
+        };
+        let summary = Crawler::new(conversion, scope, crawl.concurrency.into())
+            .crawl(crawl.url.clone(), &crawl.output_dir)
             .await?;
-    } else {
+        eprintln!("{}", summary);
+        if !summary.failures.is_empty() {
+            bail!("{} pages failed", summary.failures.len());
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Sitemap(sitemap)) = &args.command {
+        let conversion = cli_conversion(&args, fetch_config, &cache_args)?;
+        let filter = SitemapFilter {
+            include: parse_patterns(&sitemap.include_urls)?,
+            exclude: parse_patterns(&sitemap.exclude_urls)?,
+            since: sitemap.since,
+            max_pages: sitemap.max_pages.map(|max| usize::try_from(max).unwrap_or(usize::MAX)),
+        };
+        let pages = read_sitemaps(&conversion.fetcher, &sitemap.url).await?;
+        let inputs: Vec<Input> = filter.select(&pages.entries).into_iter().map(Input::Url).collect();
+        info!("Converting {} of {} pages listed in sitemaps", inputs.len(), pages.entries.len());
+        let summary = conversion.run_batch(inputs, &sitemap.output_dir, sitemap.concurrency.into()).await?;
+        eprintln!("{}", summary);
+        for (url, error) in &pages.failures {
+            eprintln!("Failed to read sitemap {}: {:#}", url, error);
+        }
+        if !summary.failures.is_empty() || !pages.failures.is_empty() {
+            bail!("{} conversions and {} sitemaps failed", summary.failures.len(), pages.failures.len());
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Serve(server)) = &args.command {
+        let server_args = server.clone().or(config_file.server);
+        let serve_config = server_args.to_serve_config()?;
+        let mut state = AppState::new(Fetcher::new(fetch_args.to_server_config()?)?)
+            .with_access(server_args.to_access_config()?);
+        // Results are kept in memory, and on disk only with --cache-dir
+        if let Some(cache_config) = cache_args.to_config()? {
+            state = state.with_cache(Cache::new(cache_config));
+        }
+        let app = http::server::create_router(state.clone());
+        let listener = std::net::TcpListener::bind(serve_config.addr)
+            .with_context(|| format!("failed to listen on {}", serve_config.addr))?;
+        let shutdown = async move {
+            shutdown_signal().await;
+            state.set_ready(false);
+        };
+        return http::server::serve(listener, app, &serve_config, shutdown).await;
+    }
+
+    let batch_mode = args.inputs.len() > 1 || args.input_file.is_some() || args.output_dir.is_some();
+    if batch_mode || !args.inputs.is_empty() {
         // Handle CLI mode
-        let url = args.url.unwrap();
-        let html = fetch_html(&url).await?;
-        let converter = MarkdownConverter::new();
-        let markdown = converter.convert(&html)?;
-        
+        let conversion = cli_conversion(&args, fetch_config, &cache_args)?;
+
+        if batch_mode {
+            let mut inputs = args.inputs.clone();
+            if let Some(input_file) = &args.input_file {
+                inputs.extend(read_input_list(input_file).await?);
+            }
+            let Some(output_dir) = args.output_dir.as_deref() else {
+                bail!("converting several inputs requires --output-dir");
+            };
+            let summary = conversion.run_batch(inputs, output_dir, args.concurrency.into()).await?;
+            eprintln!("{}", summary);
+            if !summary.failures.is_empty() {
+                bail!("{} of {} conversions failed", summary.failures.len(), summary.total);
+            }
+            return Ok(());
+        }
+
+        let markdown = conversion.convert(&args.inputs[0]).await?;
         if let Some(output_path) = args.output.as_deref() {
             save_to_file(&markdown, output_path).await?;
             info!("Saved markdown to {}", output_path);
         } else {
             println!("{}", markdown);
         }
-        
+    } else {
+        bail!("nothing to convert: pass a URL, a file or '-', or run `url2md serve` to start the API server");
     }
 
     Ok(())
 }
+
+/// The fetcher, converter and cache settings shared by the CLI modes.
+fn cli_conversion(args: &Args, fetch_config: FetchConfig, cache_args: &CacheArgs) -> Result<Conversion> {
+    let cache = cache_args.to_config()?.map(|config| Arc::new(Cache::new(config)));
+    Ok(Conversion {
+        fetcher: Fetcher::new(FetchConfig {
+            fail_on_status: !args.allow_error_status,
+            encoding: args.encoding,
+            ..fetch_config
+        })?,
+        options: args.converter.to_options()?,
+        base_url: args.base_url.clone(),
+        encoding: args.encoding,
+        cache,
+    })
+}